@@ -0,0 +1,224 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Result};
+use tokio::signal::{
+    self,
+    unix::{signal as unix_signal, SignalKind},
+};
+use tutti_daemon::DaemonRunner;
+use tutti_transport::{api::TuttiApi, client::ipc_client::IpcClient, error::TransportError};
+
+use crate::{
+    project::{config_path, load_project},
+    terminal::{self, RawMode},
+    DEFAULT_SYSTEM_DIR,
+};
+
+pub async fn attach(
+    file: Option<String>,
+    service: String,
+    detach_keys: String,
+    system_directory: Option<String>,
+) -> Result<()> {
+    let mut detach = DetachKeys::parse(&detach_keys)?;
+    let system_directory =
+        system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
+    let daemon_runner = DaemonRunner::new(system_directory);
+
+    let project = load_project(&config_path(file)?)?;
+    let project_id = project.id.clone();
+    let Some(config) = project.services.get(&service) else {
+        bail!("unknown service `{service}`");
+    };
+    let tty = config.tty;
+
+    let mut client = match IpcClient::new(daemon_runner.socket_path()).await {
+        Ok(client) => client,
+        Err(err) => bail!("failed to connect to the daemon: {err:?}"),
+    };
+    let Ok(mut events) = client.subscribe().await else {
+        bail!("failed to subscribe to the output of the project");
+    };
+
+    // Sending nothing finds out whether the service takes input before the terminal is taken
+    // over.
+    match client
+        .input(project_id.clone(), service.clone(), Vec::new())
+        .await
+    {
+        Ok(()) => {}
+        Err(TransportError::Rejected(message)) => bail!(message),
+        Err(err) => bail!("failed to attach to {service}: {err:?}"),
+    }
+
+    let mut window_changes = unix_signal(SignalKind::window_change())?;
+    if tty {
+        terminal::send_window_size(&mut client, &project_id).await;
+    }
+
+    // Only a service on a pseudo-terminal gets the key presses as they are typed, with its own
+    // terminal handling them. Anything else reads lines, so our terminal stays in line mode and
+    // Ctrl-C detaches as well.
+    let raw_mode = if tty {
+        eprintln!("Attached to {service}, press {detach_keys} to detach");
+        RawMode::enable()
+    } else {
+        eprintln!("Attached to {service}, press {detach_keys} or Ctrl-C to detach");
+        None
+    };
+    let mut input = terminal::read_stdin();
+    let mut stdout = io::stdout();
+
+    // Detaching, or the service stopping, ends the session; losing the daemon is an error.
+    let end = loop {
+        tokio::select! {
+            data = input.recv() => {
+                let Some(data) = data else {
+                    break Ok(format!("Detached from {service}"));
+                };
+                let (data, detached) = detach.feed(&data);
+                if !data.is_empty() {
+                    match client.input(project_id.clone(), service.clone(), data).await {
+                        Ok(()) => {}
+                        // The service may be restarting; the input is lost but we stay attached.
+                        Err(TransportError::Rejected(message)) => eprintln!("{message}"),
+                        Err(err) => break Err(format!("lost the connection to the daemon: {err:?}")),
+                    }
+                }
+                if detached {
+                    break Ok(format!("Detached from {service}"));
+                }
+            }
+
+            _ = signal::ctrl_c() => break Ok(format!("Detached from {service}")),
+
+            _ = window_changes.recv(), if tty => {
+                terminal::send_window_size(&mut client, &project_id).await;
+            }
+
+            message = events.recv() => match message.map(|message| message.body) {
+                Some(TuttiApi::Log { project_id: id, service: name, message, .. })
+                    if id == project_id && name == service =>
+                {
+                    let _ = stdout.write_all(message.as_bytes());
+                    let _ = stdout.flush();
+                }
+                Some(TuttiApi::ServiceStopped { project_id: id, service: name })
+                    if id == project_id && name == service =>
+                {
+                    break Ok(format!("Service stopped: {service}"));
+                }
+                Some(TuttiApi::ProjectStopped { project_id: id }) if id == project_id => {
+                    break Ok("All services stopped".to_owned());
+                }
+                Some(_) => {}
+                None => break Err("lost the connection to the daemon".to_owned()),
+            },
+        }
+    };
+
+    drop(raw_mode);
+    match end {
+        Ok(reason) => {
+            eprintln!("{reason}");
+            Ok(())
+        }
+        Err(err) => bail!(err),
+    }
+}
+
+/// Watches the input for the key sequence that detaches from the service.
+#[derive(Debug)]
+struct DetachKeys {
+    keys: Vec<u8>,
+    /// How many keys of the sequence were typed last. They are held back until it is clear
+    /// whether the sequence is completed.
+    matched: usize,
+}
+
+impl DetachKeys {
+    /// Parse a comma-separated list of keys such as `ctrl-p,ctrl-q`. A key is a single
+    /// character or `ctrl-` followed by a letter or one of `@[\]^_`.
+    fn parse(keys: &str) -> Result<Self> {
+        let keys = keys
+            .split(',')
+            .map(|key| {
+                let mut chars = key.strip_prefix("ctrl-").unwrap_or(key).chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if key.starts_with("ctrl-") => {
+                        let c = c.to_ascii_uppercase();
+                        if ('@'..='_').contains(&c) {
+                            return Ok(c as u8 & 0x1f);
+                        }
+                    }
+                    (Some(c), None) if c.is_ascii() => return Ok(c as u8),
+                    _ => {}
+                }
+                bail!("invalid detach key `{key}`, expected a character or `ctrl-<key>`")
+            })
+            .collect::<Result<Vec<u8>>>()?;
+        Ok(Self { keys, matched: 0 })
+    }
+
+    /// Pass `input` through, except for the detach sequence. Returns the input to send on and
+    /// whether the sequence was completed, in which case the rest of the input is dropped.
+    fn feed(&mut self, input: &[u8]) -> (Vec<u8>, bool) {
+        let mut output = Vec::new();
+        for &byte in input {
+            if byte != self.keys[self.matched] {
+                // Not the sequence after all: the held back keys were meant for the service.
+                output.extend_from_slice(&self.keys[..self.matched]);
+                self.matched = 0;
+            }
+            if byte == self.keys[self.matched] {
+                self.matched += 1;
+                if self.matched == self.keys.len() {
+                    self.matched = 0;
+                    return (output, true);
+                }
+            } else {
+                output.push(byte);
+            }
+        }
+        (output, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_detach_keys() {
+        assert_eq!(
+            DetachKeys::parse("ctrl-p,ctrl-q").unwrap().keys,
+            vec![0x10, 0x11]
+        );
+        assert_eq!(DetachKeys::parse("ctrl-@,q").unwrap().keys, vec![0, b'q']);
+        assert_eq!(
+            DetachKeys::parse("ctrl-").unwrap_err().to_string(),
+            "invalid detach key `ctrl-`, expected a character or `ctrl-<key>`"
+        );
+        assert!(DetachKeys::parse("ctrl-1").is_err());
+        assert!(DetachKeys::parse("ab").is_err());
+        assert!(DetachKeys::parse("").is_err());
+    }
+
+    #[test]
+    fn test_detach_keys_feed() {
+        let mut detach = DetachKeys::parse("ctrl-p,ctrl-q").unwrap();
+        assert_eq!(detach.feed(b"ls\r"), (b"ls\r".to_vec(), false));
+        assert_eq!(detach.feed(b"a\x10\x11b"), (b"a".to_vec(), true));
+
+        // A sequence split across reads is still recognised.
+        assert_eq!(detach.feed(b"\x10"), (vec![], false));
+        assert_eq!(detach.feed(b"\x11"), (vec![], true));
+
+        // An incomplete sequence is passed on once it is broken.
+        assert_eq!(detach.feed(b"\x10x"), (b"\x10x".to_vec(), false));
+        assert_eq!(detach.feed(b"\x10\x10\x11"), (b"\x10".to_vec(), true));
+    }
+}
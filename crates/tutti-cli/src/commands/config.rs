@@ -0,0 +1,204 @@
+use anyhow::Result;
+use serde_json::Value;
+use tutti_types::Project;
+
+use crate::{
+    config::PrintFormat,
+    project::{config_path, load_project},
+};
+
+pub fn config_validate(file: Option<String>) -> Result<()> {
+    let path = config_path(file)?;
+    let project = load_project(&path)?;
+
+    println!(
+        "{}: ok ({} service(s))",
+        path.display(),
+        project.services.len()
+    );
+
+    Ok(())
+}
+
+pub fn config_print(file: Option<String>, format: PrintFormat) -> Result<()> {
+    let path = config_path(file)?;
+    let project = load_project(&path)?;
+
+    println!("{}", render(&project, format)?.trim_end());
+
+    Ok(())
+}
+
+/// Render the resolved configuration in a form the loader accepts again, with keys sorted so that
+/// the output of the same file is always the same.
+fn render(project: &Project, format: PrintFormat) -> Result<String> {
+    // Maps of a `Value` are sorted, unlike the environment of a service.
+    let mut value = serde_json::to_value(project)?;
+    if let Value::Object(project) = &mut value {
+        // The id is the path of the file, which the loader derives rather than reads.
+        project.remove("id");
+        if let Some(Value::Object(services)) = project.get_mut("services") {
+            for service in services.values_mut() {
+                if let Some(inherit_env) = service.get_mut("inherit_env") {
+                    *inherit_env = raw_inherit_env(inherit_env.take());
+                }
+            }
+        }
+    }
+    prune(&mut value);
+
+    Ok(match format {
+        PrintFormat::Toml => toml::to_string_pretty(&value)?,
+        PrintFormat::Json => serde_json::to_string_pretty(&value)?,
+    })
+}
+
+/// `inherit_env` as written in a configuration file: `true`, `false` or a list of names. The
+/// default of inheriting everything is left out.
+fn raw_inherit_env(inherit_env: Value) -> Value {
+    match inherit_env {
+        Value::String(all) if all == "all" => Value::Null,
+        Value::String(_) => Value::Bool(false),
+        Value::Object(mut only) => only.remove("only").unwrap_or(Value::Null),
+        other => other,
+    }
+}
+
+/// Drop unset values and empty lists and tables, which are the defaults.
+fn prune(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.values_mut().for_each(prune);
+            map.retain(|_, value| !is_empty(value));
+        }
+        Value::Array(values) => values.iter_mut().for_each(prune),
+        _ => {}
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(values) => values.is_empty(),
+        Value::Object(map) => map.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        path::PathBuf,
+    };
+
+    use tutti_types::{Hooks, InheritEnv, LogFormat, ProjectId, Restart, Service};
+
+    use super::*;
+
+    fn project() -> Project {
+        let service = |cmd: &str| Service {
+            cmd: vec![cmd.to_owned()],
+            cwd: Some(PathBuf::from("/srv/app")),
+            env: None,
+            inherit_env: InheritEnv::All,
+            profiles: vec![],
+            deps: vec![],
+            hooks: Hooks::default(),
+            user: None,
+            group: None,
+            umask: None,
+            nice: None,
+            rlimits: BTreeMap::new(),
+            tty: false,
+            stdin_open: false,
+            log_format: LogFormat::Text,
+            max_memory: None,
+            max_cpu_percent: None,
+            limit_grace_period: None,
+            healthcheck: None,
+            restart: Restart::Never,
+        };
+        Project {
+            version: 1,
+            id: ProjectId(PathBuf::from("/srv/app/tutti.toml")),
+            services: BTreeMap::from([
+                (
+                    "api".to_owned(),
+                    Service {
+                        env: Some(HashMap::from(
+                            [("PORT", "8080"), ("DEBUG", "1"), ("RUST_LOG", "info")]
+                                .map(|(key, value)| (key.to_owned(), value.to_owned())),
+                        )),
+                        inherit_env: InheritEnv::Only(vec!["PATH".to_owned()]),
+                        deps: vec!["db".to_owned()],
+                        ..service("api")
+                    },
+                ),
+                (
+                    "db".to_owned(),
+                    Service {
+                        inherit_env: InheritEnv::None,
+                        ..service("postgres")
+                    },
+                ),
+            ]),
+            groups: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_toml() {
+        assert_eq!(
+            render(&project(), PrintFormat::Toml).unwrap(),
+            r#"version = 1
+
+[services.api]
+cmd = ["api"]
+cwd = "/srv/app"
+deps = ["db"]
+inherit_env = ["PATH"]
+restart = "never"
+
+[services.api.env]
+DEBUG = "1"
+PORT = "8080"
+RUST_LOG = "info"
+
+[services.db]
+cmd = ["postgres"]
+cwd = "/srv/app"
+inherit_env = false
+restart = "never"
+"#
+        );
+    }
+
+    #[test]
+    fn test_render_json() {
+        let output = render(&project(), PrintFormat::Json).unwrap();
+        let value: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "version": 1,
+                "services": {
+                    "api": {
+                        "cmd": ["api"],
+                        "cwd": "/srv/app",
+                        "deps": ["db"],
+                        "env": {"DEBUG": "1", "PORT": "8080", "RUST_LOG": "info"},
+                        "inherit_env": ["PATH"],
+                        "restart": "never"
+                    },
+                    "db": {
+                        "cmd": ["postgres"],
+                        "cwd": "/srv/app",
+                        "inherit_env": false,
+                        "restart": "never"
+                    }
+                }
+            })
+        );
+    }
+}
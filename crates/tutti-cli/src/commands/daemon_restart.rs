@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use tutti_daemon::DaemonRunner;
+
+use super::daemon_stop::shutdown_daemon;
+use crate::DEFAULT_SYSTEM_DIR;
+
+pub async fn daemon_restart(system_directory: Option<String>) -> Result<()> {
+    let system_directory =
+        system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
+
+    let daemon_runner = DaemonRunner::new(system_directory);
+    shutdown_daemon(&daemon_runner).await?;
+
+    if let Err(err) = daemon_runner.prepare() {
+        bail!("failed to prepare the daemon: {err}");
+    }
+    if let Err(err) = daemon_runner.spawn() {
+        bail!("failed to start the daemon: {err}");
+    }
+    println!("Daemon restarted");
+
+    Ok(())
+}
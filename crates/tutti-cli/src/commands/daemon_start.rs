@@ -1,8 +1,7 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use tutti_daemon::DaemonRunner;
-use tutti_transport::client::ipc_client::IpcClient;
 
 use crate::DEFAULT_SYSTEM_DIR;
 
@@ -11,21 +10,12 @@ pub async fn daemon_start(system_directory: Option<String>) -> Result<()> {
         system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from),
     );
 
-    if !IpcClient::check_socket(&daemon_runner.socket_path()).await
-        && daemon_runner.clear().is_err()
-    {
-        println!("Failed to clear daemon");
-        return Ok(());
+    if let Err(err) = daemon_runner.prepare() {
+        bail!("failed to prepare the daemon: {err}");
     }
 
-    if daemon_runner.prepare().is_err() {
-        println!("Failed to prepare daemon");
-        return Ok(());
-    }
-
-    if daemon_runner.start().await.is_err() {
-        println!("Failed to start daemon");
-        return Ok(());
+    if let Err(err) = daemon_runner.start().await {
+        bail!("failed to start the daemon: {err}");
     }
 
     Ok(())
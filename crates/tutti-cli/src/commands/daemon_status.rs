@@ -0,0 +1,72 @@
+use std::{fmt::Write as _, path::PathBuf, time::Duration};
+
+use anyhow::{bail, Result};
+use tutti_daemon::DaemonRunner;
+use tutti_transport::client::ipc_client::IpcClient;
+use tutti_types::DaemonStatus;
+
+use crate::DEFAULT_SYSTEM_DIR;
+
+pub async fn daemon_status(system_directory: Option<String>) -> Result<()> {
+    let system_directory =
+        system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
+    let daemon_runner = DaemonRunner::new(system_directory);
+
+    let Ok(mut client) = IpcClient::new(daemon_runner.socket_path()).await else {
+        bail!("the daemon is not running");
+    };
+    let status = match client.daemon_status().await {
+        Ok(status) => status,
+        Err(err) => bail!("failed to get the status of the daemon: {err:?}"),
+    };
+    print!("{}", render(&status));
+
+    Ok(())
+}
+
+fn render(status: &DaemonStatus) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "pid       {}", status.pid);
+    let _ = writeln!(out, "version   {}", status.version);
+    let _ = writeln!(
+        out,
+        "uptime    {}",
+        humantime::format_duration(Duration::from_secs(status.uptime))
+    );
+    if status.projects.is_empty() {
+        let _ = writeln!(out, "projects  none");
+    }
+    for (i, project) in status.projects.iter().enumerate() {
+        let label = if i == 0 { "projects" } else { "" };
+        let _ = writeln!(out, "{label:<10}{project}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use tutti_types::ProjectId;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut status = DaemonStatus {
+            pid: 42,
+            version: "0.1.5".to_owned(),
+            uptime: 3725,
+            projects: vec![],
+        };
+        assert_eq!(
+            render(&status),
+            "pid       42\nversion   0.1.5\nuptime    1h 2m 5s\nprojects  none\n"
+        );
+
+        status.projects = vec![
+            ProjectId(PathBuf::from("/work/api/tutti.toml")),
+            ProjectId(PathBuf::from("/work/web/tutti.toml")),
+        ];
+        assert!(render(&status)
+            .ends_with("projects  /work/api/tutti.toml\n          /work/web/tutti.toml\n"));
+    }
+}
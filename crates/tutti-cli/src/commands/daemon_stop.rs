@@ -1,28 +1,49 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use tokio::time::Instant;
 use tutti_daemon::DaemonRunner;
 use tutti_transport::client::ipc_client::IpcClient;
 
 use crate::DEFAULT_SYSTEM_DIR;
 
+/// How long the daemon may take to stop the services and exit.
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub async fn daemon_stop(system_directory: Option<String>) -> Result<()> {
     let system_directory =
         system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
 
     let daemon_runner = DaemonRunner::new(system_directory);
+    if !shutdown_daemon(&daemon_runner).await? {
+        println!("The daemon is not running");
+    }
 
-    let mut client = match IpcClient::new(daemon_runner.socket_path()).await {
-        Ok(client) => client,
-        Err(err) => {
-            println!("Failed to connect to the daemon: {err:?}");
-            return Ok(());
-        }
-    };
+    Ok(())
+}
 
-    if client.shutdown().await.is_err() {
-        println!("Failed to start project");
+/// Ask the daemon to stop and wait until it has exited. Returns `false` if it was not running.
+///
+/// # Errors
+/// Returns an error if the daemon does not exit in time.
+pub async fn shutdown_daemon(daemon_runner: &DaemonRunner) -> Result<bool> {
+    let Ok(mut client) = IpcClient::new(daemon_runner.socket_path()).await else {
+        return Ok(false);
+    };
+    if let Err(err) = client.shutdown().await {
+        bail!("failed to stop the daemon: {err:?}");
     }
 
-    Ok(())
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while IpcClient::check_socket(&daemon_runner.socket_path()).await {
+        if Instant::now() >= deadline {
+            bail!(
+                "the daemon did not stop within {}",
+                humantime::format_duration(STOP_TIMEOUT)
+            );
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    Ok(true)
 }
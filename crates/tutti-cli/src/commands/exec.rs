@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use futures::StreamExt;
+use tokio::{
+    io::AsyncWriteExt,
+    signal::{
+        self,
+        unix::{signal as unix_signal, SignalKind},
+    },
+};
+use tutti_core::{service_env, CommandSpec, ProcessManager, UnixProcessManager};
+use tutti_daemon::DaemonRunner;
+use tutti_transport::{api::TuttiApi, client::ipc_client::IpcClient, error::TransportError};
+use tutti_types::{ProjectId, Service};
+
+use crate::{
+    project::{client_env, config_path, load_project},
+    terminal::{self, RawMode},
+    DEFAULT_SYSTEM_DIR,
+};
+
+/// Run `cmd` with the directory, environment and process settings of `service`, and return its
+/// exit code.
+pub async fn exec(
+    file: Option<String>,
+    service: String,
+    cmd: Vec<String>,
+    daemon: bool,
+    system_directory: Option<String>,
+) -> Result<i32> {
+    let project = load_project(&config_path(file)?)?;
+    let Some(config) = project.services.get(&service).cloned() else {
+        bail!("unknown service `{service}`");
+    };
+
+    if daemon {
+        let system_directory =
+            system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
+        exec_in_daemon(
+            &DaemonRunner::new(system_directory),
+            project.id,
+            service,
+            config,
+            cmd,
+        )
+        .await
+    } else {
+        exec_locally(&service, &config, &cmd, &client_env()).await
+    }
+}
+
+/// Run the command as a child of this process. If we are on a terminal, the command gets a
+/// pseudo-terminal of its own, so it can be interactive.
+async fn exec_locally(
+    service: &str,
+    config: &Service,
+    cmd: &[String],
+    env: &HashMap<String, String>,
+) -> Result<i32> {
+    let tty = io::stdin().is_terminal() && io::stdout().is_terminal();
+    let spec = CommandSpec {
+        tty,
+        window_size: terminal::size().filter(|_| tty),
+        stdin: true,
+        ..CommandSpec::for_service(
+            config,
+            format!("{service}:exec"),
+            cmd,
+            service_env(config, env),
+        )
+    };
+
+    let mut process_manager = UnixProcessManager::new();
+    let process = process_manager.spawn(spec).await?;
+    let id = process.id;
+    let mut stdin = process.stdin;
+    let mut output = futures::stream::select(
+        process.stdout.map(|data| (data, false)),
+        process.stderr.map(|data| (data, true)),
+    );
+
+    let mut window_changes = unix_signal(SignalKind::window_change())?;
+    let raw_mode = if tty { RawMode::enable() } else { None };
+    let mut input = terminal::read_stdin();
+
+    loop {
+        tokio::select! {
+            chunk = output.next() => {
+                let Some((data, stderr)) = chunk else {
+                    break;
+                };
+                write_output(&data, stderr);
+            }
+
+            data = input.recv(), if stdin.is_some() => {
+                let written = match (data, stdin.as_mut()) {
+                    (Some(data), Some(writer)) => {
+                        writer.write_all(&data).await.is_ok() && writer.flush().await.is_ok()
+                    }
+                    _ => false,
+                };
+                // Closing stdin tells the command its input has ended.
+                if !written {
+                    stdin = None;
+                }
+            }
+
+            _ = window_changes.recv(), if tty => {
+                if let Some(size) = terminal::size() {
+                    let _ = process_manager.resize(id, size).await;
+                }
+            }
+
+            // The command runs in a session of its own, so Ctrl-C on our terminal does not
+            // reach it.
+            _ = signal::ctrl_c() => {
+                let _ = process_manager.shutdown(id).await;
+            }
+        }
+    }
+    drop(raw_mode);
+
+    Ok(process_manager
+        .wait(id, Duration::MAX)
+        .await?
+        .unwrap_or_default())
+}
+
+/// Run the command in the daemon and stream its output back.
+async fn exec_in_daemon(
+    daemon_runner: &DaemonRunner,
+    project_id: ProjectId,
+    service: String,
+    config: Service,
+    cmd: Vec<String>,
+) -> Result<i32> {
+    let mut client = match IpcClient::new(daemon_runner.socket_path()).await {
+        Ok(client) => client,
+        Err(err) => bail!("Failed to connect to the daemon: {err:?}"),
+    };
+    let Ok(mut events) = client.subscribe().await else {
+        bail!("Failed to subscribe to the daemon");
+    };
+
+    // Our process id tells the output of our command apart from the one of other clients.
+    let exec_id = std::process::id();
+    match client
+        .exec(exec_id, project_id, service, config, cmd, client_env())
+        .await
+    {
+        Ok(()) => {}
+        Err(TransportError::Rejected(message)) => bail!(message),
+        Err(err) => bail!("Failed to run the command: {err:?}"),
+    }
+
+    loop {
+        tokio::select! {
+            message = events.recv() => match message.map(|message| message.body) {
+                Some(TuttiApi::ExecOutput { exec_id: id, data, stderr }) if id == exec_id => {
+                    write_output(&data, stderr);
+                }
+                Some(TuttiApi::ExecExited { exec_id: id, code }) if id == exec_id => {
+                    return Ok(code);
+                }
+                Some(_) => {}
+                None => bail!("Lost the connection to the daemon"),
+            },
+
+            _ = signal::ctrl_c() => {
+                let _ = client.exec_kill(exec_id).await;
+            }
+        }
+    }
+}
+
+fn write_output(data: &[u8], stderr: bool) {
+    if stderr {
+        let mut stderr = io::stderr();
+        let _ = stderr.write_all(data);
+        let _ = stderr.flush();
+    } else {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(data);
+        let _ = stdout.flush();
+    }
+}
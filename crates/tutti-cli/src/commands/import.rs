@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use tutti_config::{import_compose, import_procfile, ConfigError, Import};
+
+use crate::{config::ImportSource, diagnostics};
+
+const PROCFILE_FILENAMES: [&str; 1] = ["Procfile"];
+const COMPOSE_FILENAMES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+
+pub fn import(
+    source: ImportSource,
+    input: Option<String>,
+    output: &str,
+    force: bool,
+) -> Result<()> {
+    let candidates: &[&str] = match source {
+        ImportSource::Procfile => &PROCFILE_FILENAMES,
+        ImportSource::Compose => &COMPOSE_FILENAMES,
+    };
+    let input = input.map_or_else(
+        || {
+            candidates
+                .iter()
+                .map(PathBuf::from)
+                .find(|path| path.exists())
+                .unwrap_or_else(|| PathBuf::from(candidates[0]))
+        },
+        PathBuf::from,
+    );
+
+    let text = std::fs::read_to_string(&input)
+        .map_err(|err| anyhow::anyhow!("cannot read {}: {err}", input.display()))?;
+
+    let result = match source {
+        ImportSource::Procfile => import_procfile(&text, &input),
+        ImportSource::Compose => import_compose(&text, &input),
+    };
+    let Import { config, warnings } = result.map_err(|err: ConfigError| {
+        diagnostics::report(&err);
+        anyhow::anyhow!("could not import {}", input.display())
+    })?;
+
+    for warning in &warnings {
+        diagnostics::warning(warning);
+    }
+
+    if output == "-" {
+        print!("{config}");
+        return Ok(());
+    }
+
+    let output = Path::new(output);
+    if output.exists() && !force {
+        bail!(
+            "{} already exists, use --force to overwrite it",
+            output.display()
+        );
+    }
+    std::fs::write(output, config)?;
+    println!("Imported {} into {}", input.display(), output.display());
+
+    Ok(())
+}
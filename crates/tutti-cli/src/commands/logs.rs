@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use tokio::signal;
+use tutti_daemon::DaemonRunner;
+use tutti_transport::{api::TuttiApi, client::ipc_client::IpcClient};
+
+use crate::{
+    config::{FilterArgs, OutputArgs},
+    logger::{filter_logs, LogOptions, Logger},
+    project::{config_path, load_project},
+    DEFAULT_SYSTEM_DIR,
+};
+
+pub async fn logs(
+    file: Option<String>,
+    services: Vec<String>,
+    output: &OutputArgs,
+    filter: &FilterArgs,
+    system_directory: Option<String>,
+) -> Result<()> {
+    let system_directory =
+        system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
+    let daemon_runner = DaemonRunner::new(system_directory);
+
+    let project = load_project(&config_path(file)?)?;
+    if let Some(unknown) = services
+        .iter()
+        .find(|service| !project.services.contains_key(*service))
+    {
+        bail!("unknown service `{unknown}`");
+    }
+    let shown = |tag: &str| {
+        let service = tag.split_once(':').map_or(tag, |(service, _)| service);
+        services.is_empty() || services.iter().any(|name| name == service)
+    };
+
+    let mut client = match IpcClient::new(daemon_runner.socket_path()).await {
+        Ok(client) => client,
+        Err(err) => bail!("failed to connect to the daemon: {err:?}"),
+    };
+    if filter.is_set() {
+        // The daemon leaves out the lines of other projects and services too.
+        let log_filter = filter.log_filter(&project, services.clone());
+        filter_logs(&mut client, log_filter).await?;
+    }
+    let Ok(mut events) = client.subscribe().await else {
+        bail!("failed to subscribe to the output of the project");
+    };
+
+    let mut logger = Logger::stdout(LogOptions::from_args(output))
+        .with_services(project.services.keys())
+        .with_log_formats(&project.services)
+        .with_highlight(filter.grep.as_deref())?;
+
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => return Ok(()),
+
+            message = events.recv() => {
+                let Some(message) = message else {
+                    bail!("lost the connection to the daemon");
+                };
+                match message.body {
+                    TuttiApi::Log { project_id, service, message, stderr }
+                        if project_id == project.id && shown(&service) =>
+                    {
+                        logger.log(&service, &message, stderr);
+                    }
+                    TuttiApi::ServiceStopped { project_id, service }
+                        if project_id == project.id && shown(&service) =>
+                    {
+                        logger.system(&format!("Service stopped: {service}"));
+                    }
+                    TuttiApi::ServiceRestarted { project_id, service }
+                        if project_id == project.id && shown(&service) =>
+                    {
+                        logger.system(&format!("Service restarted: {service}"));
+                    }
+                    TuttiApi::ServiceBlocked { project_id, service, reason }
+                        if project_id == project.id && shown(&service) =>
+                    {
+                        logger.error(&format!("Service {service} not started: {reason}"));
+                    }
+                    TuttiApi::LimitExceeded { project_id, service, reason }
+                        if project_id == project.id && shown(&service) =>
+                    {
+                        logger.error(&format!("Service {service} stopped: {reason}"));
+                    }
+                    TuttiApi::ProjectStopped { project_id } if project_id == project.id => {
+                        logger.system("All services stopped");
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
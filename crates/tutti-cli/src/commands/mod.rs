@@ -1,7 +1,27 @@
+mod attach;
+mod config;
+mod daemon_restart;
 mod daemon_start;
+mod daemon_status;
 mod daemon_stop;
+mod exec;
+mod import;
+mod logs;
 mod run;
+mod top;
+mod ui;
+mod wait;
 
+pub use attach::attach;
+pub use config::{config_print, config_validate};
+pub use daemon_restart::daemon_restart;
 pub use daemon_start::daemon_start;
+pub use daemon_status::daemon_status;
 pub use daemon_stop::daemon_stop;
+pub use exec::exec;
+pub use import::import;
+pub use logs::logs;
 pub use run::run;
+pub use top::top;
+pub use ui::ui;
+pub use wait::wait;
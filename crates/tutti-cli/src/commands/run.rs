@@ -1,35 +1,44 @@
 use std::path::PathBuf;
 
-use anyhow::Result;
-use tokio::signal;
-use tutti_config::load_from_path;
+use anyhow::{bail, Result};
+use tokio::signal::{
+    self,
+    unix::{signal as unix_signal, SignalKind},
+};
 use tutti_daemon::DaemonRunner;
 use tutti_transport::{api::TuttiApi, client::ipc_client::IpcClient};
+use tutti_types::ProjectId;
 
-use crate::{logger::Logger, DEFAULT_FILENAMES, DEFAULT_SYSTEM_DIR};
+use crate::{
+    config::{ExitArgs, FilterArgs, OutputArgs},
+    logger::{filter_logs, LogOptions, Logger},
+    project::{client_env, config_path, load_project, select_services},
+    terminal, DEFAULT_SYSTEM_DIR,
+};
 
+/// Exit code after a second `Ctrl+C`, as for a process killed by SIGINT.
+const INTERRUPTED: i32 = 130;
+
+/// Run a project until it stops, returning the exit code of `tutti-cli`: 0 if every service
+/// succeeded, the code of the first one that failed, or that of `--exit-code-from`.
+///
+/// # Errors
+/// Returns an error if the daemon cannot be reached or the project cannot be started.
 pub async fn run(
     file: Option<String>,
-    mut services: Vec<String>,
+    services: Vec<String>,
+    profiles: Vec<String>,
+    output: &OutputArgs,
+    filter: &FilterArgs,
+    exit: &ExitArgs,
     system_directory: Option<String>,
-    _kill_timeout: Option<u64>,
-) -> Result<()> {
-    let file = file.unwrap_or_else(|| {
-        for filename in DEFAULT_FILENAMES {
-            if std::path::Path::new(filename).exists() {
-                return filename.to_string();
-            }
-        }
-        "tutti.toml".to_string()
-    });
-
+) -> Result<i32> {
     let system_directory =
         system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
 
     let daemon_runner = DaemonRunner::new(system_directory);
-    if daemon_runner.prepare().is_err() {
-        println!("Failed to prepare daemon");
-        return Ok(());
+    if let Err(err) = daemon_runner.prepare() {
+        bail!("failed to prepare the daemon: {err}");
     }
 
     if IpcClient::check_socket(&daemon_runner.socket_path()).await {
@@ -37,87 +46,270 @@ pub async fn run(
     } else {
         tracing::debug!("Starting daemon");
         if let Err(err) = daemon_runner.spawn() {
-            println!("Failed to spawn daemon: {err:?}");
+            bail!("failed to start the daemon: {err}");
         }
     }
 
-    let path = PathBuf::from(file);
-    let project = load_from_path(&path)?;
+    let path = config_path(file)?;
+    let project = load_project(&path)?;
     let project_id = project.id.clone();
+    let services = select_services(&project, &services, &profiles)?;
+    check_exit_code_from(exit, &services)?;
+    let has_tty = project.services.values().any(|service| service.tty);
+    let mut logger = Logger::stdout(LogOptions::from_args(output))
+        .with_services(project.services.keys())
+        .with_log_formats(&project.services)
+        .with_highlight(filter.grep.as_deref())?;
 
     let mut client = match IpcClient::new(daemon_runner.socket_path()).await {
         Ok(client) => client,
-        Err(err) => {
-            println!("Failed to connect to the daemon: {err:?}");
-            return Ok(());
-        }
+        Err(err) => bail!("failed to connect to the daemon: {err:?}"),
     };
 
-    if services.is_empty() {
-        services = project
-            .services
-            .keys()
-            .map(std::borrow::ToOwned::to_owned)
-            .collect();
+    if filter.is_set() {
+        let log_filter = filter.log_filter(&project, Vec::new());
+        filter_logs(&mut client, log_filter).await?;
     }
 
-    if client.up(project, services).await.is_err() {
-        println!("Failed to start project");
+    let mut window_changes = unix_signal(SignalKind::window_change())?;
+    if has_tty {
+        terminal::send_window_size(&mut client, &project_id).await;
     }
 
+    // Subscribe first, so that no service can exit unnoticed.
     let Ok(mut logs) = client.subscribe().await else {
-        println!("Failed to subscribe to logs");
-        return Ok(());
+        bail!("failed to subscribe to the output of the project");
     };
 
+    if let Err(err) = client.up(project, services, client_env()).await {
+        bail!("failed to start the project: {err:?}");
+    }
+
+    let mut exit_code = ExitCode::new(exit);
     let mut shutting_down = false;
-    let mut logger = Logger::default();
 
     loop {
         tokio::select! {
             _ = signal::ctrl_c() => {
                 if shutting_down {
                     tracing::warn!("Second Ctrl+C: exiting immediately");
-                    return Ok(());
+                    return Ok(INTERRUPTED);
                 }
 
                 shutting_down = true;
                 tracing::info!("Ctrl+C: stopping services (sending Down)...");
 
                 if let Err(err) = client.down(project_id.clone()).await {
-                    tracing::error!("Failed to send Down: {err:?}");
-                    return Ok(());
+                    bail!("failed to stop the project: {err:?}");
                 }
             }
 
+            _ = window_changes.recv(), if has_tty => {
+                terminal::send_window_size(&mut client, &project_id).await;
+            }
+
             maybe_msg = logs.recv() => {
-                if let Some(message) = maybe_msg {
-                    match message.body {
-                        TuttiApi::ProjectStopped { project_id } => {
-                            tracing::info!("Project stopped: {}", project_id);
-                            logger.system("All services stopped");
-                            return Ok(());
-                        }
-                        TuttiApi::ServiceStopped { project_id: _, service } => {
-                            logger.system(&format!("Service stopped: {service}"));
-                        }
-                        TuttiApi::ServiceRestarted { project_id: _, service } => {
-                            logger.system(&format!("Service restarted: {service}"));
-                        }
-                        TuttiApi::Log { project_id: _, service, message } => {
-                            logger.log(&service, &message);
-                        }
-                        TuttiApi::Error { project_id: _, message } => {
-                            logger.error(&message);
-                            return Ok(());
-                        }
-                        _ => {}
+                let Some(message) = maybe_msg else {
+                    bail!("lost the connection to the daemon");
+                };
+                let stop = match handle(message.body, &project_id, &mut logger, &mut exit_code, shutting_down) {
+                    Next::Continue => false,
+                    Next::Stop => true,
+                    Next::Exit(code) => return Ok(code),
+                };
+
+                if stop && !shutting_down {
+                    shutting_down = true;
+                    logger.system("Stopping all services");
+                    if let Err(err) = client.down(project_id.clone()).await {
+                        bail!("failed to stop the project: {err:?}");
                     }
-                } else {
-                    tracing::info!("Log stream ended");
-                    return Ok(())
                 }
             }
         }
     }
 }
+
+/// What `run` does after a message from the daemon.
+enum Next {
+    Continue,
+    /// Stop all services.
+    Stop,
+    Exit(i32),
+}
+
+fn handle(
+    message: TuttiApi,
+    project_id: &ProjectId,
+    logger: &mut Logger,
+    exit_code: &mut ExitCode,
+    stopping: bool,
+) -> Next {
+    match message {
+        TuttiApi::ProjectStopped { project_id: id } if id == *project_id => {
+            tracing::info!("Project stopped: {}", project_id);
+            logger.system("All services stopped");
+            return Next::Exit(exit_code.code);
+        }
+        TuttiApi::ServiceExited {
+            project_id: id,
+            service,
+            code,
+        } if id == *project_id => {
+            if let Some(code) = code.filter(|code| *code != 0) {
+                logger.error(&format!("Service {service} exited with code {code}"));
+            }
+            if exit_code.exited(&service, code, stopping) {
+                return Next::Stop;
+            }
+        }
+        TuttiApi::ServiceStopped {
+            project_id: id,
+            service,
+        } if id == *project_id => {
+            logger.system(&format!("Service stopped: {service}"));
+        }
+        TuttiApi::ServiceRestarted {
+            project_id: id,
+            service,
+        } if id == *project_id => {
+            logger.system(&format!("Service restarted: {service}"));
+        }
+        TuttiApi::ServiceBlocked {
+            project_id: id,
+            service,
+            reason,
+        } if id == *project_id => {
+            logger.error(&format!("Service {service} not started: {reason}"));
+            if exit_code.exited(&service, None, stopping) {
+                return Next::Stop;
+            }
+        }
+        TuttiApi::LimitExceeded {
+            project_id: id,
+            service,
+            reason,
+        } if id == *project_id => {
+            logger.error(&format!("Service {service} stopped: {reason}"));
+        }
+        TuttiApi::Log {
+            project_id: id,
+            service,
+            message,
+            stderr,
+        } if id == *project_id => {
+            logger.log(&service, &message, stderr);
+        }
+        TuttiApi::Error {
+            project_id: id,
+            message,
+        } if id == *project_id => {
+            logger.error(&message);
+            return Next::Exit(1);
+        }
+        _ => {}
+    }
+    Next::Continue
+}
+
+/// Make sure `--exit-code-from` names one of the `selected` services: no other service exits
+/// during the run, so its exit code would never come.
+fn check_exit_code_from(args: &ExitArgs, selected: &[String]) -> Result<()> {
+    match &args.exit_code_from {
+        Some(service) if !selected.contains(service) => bail!(
+            "--exit-code-from service `{service}` is not among the selected services: {}",
+            selected.join(", ")
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// The exit code of `run`, following the services as they exit.
+struct ExitCode<'a> {
+    args: &'a ExitArgs,
+    code: i32,
+}
+
+impl<'a> ExitCode<'a> {
+    fn new(args: &'a ExitArgs) -> Self {
+        Self { args, code: 0 }
+    }
+
+    /// Record that `service` exited with `code`, `None` if it did not start or its code is
+    /// unknown, returning whether the whole project has to be stopped. Services killed while
+    /// `stopping` do not count as failures.
+    fn exited(&mut self, service: &str, code: Option<i32>, stopping: bool) -> bool {
+        let code = code.unwrap_or(1);
+        if self.args.exit_code_from.as_deref() == Some(service) {
+            self.code = code;
+            return true;
+        }
+        if code == 0 || stopping {
+            return false;
+        }
+        if self.args.exit_code_from.is_none() && self.code == 0 {
+            self.code = code;
+        }
+        self.args.abort_on_failure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exit_args(abort_on_failure: bool, exit_code_from: Option<&str>) -> ExitArgs {
+        ExitArgs {
+            abort_on_failure,
+            exit_code_from: exit_code_from.map(ToOwned::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let args = exit_args(false, None);
+        let mut exit_code = ExitCode::new(&args);
+        assert!(!exit_code.exited("migrate", Some(0), false));
+        assert_eq!(exit_code.code, 0);
+        assert!(!exit_code.exited("api", Some(2), false));
+        assert!(!exit_code.exited("worker", Some(1), false));
+        assert_eq!(exit_code.code, 2);
+
+        let args = exit_args(true, None);
+        let mut exit_code = ExitCode::new(&args);
+        assert!(!exit_code.exited("migrate", Some(0), false));
+        assert!(exit_code.exited("api", None, false));
+        assert_eq!(exit_code.code, 1);
+        // Services stopped because of the failure.
+        assert!(!exit_code.exited("worker", Some(143), true));
+        assert_eq!(exit_code.code, 1);
+    }
+
+    #[test]
+    fn test_exit_code_from() {
+        let args = exit_args(false, Some("tests"));
+        let mut exit_code = ExitCode::new(&args);
+        assert!(!exit_code.exited("api", Some(1), false));
+        assert_eq!(exit_code.code, 0);
+        assert!(exit_code.exited("tests", Some(3), false));
+        assert_eq!(exit_code.code, 3);
+
+        let args = exit_args(true, Some("tests"));
+        let mut exit_code = ExitCode::new(&args);
+        assert!(exit_code.exited("api", Some(1), false));
+        assert!(exit_code.exited("tests", Some(143), true));
+        assert_eq!(exit_code.code, 143);
+    }
+    #[test]
+    fn test_check_exit_code_from() {
+        let selected = vec!["api".to_owned(), "tests".to_owned()];
+        assert!(check_exit_code_from(&exit_args(false, None), &selected).is_ok());
+        assert!(check_exit_code_from(&exit_args(false, Some("tests")), &selected).is_ok());
+
+        let err = check_exit_code_from(&exit_args(false, Some("worker")), &selected).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--exit-code-from service `worker` is not among the selected services: api, tests"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use tokio::signal;
+use tutti_daemon::DaemonRunner;
+use tutti_transport::client::ipc_client::IpcClient;
+use tutti_types::ServiceStats;
+
+use crate::{
+    project::{config_path, load_project},
+    DEFAULT_SYSTEM_DIR,
+};
+
+/// Clear the screen and move the cursor to the top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+pub async fn top(
+    file: Option<String>,
+    all: bool,
+    interval: u64,
+    system_directory: Option<String>,
+) -> Result<()> {
+    let system_directory =
+        system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
+    let daemon_runner = DaemonRunner::new(system_directory);
+
+    let project_id = if all {
+        None
+    } else {
+        Some(load_project(&config_path(file)?)?.id)
+    };
+
+    let mut client = match IpcClient::new(daemon_runner.socket_path()).await {
+        Ok(client) => client,
+        Err(err) => bail!("failed to connect to the daemon: {err:?}"),
+    };
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval.max(1)));
+    loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => return Ok(()),
+            _ = ticker.tick() => {
+                let stats = match client.stats(project_id.clone()).await {
+                    Ok(stats) => stats,
+                    Err(err) => bail!("failed to get stats from the daemon: {err:?}"),
+                };
+
+                let mut stdout = io::stdout();
+                let _ = write!(stdout, "{CLEAR_SCREEN}{}", render(&stats, all));
+                let _ = stdout.flush();
+            }
+        }
+    }
+}
+
+/// Format the stats as a table, with the project of each service if `all` is set.
+fn render(stats: &[ServiceStats], all: bool) -> String {
+    if stats.is_empty() {
+        return "No running services\n".to_owned();
+    }
+
+    let name = |stat: &ServiceStats| {
+        if all {
+            format!("{}:{}", stat.project_id, stat.service)
+        } else {
+            stat.service.clone()
+        }
+    };
+    let width = stats
+        .iter()
+        .map(|stat| name(stat).len())
+        .max()
+        .unwrap_or_default()
+        .max("SERVICE".len());
+
+    let mut output = format!(
+        "{:<width$}  {:>8}  {:>6}  {:>10}  {:>7}  {:>5}  {:>5}\n",
+        "SERVICE", "PID", "CPU%", "MEM", "THREADS", "FDS", "PROCS"
+    );
+    for stat in stats {
+        let _ = writeln!(
+            output,
+            "{:<width$}  {:>8}  {:>6.1}  {:>10}  {:>7}  {:>5}  {:>5}",
+            name(stat),
+            stat.stats.pid,
+            stat.stats.cpu_percent,
+            format_bytes(stat.stats.memory_rss),
+            stat.stats.threads,
+            stat.stats.open_fds,
+            stat.stats.children + 1,
+        );
+    }
+    output
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use tutti_types::{ProcessStats, ProjectId};
+
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(25 * 1024 * 1024), "25.0 MiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(render(&[], false), "No running services\n");
+
+        let stats = vec![ServiceStats {
+            project_id: ProjectId(PathBuf::from("/app")),
+            service: "api".to_owned(),
+            stats: ProcessStats {
+                pid: 4242,
+                cpu_percent: 12.345,
+                memory_rss: 2048,
+                threads: 4,
+                open_fds: 12,
+                children: 1,
+            },
+        }];
+        assert_eq!(
+            render(&stats, false),
+            "SERVICE       PID    CPU%         MEM  THREADS    FDS  PROCS\n\
+             api          4242    12.3     2.0 KiB        4     12      2\n"
+        );
+        assert!(render(&stats, true).contains("/app:api "));
+    }
+}
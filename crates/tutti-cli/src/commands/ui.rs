@@ -0,0 +1,186 @@
+use std::{path::PathBuf, thread, time::Duration};
+
+use anyhow::{bail, Result};
+use ratatui::{
+    crossterm::event::{self, Event, KeyEventKind},
+    DefaultTerminal,
+};
+use tokio::sync::mpsc;
+use tutti_daemon::DaemonRunner;
+use tutti_transport::{
+    api::{TuttiApi, TuttiMessage},
+    client::ipc_client::IpcClient,
+    error::TransportError,
+};
+use tutti_types::Project;
+
+use crate::{
+    dashboard::{Action, Dashboard},
+    project::{client_env, config_path, load_project},
+    DEFAULT_SYSTEM_DIR,
+};
+
+/// How often the status of the services is refreshed.
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the screen is redrawn at most while output comes in.
+const FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+pub async fn ui(file: Option<String>, system_directory: Option<String>) -> Result<()> {
+    let system_directory =
+        system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
+    let daemon_runner = DaemonRunner::new(system_directory);
+
+    let project = load_project(&config_path(file)?)?;
+
+    let mut client = match IpcClient::new(daemon_runner.socket_path()).await {
+        Ok(client) => client,
+        Err(err) => bail!("failed to connect to the daemon: {err:?}"),
+    };
+    let Ok(events) = client.subscribe().await else {
+        bail!("failed to subscribe to the output of the project");
+    };
+
+    let mut dashboard = Dashboard::new(project.services.keys().cloned());
+    let mut terminal = ratatui::init();
+    let result = run_dashboard(&mut terminal, &mut dashboard, client, events, &project).await;
+    ratatui::restore();
+    result
+}
+
+async fn run_dashboard(
+    terminal: &mut DefaultTerminal,
+    dashboard: &mut Dashboard,
+    mut client: IpcClient,
+    mut events: mpsc::Receiver<TuttiMessage>,
+    project: &Project,
+) -> Result<()> {
+    let mut keys = read_terminal_events();
+    let mut status_ticker = tokio::time::interval(STATUS_INTERVAL);
+    let mut frame_ticker = tokio::time::interval(FRAME_INTERVAL);
+    let mut dirty = true;
+
+    loop {
+        tokio::select! {
+            event = keys.recv() => match event {
+                Some(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    match dashboard.handle_key(key) {
+                        Some(Action::Quit) => return Ok(()),
+                        Some(action) => {
+                            perform(&mut client, dashboard, project, action).await;
+                            refresh_status(&mut client, dashboard, project).await;
+                        }
+                        None => {}
+                    }
+                    terminal.draw(|frame| dashboard.draw(frame))?;
+                    dirty = false;
+                }
+                Some(_) => dirty = true,
+                None => return Ok(()),
+            },
+
+            _ = status_ticker.tick() => {
+                refresh_status(&mut client, dashboard, project).await;
+                dirty = true;
+            }
+
+            message = events.recv() => {
+                let Some(message) = message else {
+                    bail!("lost the connection to the daemon");
+                };
+                dirty |= handle_event(dashboard, project, message.body);
+            }
+
+            _ = frame_ticker.tick(), if dirty => {
+                terminal.draw(|frame| dashboard.draw(frame))?;
+                dirty = false;
+            }
+        }
+    }
+}
+
+/// Ask the daemon to start, stop or restart a service, reporting failures in the status line.
+async fn perform(
+    client: &mut IpcClient,
+    dashboard: &mut Dashboard,
+    project: &Project,
+    action: Action,
+) {
+    let result = match action {
+        // Starting goes through `Up`, so that the daemon knows the project even if it was never
+        // started, and the dependencies of the service are started too.
+        Action::Start(service) => {
+            client
+                .up(project.clone(), vec![service], client_env())
+                .await
+        }
+        Action::Stop(service) => client.stop_service(project.id.clone(), service).await,
+        Action::Restart(service) => client.restart_service(project.id.clone(), service).await,
+        Action::Quit => Ok(()),
+    };
+    match result {
+        Ok(()) => {}
+        Err(TransportError::Rejected(message)) => dashboard.set_message(message),
+        Err(err) => dashboard.set_message(format!("Request failed: {err:?}")),
+    }
+}
+
+async fn refresh_status(client: &mut IpcClient, dashboard: &mut Dashboard, project: &Project) {
+    match client.status(project.id.clone()).await {
+        Ok(services) => dashboard.set_status(services),
+        // The daemon does not know the project until one of its services is started.
+        Err(TransportError::Rejected(_)) => dashboard.set_stopped(),
+        Err(err) => dashboard.set_message(format!("Failed to get the status: {err:?}")),
+    }
+}
+
+/// Apply an event of the daemon to the dashboard, returning whether it changed.
+fn handle_event(dashboard: &mut Dashboard, project: &Project, event: TuttiApi) -> bool {
+    match event {
+        TuttiApi::Log {
+            project_id,
+            service,
+            message,
+            ..
+        } if project_id == project.id => dashboard.log(&service, &message),
+        TuttiApi::ServiceStopped {
+            project_id,
+            service,
+        } if project_id == project.id => dashboard.notice(&service, "stopped"),
+        TuttiApi::ServiceRestarted {
+            project_id,
+            service,
+        } if project_id == project.id => dashboard.notice(&service, "restarted"),
+        TuttiApi::ServiceBlocked {
+            project_id,
+            service,
+            reason,
+        } if project_id == project.id => {
+            dashboard.notice(&service, &format!("not started: {reason}"));
+        }
+        TuttiApi::LimitExceeded {
+            project_id,
+            service,
+            reason,
+        } if project_id == project.id => dashboard.notice(&service, &format!("stopped: {reason}")),
+        TuttiApi::Error {
+            project_id,
+            message,
+        } if project_id == project.id => dashboard.set_message(message),
+        _ => return false,
+    }
+    true
+}
+
+/// Read the key presses and resizes of the terminal on a thread of its own, since reading
+/// blocks.
+fn read_terminal_events() -> mpsc::UnboundedReceiver<Event> {
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if events_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    events_rx
+}
@@ -0,0 +1,204 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{bail, Result};
+use tokio::time::Instant;
+use tutti_daemon::DaemonRunner;
+use tutti_transport::{client::ipc_client::IpcClient, error::TransportError};
+use tutti_types::{ServiceState, ServiceStatus};
+
+use crate::{
+    config::WaitCondition,
+    project::{config_path, load_project, select_services},
+    DEFAULT_SYSTEM_DIR,
+};
+
+/// How often the status of the services is checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Wait until every one of `services` meets `condition`.
+///
+/// # Errors
+/// Returns an error naming the services that are not there yet if `timeout` passes first, if one
+/// of the services waited for to exit did so with a non-zero code, or if the daemon cannot be
+/// reached.
+pub async fn wait(
+    file: Option<String>,
+    services: Vec<String>,
+    condition: WaitCondition,
+    timeout: Duration,
+    system_directory: Option<String>,
+) -> Result<()> {
+    let system_directory =
+        system_directory.map_or_else(|| PathBuf::from(DEFAULT_SYSTEM_DIR), PathBuf::from);
+    let daemon_runner = DaemonRunner::new(system_directory);
+
+    let project = load_project(&config_path(file)?)?;
+    let services = select_services(&project, &services, &[])?;
+
+    let mut client = match IpcClient::new(daemon_runner.socket_path()).await {
+        Ok(client) => client,
+        Err(err) => bail!("failed to connect to the daemon: {err:?}"),
+    };
+
+    let deadline = Instant::now() + timeout;
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let status = match client.status(project.id.clone()).await {
+            Ok(status) => status,
+            // The daemon does not know the project until it is started.
+            Err(TransportError::Rejected(_)) => Vec::new(),
+            Err(err) => bail!("failed to get the status of the project: {err:?}"),
+        };
+
+        let pending = pending(&status, &services, condition);
+        if pending.is_empty() {
+            let failed = failed(&status, &services);
+            if condition == WaitCondition::Exited && !failed.is_empty() {
+                bail!("services exited with an error: {}", failed.join(", "));
+            }
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            bail!(
+                "timed out after {} waiting for services to be {}: {}",
+                humantime::format_duration(timeout),
+                condition.as_str(),
+                pending.join(", ")
+            );
+        }
+    }
+}
+
+/// The services that do not meet `condition` yet, described with their state. Services the
+/// daemon does not report have not been started.
+fn pending(status: &[ServiceStatus], services: &[String], condition: WaitCondition) -> Vec<String> {
+    services
+        .iter()
+        .filter_map(|service| {
+            let status = status.iter().find(|status| &status.service == service);
+            let state = status.map_or(ServiceState::Stopped, |status| status.state);
+            let exited = status.is_some_and(|status| status.last_exit.is_some());
+            let met = match condition {
+                WaitCondition::Healthy => state == ServiceState::Running,
+                WaitCondition::Running => {
+                    matches!(state, ServiceState::Starting | ServiceState::Running)
+                }
+                // A service that was never started has not exited either.
+                WaitCondition::Exited => state == ServiceState::Stopped && exited,
+            };
+            if met {
+                None
+            } else if state == ServiceState::Stopped && !exited {
+                Some(format!("{service} has not been started"))
+            } else {
+                Some(format!("{service} is {}", state.as_str()))
+            }
+        })
+        .collect()
+}
+
+/// The services whose last process exited with a non-zero or unknown code, described.
+fn failed(status: &[ServiceStatus], services: &[String]) -> Vec<String> {
+    status
+        .iter()
+        .filter(|status| services.contains(&status.service))
+        .filter_map(|status| match status.last_exit?.code {
+            Some(0) => None,
+            Some(code) => Some(format!("{} exited with code {code}", status.service)),
+            None => Some(format!("{} exited without an exit code", status.service)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tutti_types::ServiceExit;
+
+    use super::*;
+
+    fn status(service: &str, state: ServiceState) -> ServiceStatus {
+        ServiceStatus {
+            service: service.to_owned(),
+            state,
+            pid: None,
+            uptime: None,
+            restarts: 0,
+            last_exit: None,
+        }
+    }
+
+    fn exited(service: &str, code: Option<i32>) -> ServiceStatus {
+        ServiceStatus {
+            last_exit: Some(ServiceExit { code }),
+            ..status(service, ServiceState::Stopped)
+        }
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_pending() {
+        let status = [
+            status("api", ServiceState::Starting),
+            status("db", ServiceState::Running),
+            status("worker", ServiceState::Waiting),
+            status("cron", ServiceState::Stopped),
+            exited("migrate", Some(0)),
+        ];
+        let services = strings(&["api", "db", "tests"]);
+
+        assert_eq!(
+            pending(&status, &services, WaitCondition::Healthy),
+            strings(&["api is starting", "tests has not been started"])
+        );
+        assert_eq!(
+            pending(&status, &services, WaitCondition::Running),
+            strings(&["tests has not been started"])
+        );
+        assert_eq!(
+            pending(&status, &services, WaitCondition::Exited),
+            strings(&[
+                "api is starting",
+                "db is running",
+                "tests has not been started"
+            ])
+        );
+        assert!(pending(&status, &services[1..2], WaitCondition::Healthy).is_empty());
+
+        // Services that are stopped without having run, or unknown to a daemon that does not
+        // know the project, have not exited.
+        assert_eq!(
+            pending(
+                &status,
+                &strings(&["cron", "migrate"]),
+                WaitCondition::Exited
+            ),
+            strings(&["cron has not been started"])
+        );
+        assert_eq!(
+            pending(&[], &strings(&["migrate"]), WaitCondition::Exited),
+            strings(&["migrate has not been started"])
+        );
+    }
+
+    #[test]
+    fn test_failed() {
+        let status = [
+            exited("migrate", Some(0)),
+            exited("seed", Some(3)),
+            exited("tests", None),
+        ];
+
+        assert!(failed(&status, &strings(&["migrate"])).is_empty());
+        assert_eq!(
+            failed(&status, &strings(&["migrate", "seed", "tests"])),
+            strings(&[
+                "seed exited with code 3",
+                "tests exited without an exit code"
+            ])
+        );
+    }
+}
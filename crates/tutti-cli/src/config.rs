@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand};
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use tutti_transport::filter::LogFilter;
+use tutti_types::{LogLevel, Project};
 
 /// CLI for tutti
 #[derive(Parser, Debug)]
@@ -6,19 +10,27 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Configuration file to use instead of searching for one
+    #[arg(short, long, global = true, env = "TUTTI_CONFIG")]
+    pub file: Option<String>,
+
+    /// Run as if tutti was started in this directory
+    #[arg(short = 'C', long, global = true)]
+    pub project_dir: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Start the project using the specified configuration
     Run {
-        /// File path to the configuration file (TOML)
-        #[arg(short, long)]
-        file: Option<String>,
-
-        /// Services to start
+        /// Services to start; `@name` selects every service of a group
         services: Vec<String>,
 
+        /// Also start services that belong to this profile (can be repeated)
+        #[arg(long = "profile")]
+        profiles: Vec<String>,
+
         /// System directory path
         #[arg(short, long)]
         system_directory: Option<String>,
@@ -26,6 +38,98 @@ pub enum Commands {
         /// Timeout for killing services (in seconds)
         #[arg(short, long)]
         kill_timeout: Option<u64>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        #[command(flatten)]
+        exit: ExitArgs,
+    },
+    /// Follow the output of the running project
+    Logs {
+        /// Only show these services
+        services: Vec<String>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// System directory path
+        #[arg(short, long)]
+        system_directory: Option<String>,
+    },
+    /// Show the CPU and memory usage of the running services
+    Top {
+        /// Show the services of every project instead of the current one
+        #[arg(short, long)]
+        all: bool,
+
+        /// Seconds between updates
+        #[arg(short = 'n', long, default_value_t = 1)]
+        interval: u64,
+
+        /// System directory path
+        #[arg(short, long)]
+        system_directory: Option<String>,
+    },
+    /// Open a dashboard to watch the services of the project and start, stop or restart them
+    Ui {
+        /// System directory path
+        #[arg(short, long)]
+        system_directory: Option<String>,
+    },
+    /// Connect the terminal to a running service, to type into it
+    Attach {
+        /// Service to attach to; it needs `stdin_open` or `tty`
+        service: String,
+
+        /// Key sequence to detach from the service, such as `ctrl-p,ctrl-q`
+        #[arg(long, default_value = "ctrl-p,ctrl-q")]
+        detach_keys: String,
+
+        /// System directory path
+        #[arg(short, long)]
+        system_directory: Option<String>,
+    },
+    /// Run a command with the directory, environment and process settings of a service
+    Exec {
+        /// Service whose settings to use
+        service: String,
+
+        /// Command to run, after `--`
+        #[arg(last = true, required = true)]
+        cmd: Vec<String>,
+
+        /// Run the command in the daemon instead of as a child of this process
+        #[arg(long)]
+        daemon: bool,
+
+        /// System directory path
+        #[arg(short, long)]
+        system_directory: Option<String>,
+    },
+    /// Wait until services of the running project are healthy, running or exited
+    Wait {
+        /// Services to wait for; `@name` selects every service of a group. Defaults to the
+        /// services that `run` starts without arguments
+        services: Vec<String>,
+
+        /// How long to wait before giving up, such as `60s` or `2m`
+        #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+        timeout: Duration,
+
+        /// State the services have to reach
+        #[arg(long, value_enum, default_value_t = WaitCondition::Healthy)]
+        condition: WaitCondition,
+
+        /// System directory path
+        #[arg(short, long)]
+        system_directory: Option<String>,
     },
     /// Manage tutti daemon service
     Daemon {
@@ -36,12 +140,185 @@ pub enum Commands {
         #[arg(short, long)]
         system_directory: Option<String>,
     },
+    /// Inspect the configuration file
+    Config {
+        #[command(subcommand)]
+        cmd: ConfigCmd,
+    },
+    /// Convert a Procfile or docker-compose file into a tutti configuration
+    Import {
+        /// Format of the file to import
+        #[arg(value_enum)]
+        source: ImportSource,
+
+        /// File to import (defaults to `Procfile` or `compose.yaml`/`docker-compose.yml`)
+        #[arg(short, long)]
+        input: Option<String>,
+
+        /// Where to write the configuration (`-` for stdout)
+        #[arg(short, long, default_value = "tutti.toml")]
+        output: String,
+
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// How service output is printed by `run` and `logs`.
+#[derive(Args, Debug)]
+pub struct OutputArgs {
+    /// Start every line with the time it was received
+    #[arg(long)]
+    pub timestamps: bool,
+
+    /// Leave out the `[service]` prefix
+    #[arg(long)]
+    pub no_prefix: bool,
+
+    /// Print without colours, also done when `NO_COLOR` is set or stdout is not a terminal
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Print lines as text or as one JSON object per line
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+}
+
+/// When `run` stops and with which exit code.
+#[derive(Args, Debug)]
+pub struct ExitArgs {
+    /// Stop all services as soon as one of them fails
+    #[arg(long)]
+    pub abort_on_failure: bool,
+
+    /// Stop all services once this one exits, and exit with its exit code
+    #[arg(long, value_name = "SERVICE")]
+    pub exit_code_from: Option<String>,
+}
+
+/// Which lines of service output `run` and `logs` print.
+#[derive(Args, Debug)]
+pub struct FilterArgs {
+    /// Only show lines matching this regular expression, highlighting the matches
+    #[arg(long)]
+    pub grep: Option<String>,
+
+    /// Hide lines matching this regular expression
+    #[arg(long)]
+    pub exclude: Option<String>,
+
+    /// Only show lines of this level or above: trace, debug, info, warn or error
+    #[arg(long)]
+    pub level: Option<LogLevel>,
+
+    /// Only show what services write to stderr
+    #[arg(long)]
+    pub only_stderr: bool,
+
+    /// Only show lines of services with a `log_format` that have this field (can be repeated)
+    #[arg(long = "field", value_name = "KEY=VALUE", value_parser = parse_field)]
+    pub fields: Vec<(String, String)>,
+}
+
+impl FilterArgs {
+    /// Whether any line is to be left out, which the daemon then takes care of.
+    pub fn is_set(&self) -> bool {
+        self.grep.is_some()
+            || self.exclude.is_some()
+            || self.level.is_some()
+            || self.only_stderr
+            || !self.fields.is_empty()
+    }
+
+    /// The filter for the output of `services` of `project`, or of all of them if empty.
+    pub fn log_filter(&self, project: &Project, services: Vec<String>) -> LogFilter {
+        LogFilter {
+            project_id: project.id.clone(),
+            services,
+            grep: self.grep.clone(),
+            exclude: self.exclude.clone(),
+            level: self.level,
+            only_stderr: self.only_stderr,
+            fields: self.fields.clone(),
+            formats: project
+                .services
+                .iter()
+                .filter(|(_, service)| !service.log_format.is_text())
+                .map(|(name, service)| (name.clone(), service.log_format))
+                .collect(),
+        }
+    }
+}
+
+fn parse_field(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{value}`"))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Text,
+    /// `{"service", "stream", "ts", "message"}` objects
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitCondition {
+    /// Running and passing its healthcheck, if it has one
+    Healthy,
+    /// Started, whether healthy yet or not
+    Running,
+    /// Started and exited again, failing if it exited with a non-zero code
+    Exited,
+}
+
+impl WaitCondition {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WaitCondition::Healthy => "healthy",
+            WaitCondition::Running => "running",
+            WaitCondition::Exited => "exited",
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
 pub enum DaemonCmd {
     /// Start the daemon service
     Run,
-    /// Stop the daemon service
+    /// Stop the daemon service and the services of every project
     Stop,
+    /// Show the pid, version, uptime and projects of the daemon
+    Status,
+    /// Stop the daemon and start it again in the background
+    Restart,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCmd {
+    /// Check the configuration file and report every problem found
+    Validate,
+    /// Print the fully resolved configuration
+    Print {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = PrintFormat::Toml)]
+        format: PrintFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum PrintFormat {
+    Toml,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ImportSource {
+    /// foreman/honcho style Procfile
+    Procfile,
+    /// docker-compose file
+    Compose,
 }
@@ -0,0 +1,590 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write as _,
+};
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Row, Table, TableState},
+    Frame,
+};
+use tutti_types::{ServiceState, ServiceStatus};
+
+/// Lines kept per service; older output is dropped.
+const MAX_LOG_LINES: usize = 10_000;
+const HELP: &str =
+    "↑↓ select  s start  x stop  r restart  / search  n/N older/newer match  PgUp/PgDn scroll  q quit";
+
+/// Something the user asked for that needs the daemon.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Start(String),
+    Stop(String),
+    Restart(String),
+}
+
+/// Output of one service, split into lines.
+#[derive(Debug, Default)]
+struct LogBuffer {
+    lines: VecDeque<String>,
+    /// Output after the last newline, shown once it is terminated.
+    partial: String,
+}
+
+impl LogBuffer {
+    /// Append output, returning how many lines were completed.
+    fn push(&mut self, chunk: &str) -> usize {
+        self.partial.push_str(chunk);
+        let mut added = 0;
+        while let Some(end) = self.partial.find('\n') {
+            let line = clean_line(&self.partial[..end]);
+            self.partial.drain(..=end);
+            self.lines.push_back(line);
+            if self.lines.len() > MAX_LOG_LINES {
+                self.lines.pop_front();
+            }
+            added += 1;
+        }
+        added
+    }
+}
+
+/// Make a line of output printable: colours and other escape sequences are dropped, and only
+/// the last of several carriage-return separated versions of the line is kept.
+fn clean_line(line: &str) -> String {
+    let line = line.trim_end_matches('\r');
+    let line = line.rsplit('\r').next().unwrap_or(line);
+
+    let mut output = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters up to a final byte in `@`..`~`.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: up to BEL or ST.
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' || (c == '\x1b' && chars.next() == Some('\\')) {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\t' => output.push_str("    "),
+            c if c.is_control() => {}
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+/// State of the `tutti ui` dashboard: the services, their output and what the user is doing.
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    services: Vec<ServiceStatus>,
+    table: TableState,
+    logs: HashMap<String, LogBuffer>,
+    /// Lines scrolled up from the end of the selected log; 0 follows new output.
+    scroll: usize,
+    /// Height of the log pane at the last draw, for paging.
+    log_height: usize,
+    /// Search being typed after `/`.
+    input: Option<String>,
+    query: Option<String>,
+    /// Line of the selected log the last search jumped to.
+    current_match: Option<usize>,
+    /// Shown in place of the help until the next key press.
+    message: Option<String>,
+}
+
+impl Dashboard {
+    /// A dashboard listing `services` as stopped until the daemon reports them.
+    pub fn new(services: impl IntoIterator<Item = String>) -> Self {
+        let mut dashboard = Self::default();
+        dashboard.set_status(services.into_iter().map(stopped).collect());
+        dashboard
+    }
+
+    /// Replace the service list, keeping the selected service.
+    pub fn set_status(&mut self, mut services: Vec<ServiceStatus>) {
+        services.sort_by(|a, b| a.service.cmp(&b.service));
+        let selected = self.selected().map(str::to_owned);
+        self.services = services;
+
+        let idx = selected
+            .and_then(|name| self.services.iter().position(|s| s.service == name))
+            .unwrap_or_default();
+        self.table
+            .select((!self.services.is_empty()).then_some(idx));
+    }
+
+    /// Mark every service as stopped, for when the daemon does not know the project.
+    pub fn set_stopped(&mut self) {
+        let services = self
+            .services
+            .iter()
+            .map(|status| stopped(status.service.clone()))
+            .collect();
+        self.set_status(services);
+    }
+
+    /// Add the output of a service, or of one of its hooks if `tag` is `service:hook`.
+    pub fn log(&mut self, tag: &str, message: &str) {
+        let (service, message) = match tag.split_once(':') {
+            Some((service, hook)) => (service, prefix_lines(&format!("[{hook}] "), message)),
+            None => (tag, message.to_owned()),
+        };
+        let added = self
+            .logs
+            .entry(service.to_owned())
+            .or_default()
+            .push(&message);
+
+        // Keep the view still while scrolled up.
+        if self.scroll > 0 && self.selected() == Some(service) {
+            self.scroll += added;
+        }
+    }
+
+    /// Report something that happened to a service in its log and in the status line.
+    pub fn notice(&mut self, service: &str, message: &str) {
+        self.log(service, &format!("--- {message} ---\n"));
+        self.message = Some(format!("{service}: {message}"));
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.table
+            .selected()
+            .and_then(|idx| self.services.get(idx))
+            .map(|status| status.service.as_str())
+    }
+
+    fn selected_log(&self) -> Option<&LogBuffer> {
+        self.selected().and_then(|service| self.logs.get(service))
+    }
+
+    fn log_len(&self) -> usize {
+        self.selected_log().map_or(0, |log| log.lines.len())
+    }
+
+    /// React to a key press, returning what has to be asked of the daemon.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        self.message = None;
+        if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Esc => self.input = None,
+                KeyCode::Enter => {
+                    self.query = self.input.take().filter(|query| !query.is_empty());
+                    self.current_match = None;
+                    self.find_match(true);
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return None;
+        }
+
+        let page = isize::try_from(self.log_height.max(1)).unwrap_or(isize::MAX);
+        match key.code {
+            KeyCode::Char('q') => return Some(Action::Quit),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Some(Action::Quit);
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.select(1),
+            KeyCode::PageUp => self.scroll_by(page),
+            KeyCode::PageDown => self.scroll_by(-page),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll = self.max_scroll(),
+            KeyCode::End | KeyCode::Char('G') => self.scroll = 0,
+            KeyCode::Char('/') => self.input = Some(String::new()),
+            KeyCode::Char('n') => self.find_match(true),
+            KeyCode::Char('N') => self.find_match(false),
+            KeyCode::Esc => {
+                self.query = None;
+                self.current_match = None;
+            }
+            KeyCode::Char('s') => return self.selected().map(|s| Action::Start(s.to_owned())),
+            KeyCode::Char('x') => return self.selected().map(|s| Action::Stop(s.to_owned())),
+            KeyCode::Char('r') => return self.selected().map(|s| Action::Restart(s.to_owned())),
+            _ => {}
+        }
+        None
+    }
+
+    fn select(&mut self, offset: isize) {
+        if self.services.is_empty() {
+            return;
+        }
+        let idx = self
+            .table
+            .selected()
+            .unwrap_or_default()
+            .saturating_add_signed(offset)
+            .min(self.services.len() - 1);
+        self.table.select(Some(idx));
+        self.scroll = 0;
+        self.current_match = None;
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.log_len().saturating_sub(self.log_height)
+    }
+
+    fn scroll_by(&mut self, lines: isize) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(lines)
+            .min(self.max_scroll());
+    }
+
+    /// Jump to the closest line above (`older`) or below the current match that contains the
+    /// query, ignoring ASCII case. Without a current match the search starts from the end.
+    fn find_match(&mut self, older: bool) {
+        let Some(query) = self.query.as_ref().map(|q| q.to_ascii_lowercase()) else {
+            return;
+        };
+        let Some(log) = self.selected_log() else {
+            self.message = Some(format!("Pattern not found: {query}"));
+            return;
+        };
+        let matches = |idx: &usize| log.lines[*idx].to_ascii_lowercase().contains(&query);
+        let found = match (self.current_match, older) {
+            (Some(current), true) => (0..current).rev().find(matches),
+            (None, true) => (0..log.lines.len()).rev().find(matches),
+            (Some(current), false) => (current + 1..log.lines.len()).find(matches),
+            (None, false) => None,
+        };
+
+        match found {
+            Some(idx) => {
+                let len = log.lines.len();
+                self.current_match = Some(idx);
+                // Show the match in the middle of the pane.
+                self.scroll = (len - 1 - idx)
+                    .saturating_sub(self.log_height / 2)
+                    .min(self.max_scroll());
+            }
+            None => self.message = Some(format!("Pattern not found: {query}")),
+        }
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame) {
+        let area = frame.area();
+        let table_height = u16::try_from(self.services.len() + 3)
+            .unwrap_or(u16::MAX)
+            .min(area.height / 2);
+        let [table_area, log_area, footer_area] = Layout::vertical([
+            Constraint::Length(table_height),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+
+        self.draw_services(frame, table_area);
+        self.draw_log(frame, log_area);
+
+        let footer = match (&self.input, &self.message) {
+            (Some(input), _) => Line::from(format!("/{input}")),
+            (None, Some(message)) => Line::from(message.as_str()).yellow(),
+            (None, None) => Line::from(HELP).dark_gray(),
+        };
+        frame.render_widget(Paragraph::new(footer), footer_area);
+    }
+
+    fn draw_services(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = self.services.iter().map(|status| {
+            let (state, color) = match status.state {
+                ServiceState::Waiting => ("waiting", Color::Blue),
+                ServiceState::Starting => ("starting", Color::Yellow),
+                ServiceState::Running => ("running", Color::Green),
+                ServiceState::Stopped => ("stopped", Color::DarkGray),
+            };
+            Row::new(vec![
+                Span::raw(status.service.clone()),
+                Span::styled(state, Style::new().fg(color)),
+                Span::raw(
+                    status
+                        .pid
+                        .map_or_else(|| "-".to_owned(), |pid| pid.to_string()),
+                ),
+                Span::raw(status.uptime.map_or_else(|| "-".to_owned(), format_uptime)),
+                Span::raw(status.restarts.to_string()),
+            ])
+        });
+        let name_width = self
+            .services
+            .iter()
+            .map(|status| status.service.len())
+            .max()
+            .unwrap_or_default()
+            .max("SERVICE".len());
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(u16::try_from(name_width).unwrap_or(u16::MAX)),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(8),
+            ],
+        )
+        .header(Row::new(["SERVICE", "STATUS", "PID", "UPTIME", "RESTARTS"]).bold())
+        .block(Block::bordered().title(" services "))
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_log(&mut self, frame: &mut Frame, area: Rect) {
+        self.log_height = usize::from(area.height.saturating_sub(2));
+        self.scroll = self.scroll.min(self.max_scroll());
+
+        let mut title = format!(" {} ", self.selected().unwrap_or("logs"));
+        if let Some(query) = &self.query {
+            let _ = write!(title, "/{query} ");
+        }
+        if self.scroll > 0 {
+            let _ = write!(title, "(+{} lines below) ", self.scroll);
+        }
+
+        let lines: Vec<Line> = match self.selected_log() {
+            Some(log) => {
+                let end = log.lines.len() - self.scroll;
+                let start = end.saturating_sub(self.log_height);
+                (start..end)
+                    .map(|idx| {
+                        let line = highlight(&log.lines[idx], self.query.as_deref());
+                        if self.current_match == Some(idx) {
+                            line.on_dark_gray()
+                        } else {
+                            line
+                        }
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            area,
+        );
+    }
+}
+
+/// A service the daemon does not run.
+fn stopped(service: String) -> ServiceStatus {
+    ServiceStatus {
+        service,
+        state: ServiceState::Stopped,
+        pid: None,
+        uptime: None,
+        restarts: 0,
+        last_exit: None,
+    }
+}
+
+fn prefix_lines(prefix: &str, message: &str) -> String {
+    message
+        .split_inclusive('\n')
+        .fold(String::new(), |mut output, line| {
+            let _ = write!(output, "{prefix}{line}");
+            output
+        })
+}
+
+/// Style the occurrences of `query` in `line`, ignoring ASCII case.
+fn highlight<'a>(line: &'a str, query: Option<&str>) -> Line<'a> {
+    let Some(query) = query.map(str::to_ascii_lowercase) else {
+        return Line::raw(line);
+    };
+    let lowercase = line.to_ascii_lowercase();
+    let mut spans = Vec::new();
+    let mut last = 0;
+    for (start, matched) in lowercase.match_indices(&query) {
+        spans.push(Span::raw(&line[last..start]));
+        spans.push(Span::styled(
+            &line[start..start + matched.len()],
+            Style::new().black().on_yellow(),
+        ));
+        last = start + matched.len();
+    }
+    spans.push(Span::raw(&line[last..]));
+    Line::from(spans)
+}
+
+/// Format seconds as the two largest units, such as `3m 12s` or `2d 5h`.
+fn format_uptime(seconds: u64) -> String {
+    let (days, hours, minutes, seconds) = (
+        seconds / 86_400,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+    );
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn type_keys(dashboard: &mut Dashboard, keys: &str) {
+        for c in keys.chars() {
+            dashboard.handle_key(key(KeyCode::Char(c)));
+        }
+    }
+
+    fn running(service: &str, pid: u32) -> ServiceStatus {
+        ServiceStatus {
+            service: service.to_owned(),
+            state: ServiceState::Running,
+            pid: Some(pid),
+            uptime: Some(75),
+            restarts: 2,
+            last_exit: None,
+        }
+    }
+
+    #[test]
+    fn test_clean_line() {
+        assert_eq!(clean_line("\x1b[1;32mok\x1b[0m done\r"), "ok done");
+        assert_eq!(clean_line("10%\r50%\r100%"), "100%");
+        assert_eq!(clean_line("\x1b]0;title\x07a\tb"), "a    b");
+    }
+
+    #[test]
+    fn test_log_lines() {
+        let mut dashboard = Dashboard::new(["api".to_owned()]);
+        dashboard.log("api", "one\ntw");
+        dashboard.log("api", "o\n");
+        dashboard.log("api:pre_start", "installing\n");
+        assert_eq!(
+            dashboard.logs["api"].lines,
+            ["one", "two", "[pre_start] installing"]
+        );
+    }
+
+    #[test]
+    fn test_select_and_actions() {
+        let mut dashboard = Dashboard::new(["web".to_owned(), "api".to_owned()]);
+        assert_eq!(dashboard.selected(), Some("api"));
+        dashboard.handle_key(key(KeyCode::Down));
+        dashboard.handle_key(key(KeyCode::Down));
+        assert_eq!(dashboard.selected(), Some("web"));
+
+        // The selection follows the service when the list changes.
+        dashboard.set_status(vec![running("web", 1), running("db", 2), running("api", 3)]);
+        assert_eq!(dashboard.selected(), Some("web"));
+
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('r'))),
+            Some(Action::Restart("web".to_owned()))
+        );
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('x'))),
+            Some(Action::Stop("web".to_owned()))
+        );
+        assert_eq!(
+            dashboard.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Some(Action::Quit)
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let mut dashboard = Dashboard::new(["api".to_owned()]);
+        dashboard.log_height = 2;
+        dashboard.log("api", "GET /a\nerror: one\nGET /b\nERROR: two\nGET /c\n");
+
+        type_keys(&mut dashboard, "/error");
+        assert_eq!(dashboard.handle_key(key(KeyCode::Enter)), None);
+        assert_eq!(dashboard.current_match, Some(3));
+        assert_eq!(dashboard.scroll, 0);
+
+        type_keys(&mut dashboard, "n");
+        assert_eq!(dashboard.current_match, Some(1));
+        assert_eq!(dashboard.scroll, 2);
+
+        type_keys(&mut dashboard, "n");
+        assert_eq!(dashboard.current_match, Some(1));
+        assert_eq!(
+            dashboard.message.as_deref(),
+            Some("Pattern not found: error")
+        );
+
+        type_keys(&mut dashboard, "N");
+        assert_eq!(dashboard.current_match, Some(3));
+    }
+
+    #[test]
+    fn test_scroll_keeps_view() {
+        let mut dashboard = Dashboard::new(["api".to_owned()]);
+        dashboard.log_height = 2;
+        dashboard.log("api", "1\n2\n3\n4\n");
+        dashboard.handle_key(key(KeyCode::PageUp));
+        assert_eq!(dashboard.scroll, 2);
+        dashboard.log("api", "5\n");
+        assert_eq!(dashboard.scroll, 3);
+        dashboard.handle_key(key(KeyCode::End));
+        assert_eq!(dashboard.scroll, 0);
+    }
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(5), "5s");
+        assert_eq!(format_uptime(192), "3m 12s");
+        assert_eq!(format_uptime(7500), "2h 5m");
+        assert_eq!(format_uptime(190_000), "2d 4h");
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut dashboard = Dashboard::new(["api".to_owned()]);
+        dashboard.set_status(vec![running("api", 4242)]);
+        dashboard.log("api", "listening on :3000\n");
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 12)).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+
+        assert!(screen.contains("api     running  4242     1m 15s   2"));
+        assert!(screen.contains("listening on :3000"));
+    }
+}
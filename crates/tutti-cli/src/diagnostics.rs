@@ -0,0 +1,105 @@
+use colored::{Color, Colorize};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use tutti_config::{ConfigError, Diagnostic};
+
+/// Render a diagnostic in a compiler-like format.
+pub fn render(diagnostic: &Diagnostic) -> String {
+    let mut out = String::new();
+    let arrow = "-->".color(Color::Blue).bold();
+    let _ = writeln!(
+        out,
+        "{}{} {}",
+        "error".color(Color::Red).bold(),
+        ":".bold(),
+        diagnostic.message.bold()
+    );
+
+    let Some(span) = &diagnostic.span else {
+        let _ = writeln!(out, " {arrow} {}", diagnostic.file.display());
+        return out;
+    };
+
+    let line_number = span.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+    let pipe = "|".color(Color::Blue).bold();
+
+    let _ = writeln!(
+        out,
+        "{gutter}{arrow} {}:{}:{}",
+        diagnostic.file.display(),
+        span.line,
+        span.column
+    );
+    let _ = writeln!(out, "{gutter} {pipe}");
+    let _ = writeln!(
+        out,
+        "{} {pipe} {}",
+        line_number.color(Color::Blue).bold(),
+        span.source_line
+    );
+    let _ = writeln!(
+        out,
+        "{gutter} {pipe} {}{}",
+        " ".repeat(span.column.saturating_sub(1)),
+        "^".repeat(span.length).color(Color::Red).bold()
+    );
+    out
+}
+
+/// Print every diagnostic attached to a configuration error to stderr.
+pub fn report(err: &ConfigError) {
+    let mut stderr = io::stderr();
+    let diagnostics = err.diagnostics();
+    if diagnostics.is_empty() {
+        let _ = writeln!(
+            stderr,
+            "{}{} {}",
+            "error".color(Color::Red).bold(),
+            ":".bold(),
+            err
+        );
+        return;
+    }
+    for diagnostic in diagnostics {
+        let _ = writeln!(stderr, "{}", render(diagnostic));
+    }
+}
+
+/// Print a warning to stderr.
+pub fn warning(message: &str) {
+    let _ = writeln!(
+        io::stderr(),
+        "{}{} {message}",
+        "warning".color(Color::Yellow).bold(),
+        ":".bold()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tutti_config::Span;
+
+    use super::*;
+
+    #[test]
+    fn test_render_with_span() {
+        colored::control::set_override(false);
+        let diagnostic = Diagnostic {
+            message: "service `api`: depends on unknown service `dbx`".to_owned(),
+            file: PathBuf::from("tutti.toml"),
+            span: Some(Span {
+                line: 4,
+                column: 9,
+                length: 5,
+                source_line: "deps = [\"dbx\"]".to_owned(),
+            }),
+        };
+        assert_eq!(
+            render(&diagnostic),
+            "error: service `api`: depends on unknown service `dbx`\n --> tutti.toml:4:9\n  |\n4 | deps = [\"dbx\"]\n  |         ^^^^^\n"
+        );
+    }
+}
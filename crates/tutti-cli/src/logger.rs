@@ -1,78 +1,344 @@
+use anyhow::{bail, Context, Result};
 use colored::{Color, Colorize};
-use std::hash::{DefaultHasher, Hash, Hasher};
-use std::io::{self, Stdout, Write};
+use regex::{Captures, Regex};
+use serde_json::json;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, IsTerminal, Stdout, Write};
+use std::time::SystemTime;
+use tutti_transport::{
+    client::ipc_client::IpcClient, error::TransportError, filter::LogFilter, record::LogRecord,
+};
+use tutti_types::{LogFormat, LogLevel, Service};
+
+use crate::config::{OutputArgs, OutputFormat};
+
+/// Colours of the service prefixes, handed out in order so that the services of a project do not
+/// share one. Yellow and red are kept for system messages and errors.
+const COLORS: [Color; 8] = [
+    Color::Green,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::BrightGreen,
+    Color::BrightBlue,
+    Color::BrightMagenta,
+    Color::BrightCyan,
+];
+
+/// How log lines are printed.
+#[derive(Debug, Clone, Copy)]
+pub struct LogOptions {
+    pub format: OutputFormat,
+    pub timestamps: bool,
+    pub prefix: bool,
+    pub color: bool,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Text,
+            timestamps: false,
+            prefix: true,
+            color: true,
+        }
+    }
+}
+
+impl LogOptions {
+    /// Options from the command line. Colours are also left out when `NO_COLOR` is set or
+    /// stdout is not a terminal.
+    pub fn from_args(args: &OutputArgs) -> Self {
+        let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Self {
+            format: args.output,
+            timestamps: args.timestamps,
+            prefix: !args.no_prefix,
+            color: !args.no_color && !no_color_env && io::stdout().is_terminal(),
+        }
+    }
+}
 
 pub struct Logger<W: Write = Stdout> {
     output: W,
+    options: LogOptions,
+    colors: HashMap<String, Color>,
+    /// Width of the widest known prefix, which the others are padded to.
+    width: usize,
+    /// Pattern whose matches are highlighted in the output of services.
+    highlight: Option<Regex>,
+    /// `log_format` of the services whose lines are parsed.
+    formats: HashMap<String, LogFormat>,
 }
 
 impl<W: Write> Logger<W> {
-    pub fn new(output: W) -> Self {
-        Self { output }
+    pub fn new(output: W, options: LogOptions) -> Self {
+        Self {
+            output,
+            options,
+            colors: HashMap::new(),
+            width: 0,
+            highlight: None,
+            formats: HashMap::new(),
+        }
     }
 
-    fn string_to_color(s: &str) -> Color {
-        let colors = [
-            Color::Green,
-            Color::Blue,
-            Color::Magenta,
-            Color::Cyan,
-            Color::BrightGreen,
-            Color::BrightBlue,
-            Color::BrightMagenta,
-            Color::BrightCyan,
-        ];
+    /// Highlight the matches of `pattern` in the output of services, if printed in colour.
+    ///
+    /// # Errors
+    /// Returns an error if `pattern` is not a valid regular expression.
+    pub fn with_highlight(mut self, pattern: Option<&str>) -> Result<Self> {
+        if let Some(pattern) = pattern {
+            let regex = Regex::new(pattern).context("invalid --grep pattern")?;
+            self.highlight = Some(regex);
+        }
+        Ok(self)
+    }
 
-        let mut hasher = DefaultHasher::new();
-        s.hash(&mut hasher);
-        let hash = hasher.finish();
+    /// Give each of `services` its own colour and line their prefixes up. Services that show up
+    /// later get the next colour.
+    pub fn with_services<'a>(mut self, services: impl IntoIterator<Item = &'a String>) -> Self {
+        for service in services {
+            self.color(service);
+            self.width = self.width.max(service.len() + 2);
+        }
+        self
+    }
 
-        let idx = usize::try_from(hash).unwrap_or_default() % colors.len();
-        colors[idx]
+    /// Parse the output of the services according to their `log_format`.
+    pub fn with_log_formats<'a>(
+        mut self,
+        services: impl IntoIterator<Item = (&'a String, &'a Service)>,
+    ) -> Self {
+        for (name, service) in services {
+            if !service.log_format.is_text() {
+                self.formats.insert(name.clone(), service.log_format);
+            }
+        }
+        self
+    }
+
+    fn color(&mut self, service: &str) -> Color {
+        let next = self.colors.len();
+        *self
+            .colors
+            .entry(service.to_owned())
+            .or_insert_with(|| palette(next))
     }
 
-    pub fn log(&mut self, service_name: &str, message: &str) {
-        let prefix = format!("[{service_name}]").color(Self::string_to_color(service_name));
+    /// Print the output of a service, or of one of its hooks if `tag` is `service:hook`.
+    pub fn log(&mut self, tag: &str, message: &str, stderr: bool) {
+        let service = tag.split_once(':').map_or(tag, |(service, _)| service);
+        let color = self.color(service);
+        let stream = if stderr { "stderr" } else { "stdout" };
+        let format = self.formats.get(tag).copied().unwrap_or_default();
         for line in message.lines() {
-            let _ = writeln!(self.output, "{prefix} {line}");
+            if let Some(record) = LogRecord::parse(format, line) {
+                self.write_record(tag, stream, &record, color);
+            } else {
+                let line = self.highlighted(line);
+                self.write_line(Some(tag), stream, &line, color);
+            }
         }
     }
 
     pub fn error(&mut self, message: &str) {
-        let prefix = "[error]".color(Color::Red);
         for line in message.lines() {
-            let _ = writeln!(self.output, "{prefix} {line}");
+            self.write_line(None, "error", line, Color::Red);
         }
     }
 
     pub fn system(&mut self, message: &str) {
-        let prefix = "[system]".color(Color::Yellow);
         for line in message.lines() {
-            let _ = writeln!(self.output, "{prefix} {line}");
+            self.write_line(None, "system", line, Color::Yellow);
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self.options.format, OutputFormat::Text)
+    }
+
+    fn highlighted<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match &self.highlight {
+            Some(regex) if self.options.color && self.is_text() => regex
+                .replace_all(text, |captures: &Captures| {
+                    captures[0].black().on_yellow().to_string()
+                }),
+            _ => Cow::Borrowed(text),
+        }
+    }
+
+    /// Print one line as JSON, or as text behind the timestamp and the prefix, which is the tag
+    /// of the service or `stream` for messages of tutti itself.
+    fn write_line(&mut self, tag: Option<&str>, stream: &str, line: &str, color: Color) {
+        let ts = humantime::format_rfc3339_millis(SystemTime::now()).to_string();
+
+        if !self.is_text() {
+            let record = json!({ "service": tag, "stream": stream, "ts": ts, "message": line });
+            let _ = writeln!(self.output, "{record}");
+            return;
+        }
+
+        let prefix = self.prefix(tag.unwrap_or(stream), &ts, color);
+        let _ = writeln!(self.output, "{prefix}{line}");
+    }
+
+    /// Print a parsed line as JSON with its level and fields, or as text with the level first
+    /// and the fields last.
+    fn write_record(&mut self, tag: &str, stream: &str, record: &LogRecord, color: Color) {
+        let ts = humantime::format_rfc3339_millis(SystemTime::now()).to_string();
+
+        if !self.is_text() {
+            let line = json!({
+                "service": tag,
+                "stream": stream,
+                "ts": ts,
+                "level": record.level,
+                "message": record.message,
+                "fields": record.fields,
+            });
+            let _ = writeln!(self.output, "{line}");
+            return;
+        }
+
+        let mut text = self.prefix(tag, &ts, color);
+        if let Some(level) = record.level {
+            let name = format!("{:<5}", level.as_str().to_uppercase());
+            if self.options.color {
+                text.push_str(&name.color(level_color(level)).to_string());
+            } else {
+                text.push_str(&name);
+            }
+            text.push(' ');
         }
+        text.push_str(&self.highlighted(&record.message));
+        for (key, value) in &record.fields {
+            let value = match value {
+                serde_json::Value::String(value)
+                    if value.is_empty()
+                        || value.contains(|c: char| c.is_whitespace() || c == '"') =>
+                {
+                    format!("{value:?}")
+                }
+                serde_json::Value::String(value) => value.clone(),
+                other => other.to_string(),
+            };
+            let value = self.highlighted(&value);
+            if self.options.color {
+                let _ = write!(text, " {}{value}", format!("{key}=").dimmed());
+            } else {
+                let _ = write!(text, " {key}={value}");
+            }
+        }
+        let _ = writeln!(self.output, "{text}");
+    }
+
+    /// The timestamp and the padded `[label]` in front of a line, as far as they are shown.
+    fn prefix(&self, label: &str, ts: &str, color: Color) -> String {
+        let mut text = String::new();
+        if self.options.timestamps {
+            if self.options.color {
+                text.push_str(&ts.dimmed().to_string());
+            } else {
+                text.push_str(ts);
+            }
+            text.push(' ');
+        }
+        if self.options.prefix {
+            let prefix = format!("[{label}]");
+            let padding = " ".repeat(self.width.saturating_sub(prefix.len()) + 1);
+            if self.options.color {
+                text.push_str(&prefix.color(color).to_string());
+            } else {
+                text.push_str(&prefix);
+            }
+            text.push_str(&padding);
+        }
+        text
     }
 }
 
 impl Logger {
-    pub fn default() -> Self {
-        Self::new(io::stdout())
+    pub fn stdout(options: LogOptions) -> Self {
+        Self::new(io::stdout(), options)
+    }
+}
+
+/// Have the daemon only send the log lines matching `filter` to `client`.
+///
+/// # Errors
+/// Returns an error if the filter is invalid or the request fails.
+pub async fn filter_logs(client: &mut IpcClient, filter: LogFilter) -> Result<()> {
+    match client.filter_logs(filter).await {
+        Ok(()) => Ok(()),
+        Err(TransportError::Rejected(message)) => bail!(message),
+        Err(err) => bail!("failed to filter logs: {err:?}"),
     }
 }
 
+fn level_color(level: LogLevel) -> Color {
+    match level {
+        LogLevel::Trace => Color::BrightBlack,
+        LogLevel::Debug => Color::Blue,
+        LogLevel::Info => Color::Green,
+        LogLevel::Warn => Color::Yellow,
+        LogLevel::Error => Color::Red,
+    }
+}
+
+/// Colour of the `index`th service seen: the palette first, then evenly spread hues.
+fn palette(index: usize) -> Color {
+    if let Some(color) = COLORS.get(index) {
+        return *color;
+    }
+    // Golden angle steps keep consecutive hues far apart.
+    let step = u32::try_from(index - COLORS.len()).unwrap_or(u32::MAX);
+    let hue = (f64::from(step) * 137.508) % 360.0;
+    let (r, g, b) = hsv_to_rgb(hue, 0.6, 0.95);
+    Color::TrueColor { r, g, b }
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::many_single_char_names
+)]
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> (u8, u8, u8) {
+    let c = value * saturation;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = value - c;
+    let (r, g, b) = match hue {
+        h if h < 60.0 => (c, x, 0.0),
+        h if h < 120.0 => (x, c, 0.0),
+        h if h < 180.0 => (0.0, c, x),
+        h if h < 240.0 => (0.0, x, c),
+        h if h < 300.0 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let channel = |v: f64| ((v + m) * 255.0).round() as u8;
+    (channel(r), channel(g), channel(b))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Cursor;
 
+    fn output(logger: Logger<Cursor<Vec<u8>>>) -> String {
+        String::from_utf8(logger.output.into_inner()).unwrap()
+    }
+
     #[test]
     fn test_log() {
         let buffer = Vec::new();
-        let mut logger = Logger::new(Cursor::new(buffer));
+        let mut logger = Logger::new(Cursor::new(buffer), LogOptions::default());
 
-        logger.log("test", "line1\nline2");
+        logger.log("test", "line1\nline2", false);
 
-        let output = String::from_utf8(logger.output.into_inner()).unwrap();
-        let service = "[test]".color(Color::BrightGreen);
+        let output = output(logger);
+        let service = "[test]".color(Color::Green);
         let line1 = format!("{service} line1");
         let line2 = format!("{service} line2");
         assert_eq!(output, format!("{line1}\n{line2}\n"));
@@ -80,6 +346,146 @@ mod tests {
 
     #[test]
     fn test_log_default() {
-        let _logger = Logger::default();
+        let _logger = Logger::stdout(LogOptions::default());
+    }
+
+    #[test]
+    fn test_aligned_plain_prefixes() {
+        let services = ["api".to_owned(), "worker".to_owned()];
+        let options = LogOptions {
+            color: false,
+            ..LogOptions::default()
+        };
+        let mut logger = Logger::new(Cursor::new(Vec::new()), options).with_services(&services);
+
+        logger.log("api", "ready\n", false);
+        logger.log("worker", "busy\n", true);
+        logger.system("All services stopped");
+
+        assert_eq!(
+            output(logger),
+            "[api]    ready\n[worker] busy\n[system] All services stopped\n"
+        );
+    }
+
+    #[test]
+    fn test_no_prefix() {
+        let options = LogOptions {
+            prefix: false,
+            ..LogOptions::default()
+        };
+        let mut logger = Logger::new(Cursor::new(Vec::new()), options);
+        logger.log("api", "ready\n", false);
+        assert_eq!(output(logger), "ready\n");
+    }
+
+    #[test]
+    fn test_json_output() {
+        let options = LogOptions {
+            format: OutputFormat::Json,
+            ..LogOptions::default()
+        };
+        let mut logger = Logger::new(Cursor::new(Vec::new()), options);
+        logger.log("api:pre_start", "one\ntwo\n", true);
+        logger.error("boom");
+
+        let records: Vec<serde_json::Value> = output(logger)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["service"], "api:pre_start");
+        assert_eq!(records[0]["stream"], "stderr");
+        assert_eq!(records[1]["message"], "two");
+        assert!(humantime::parse_rfc3339(records[1]["ts"].as_str().unwrap()).is_ok());
+        assert_eq!(records[2]["service"], serde_json::Value::Null);
+        assert_eq!(records[2]["stream"], "error");
+    }
+
+    fn structured(format: LogFormat) -> HashMap<String, Service> {
+        let service = json!({
+            "cmd": ["api"],
+            "deps": [],
+            "restart": "never",
+            "log_format": format,
+        });
+        HashMap::from([("api".to_owned(), serde_json::from_value(service).unwrap())])
+    }
+
+    #[test]
+    fn test_structured_text() {
+        let options = LogOptions {
+            color: false,
+            ..LogOptions::default()
+        };
+        let services = structured(LogFormat::Json);
+        let mut logger = Logger::new(Cursor::new(Vec::new()), options)
+            .with_services(services.keys())
+            .with_log_formats(&services);
+
+        logger.log(
+            "api",
+            "{\"level\":\"warn\",\"msg\":\"slow query\",\"ms\":1200,\"table\":\"user list\"}\npanicked\n",
+            false,
+        );
+        logger.log("api:pre_start", "{\"msg\":\"not parsed\"}", false);
+
+        assert_eq!(
+            output(logger),
+            "[api] WARN  slow query ms=1200 table=\"user list\"\n[api] panicked\n[api:pre_start] {\"msg\":\"not parsed\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_structured_json() {
+        let options = LogOptions {
+            format: OutputFormat::Json,
+            ..LogOptions::default()
+        };
+        let services = structured(LogFormat::Logfmt);
+        let mut logger = Logger::new(Cursor::new(Vec::new()), options).with_log_formats(&services);
+        logger.log("api", "level=error msg=boom path=/users", true);
+
+        let record: serde_json::Value = serde_json::from_str(&output(logger)).unwrap();
+        assert_eq!(record["level"], "error");
+        assert_eq!(record["message"], "boom");
+        assert_eq!(record["fields"], json!({ "path": "/users" }));
+        assert_eq!(record["stream"], "stderr");
+    }
+
+    #[test]
+    fn test_highlight() {
+        let mut logger = Logger::new(Cursor::new(Vec::new()), LogOptions::default())
+            .with_highlight(Some("GET"))
+            .unwrap();
+        logger.log("api", "GET /users", false);
+
+        let service = "[api]".color(Color::Green);
+        let matched = "GET".black().on_yellow();
+        assert_eq!(output(logger), format!("{service} {matched} /users\n"));
+
+        let invalid = Logger::new(Cursor::new(Vec::new()), LogOptions::default());
+        assert!(invalid.with_highlight(Some("(")).is_err());
+    }
+
+    #[test]
+    fn test_colors_do_not_collide() {
+        let services: Vec<String> = (0..20).map(|i| format!("service{i}")).collect();
+        let mut logger =
+            Logger::new(Cursor::new(Vec::new()), LogOptions::default()).with_services(&services);
+
+        let mut colors: Vec<String> = services
+            .iter()
+            .map(|service| format!("{:?}", logger.color(service)))
+            .collect();
+        colors.sort();
+        colors.dedup();
+        assert_eq!(colors.len(), services.len());
+
+        // Hooks share the colour of their service.
+        let api = logger.color("api");
+        logger.log("api:pre_start", "x", false);
+        assert_eq!(logger.color("api"), api);
+        assert_eq!(logger.colors.len(), services.len() + 1);
     }
 }
@@ -1,16 +1,34 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 
 use crate::{
-    commands::{daemon_start, daemon_stop, run},
-    config::DaemonCmd,
+    commands::{
+        attach, config_print, config_validate, daemon_restart, daemon_start, daemon_status,
+        daemon_stop, exec, import, logs, run, top, ui, wait,
+    },
+    config::{ConfigCmd, DaemonCmd},
 };
 
 mod commands;
 mod config;
+mod dashboard;
+mod diagnostics;
 mod logger;
+mod project;
+mod terminal;
 
-const DEFAULT_FILENAMES: [&str; 3] = ["tutti.toml", "tutti.config.toml", "Tutti.toml"];
+const DEFAULT_FILENAMES: [&str; 10] = [
+    "tutti.toml",
+    "tutti.config.toml",
+    "Tutti.toml",
+    "tutti.yaml",
+    "tutti.yml",
+    "tutti.config.yaml",
+    "tutti.config.yml",
+    "tutti.json",
+    "tutti.config.json",
+    "Procfile",
+];
 const DEFAULT_SYSTEM_DIR: &str = "~/.tutti/";
 
 #[tokio::main]
@@ -22,20 +40,85 @@ async fn main() -> Result<()> {
         tracing_subscriber::fmt::init();
     }
 
+    if let Some(project_dir) = &cli.project_dir {
+        std::env::set_current_dir(project_dir)
+            .with_context(|| format!("cannot change directory to {project_dir}"))?;
+    }
+    let file = cli.file;
+
     match cli.command {
         config::Commands::Run {
-            file,
             services,
+            profiles,
+            system_directory,
+            kill_timeout: _,
+            output,
+            filter,
+            exit,
+        } => {
+            let code = run(
+                file,
+                services,
+                profiles,
+                &output,
+                &filter,
+                &exit,
+                system_directory,
+            )
+            .await?;
+            std::process::exit(code);
+        }
+        config::Commands::Logs {
+            services,
+            output,
+            filter,
+            system_directory,
+        } => logs(file, services, &output, &filter, system_directory).await?,
+        config::Commands::Top {
+            all,
+            interval,
+            system_directory,
+        } => top(file, all, interval, system_directory).await?,
+        config::Commands::Ui { system_directory } => ui(file, system_directory).await?,
+        config::Commands::Attach {
+            service,
+            detach_keys,
             system_directory,
-            kill_timeout,
-        } => run(file, services, system_directory, kill_timeout).await?,
+        } => attach(file, service, detach_keys, system_directory).await?,
+        config::Commands::Exec {
+            service,
+            cmd,
+            daemon,
+            system_directory,
+        } => {
+            let code = exec(file, service, cmd, daemon, system_directory).await?;
+            std::process::exit(code);
+        }
+        config::Commands::Wait {
+            services,
+            timeout,
+            condition,
+            system_directory,
+        } => wait(file, services, condition, timeout, system_directory).await?,
         config::Commands::Daemon {
             system_directory,
             cmd,
         } => match cmd {
             DaemonCmd::Run => daemon_start(system_directory).await?,
             DaemonCmd::Stop => daemon_stop(system_directory).await?,
+            DaemonCmd::Status => daemon_status(system_directory).await?,
+            DaemonCmd::Restart => daemon_restart(system_directory).await?,
+        },
+        config::Commands::Config { cmd } => match cmd {
+            ConfigCmd::Validate => config_validate(file)?,
+            ConfigCmd::Print { format } => config_print(file, format)?,
         },
+        config::Commands::Import {
+            source,
+            input,
+            output,
+            force,
+        } => import(source, input, &output, force)?,
     }
 
     Ok(())
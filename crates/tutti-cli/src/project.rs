@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use tutti_config::load_from_path;
+use tutti_types::Project;
+
+use crate::{diagnostics, DEFAULT_FILENAMES};
+
+/// Resolve the configuration file path: an explicit `file` (from `--file` or `TUTTI_CONFIG`) wins,
+/// otherwise the default filenames are searched from the current directory upwards.
+///
+/// # Errors
+/// Returns an error if no configuration file can be found.
+pub fn config_path(file: Option<String>) -> Result<PathBuf> {
+    if let Some(file) = file {
+        return Ok(PathBuf::from(file));
+    }
+
+    let cwd = std::env::current_dir()?;
+    discover_config(&cwd).ok_or_else(|| {
+        anyhow::anyhow!(
+            "could not find a configuration file ({}) in {} or any parent directory",
+            DEFAULT_FILENAMES.join(", "),
+            cwd.display()
+        )
+    })
+}
+
+/// Look for one of the default filenames in `start` and its parents, stopping at the root of the
+/// git repository `start` belongs to (or at the filesystem root).
+fn discover_config(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        for filename in DEFAULT_FILENAMES {
+            let candidate = dir.join(filename);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// Load a project, rendering configuration errors to stderr.
+///
+/// # Errors
+/// Returns an error if the configuration cannot be loaded.
+pub fn load_project(path: &Path) -> Result<Project> {
+    load_from_path(path).map_err(|err| {
+        diagnostics::report(&err);
+        anyhow::anyhow!("could not load {}", path.display())
+    })
+}
+
+/// Environment of this process, passed on to the services according to their `inherit_env`.
+/// Variables that are not valid UTF-8 are left out.
+pub fn client_env() -> HashMap<String, String> {
+    std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
+}
+
+/// Resolve the services to start from the command line.
+///
+/// Without explicit names every service that has no profile is selected. Names starting with `@`
+/// expand to the members of that group. Services in one of `profiles` are always added.
+/// Dependencies are not expanded here, the supervisor does that when the project starts.
+///
+/// # Errors
+/// Returns an error for unknown services, groups or profiles.
+pub fn select_services(
+    project: &Project,
+    names: &[String],
+    profiles: &[String],
+) -> Result<Vec<String>> {
+    for profile in profiles {
+        if !project
+            .services
+            .values()
+            .any(|service| service.profiles.contains(profile))
+        {
+            anyhow::bail!("no service belongs to profile `{profile}`");
+        }
+    }
+
+    let mut selected = Vec::new();
+    let mut select = |name: &String| {
+        if !selected.contains(name) {
+            selected.push(name.clone());
+        }
+    };
+
+    if names.is_empty() {
+        for (name, service) in &project.services {
+            if service.profiles.is_empty() {
+                select(name);
+            }
+        }
+    }
+
+    for name in names {
+        if let Some(group) = name.strip_prefix('@') {
+            let members = project
+                .groups
+                .get(group)
+                .ok_or_else(|| anyhow::anyhow!("unknown group `{group}`"))?;
+            members.iter().for_each(&mut select);
+        } else if project.services.contains_key(name) {
+            select(name);
+        } else {
+            anyhow::bail!("unknown service `{name}`");
+        }
+    }
+
+    for (name, service) in &project.services {
+        if service
+            .profiles
+            .iter()
+            .any(|profile| profiles.contains(profile))
+        {
+            select(name);
+        }
+    }
+
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tutti_types::{Hooks, InheritEnv, LogFormat, ProjectId, Restart, Service};
+
+    use super::*;
+
+    fn project() -> Project {
+        let service = |profiles: &[&str]| Service {
+            cmd: vec!["echo".to_owned()],
+            cwd: None,
+            env: None,
+            inherit_env: InheritEnv::All,
+            profiles: profiles.iter().map(ToString::to_string).collect(),
+            deps: vec![],
+            hooks: Hooks::default(),
+            user: None,
+            group: None,
+            umask: None,
+            nice: None,
+            rlimits: BTreeMap::new(),
+            tty: false,
+            stdin_open: false,
+            log_format: LogFormat::Text,
+            max_memory: None,
+            max_cpu_percent: None,
+            limit_grace_period: None,
+            healthcheck: None,
+            restart: Restart::Never,
+        };
+        Project {
+            version: 1,
+            id: ProjectId(PathBuf::from("/tmp/tutti.toml")),
+            services: BTreeMap::from([
+                ("api".to_owned(), service(&[])),
+                ("db".to_owned(), service(&[])),
+                ("debugger".to_owned(), service(&["debug"])),
+                ("web".to_owned(), service(&["frontend"])),
+            ]),
+            groups: BTreeMap::from([(
+                "backend".to_owned(),
+                vec!["api".to_owned(), "db".to_owned()],
+            )]),
+        }
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_select_services() {
+        let project = project();
+        assert_eq!(
+            select_services(&project, &[], &[]).unwrap(),
+            strings(&["api", "db"])
+        );
+        assert_eq!(
+            select_services(&project, &[], &strings(&["debug"])).unwrap(),
+            strings(&["api", "db", "debugger"])
+        );
+        assert_eq!(
+            select_services(&project, &strings(&["web", "@backend"]), &[]).unwrap(),
+            strings(&["web", "api", "db"])
+        );
+        assert_eq!(
+            select_services(&project, &strings(&["db"]), &strings(&["frontend"])).unwrap(),
+            strings(&["db", "web"])
+        );
+
+        assert!(select_services(&project, &strings(&["@missing"]), &[]).is_err());
+        assert!(select_services(&project, &strings(&["missing"]), &[]).is_err());
+        assert!(select_services(&project, &[], &strings(&["missing"])).is_err());
+    }
+
+    #[test]
+    fn test_discover_config() {
+        let root = std::env::temp_dir().join(format!("tutti-discover-{}", std::process::id()));
+        let nested = root.join("repo/services/api");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(root.join("repo/.git")).unwrap();
+        std::fs::write(root.join("tutti.toml"), "").unwrap();
+
+        // The git root stops the search before the config outside the repository.
+        assert_eq!(discover_config(&nested), None);
+
+        std::fs::write(root.join("repo/tutti.yaml"), "").unwrap();
+        assert_eq!(discover_config(&nested), Some(root.join("repo/tutti.yaml")));
+
+        std::fs::write(root.join("repo/services/tutti.toml"), "").unwrap();
+        assert_eq!(
+            discover_config(&nested),
+            Some(root.join("repo/services/tutti.toml"))
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    io::{self, Read},
+    thread,
+};
+
+use tokio::sync::mpsc;
+use tutti_transport::client::ipc_client::IpcClient;
+use tutti_types::{ProjectId, WindowSize};
+
+/// Size of the terminal stdout is connected to, `None` if it is not a terminal.
+pub fn size() -> Option<WindowSize> {
+    let mut winsize = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    #[allow(unsafe_code)]
+    let rc = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &raw mut winsize) };
+    (rc == 0 && winsize.ws_col > 0).then_some(WindowSize {
+        cols: winsize.ws_col,
+        rows: winsize.ws_row,
+    })
+}
+
+/// Pass the size of our terminal on to the services running on a pseudo-terminal.
+pub async fn send_window_size(client: &mut IpcClient, project_id: &ProjectId) {
+    let Some(size) = size() else {
+        return;
+    };
+    if let Err(err) = client.resize(project_id.clone(), size).await {
+        tracing::warn!("Failed to send the terminal size: {err:?}");
+    }
+}
+
+/// Keeps the terminal on stdin in raw mode while alive, so every key press is read as it is
+/// typed, including Ctrl-C. Output processing is left on, so lines still start at the left edge.
+pub struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    /// Switch to raw mode, `None` if stdin is not a terminal.
+    pub fn enable() -> Option<Self> {
+        #[allow(unsafe_code)]
+        unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &raw mut original) == -1 {
+                return None;
+            }
+            let mut raw = original;
+            libc::cfmakeraw(&raw mut raw);
+            raw.c_oflag = original.c_oflag;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw const raw) == -1 {
+                return None;
+            }
+            Some(Self { original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        #[allow(unsafe_code)]
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw const self.original);
+        }
+    }
+}
+
+/// Read stdin on a thread of its own. A blocking read on the runtime would keep the process
+/// alive after detaching, until the next key press.
+pub fn read_stdin() -> mpsc::Receiver<Vec<u8>> {
+    let (input_tx, input_rx) = mpsc::channel(16);
+    thread::spawn(move || {
+        let mut stdin = io::stdin().lock();
+        let mut buf = [0; 1024];
+        loop {
+            match stdin.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if input_tx.blocking_send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+    });
+    input_rx
+}
@@ -1,69 +1,477 @@
-use std::{collections::BTreeMap, path::Path};
+use std::collections::{BTreeMap, HashMap};
 
-use tutti_types::{Project, ProjectId, Restart, Service};
+use tutti_types::{Hook, HookKind, Hooks, InheritEnv, Project, ProjectId, Restart, Service};
 
 use crate::{
-    raw::{RawProject, RawRestart},
+    env_file,
+    interpolate::interpolate,
+    merge::{Composition, Sources},
+    raw::{
+        RawCmd, RawEnvFile, RawHook, RawInheritEnv, RawRestart, RawService, RawShell, RawSize,
+        RawUmask,
+    },
+    shell_words,
+    validate::Validator,
     ConfigError,
 };
 
-impl RawProject {
-    pub fn to_project(&self, path: &Path) -> Result<Project, ConfigError> {
+/// Seconds a hook may run when it sets no `timeout`.
+const DEFAULT_HOOK_TIMEOUT: u64 = 60;
+
+/// Shell used for string commands when none is configured.
+const DEFAULT_SHELL: &[&str] = &["sh", "-c"];
+
+impl Composition {
+    pub fn to_project(&self, shell: &HashMap<String, String>) -> Result<Project, ConfigError> {
+        let mut validator = Validator::new(&self.sources);
+        for diagnostic in &self.diagnostics {
+            validator.push(diagnostic.clone());
+        }
+
+        let mut project_env = BTreeMap::new();
+        load_env_files(
+            self.project.env_file.as_deref(),
+            &["env_file"],
+            &self.sources,
+            shell,
+            &mut project_env,
+            &mut validator,
+        );
+
+        let project_shell =
+            resolve_shell(self.project.shell.as_ref(), &["shell"], "", &mut validator);
+
         let services = self
+            .project
             .services
             .iter()
             .map(|(name, raw_service)| {
-                if raw_service.cmd.is_empty() {
-                    return Err(ConfigError::Validation(format!(
-                        "service `{name}`: cmd is empty"
-                    )));
-                }
-                if raw_service.cmd.iter().any(|c| c.trim().is_empty()) {
-                    return Err(ConfigError::Validation(format!(
-                        "service `{name}`: cmd contains empty element"
-                    )));
-                }
-                // TODO: Add validations
-
-                let restart = raw_service
-                    .restart
-                    .as_ref()
-                    .map(|policy| match policy {
-                        RawRestart::Always => Restart::Always,
-                        RawRestart::Never => Restart::Never,
-                    })
-                    .unwrap_or_default();
-
-                Ok((
-                    name.clone(),
-                    Service {
-                        cmd: raw_service.cmd.clone(),
-                        cwd: raw_service.cwd.clone().and_then(|cwd| cwd.parse().ok()),
-                        env: raw_service.env.clone(),
-                        deps: raw_service.deps.clone().unwrap_or_default(),
-                        healthcheck: raw_service.healthcheck,
-                        restart,
-                    },
-                ))
+                let service = raw_service.to_service(
+                    name,
+                    &self.sources,
+                    shell,
+                    &project_env,
+                    project_shell.as_deref(),
+                    &mut validator,
+                );
+                (name.clone(), service)
             })
-            .collect::<Result<BTreeMap<String, Service>, ConfigError>>()?;
+            .collect::<BTreeMap<String, Service>>();
+
+        let groups = self.project.groups.clone().unwrap_or_default();
+
+        validator.validate_services(&services);
+        validator.validate_groups(&groups, &services);
+        validator.finish()?;
 
         Ok(Project {
-            id: ProjectId(path.into()),
-            version: self.version,
+            id: ProjectId(self.sources.main().canonical.clone()),
+            version: self.project.version,
             services,
+            groups,
         })
     }
 }
 
+impl RawService {
+    fn to_service(
+        &self,
+        name: &str,
+        sources: &Sources,
+        shell: &HashMap<String, String>,
+        project_env: &BTreeMap<String, String>,
+        project_shell: Option<&[String]>,
+        validator: &mut Validator<'_>,
+    ) -> Service {
+        let mut file_env = project_env.clone();
+        load_env_files(
+            self.env_file.as_deref(),
+            &["services", name, "env_file"],
+            sources,
+            shell,
+            &mut file_env,
+            validator,
+        );
+
+        // Shell variables take precedence over env files when interpolating.
+        let lookup = |var: &str| shell.get(var).or_else(|| file_env.get(var)).cloned();
+
+        let mut explicit_env = BTreeMap::new();
+        let mut resolved_keys: HashMap<String, &str> = HashMap::new();
+        for (raw_key, raw_value) in self.env.iter().flatten() {
+            let key = match interpolate(raw_key, &lookup) {
+                Ok(key) => key,
+                Err(err) => {
+                    validator.error_at_key(
+                        &["services", name, "env", raw_key],
+                        format!("service `{name}`: {err}"),
+                    );
+                    continue;
+                }
+            };
+            let value = match interpolate(raw_value, &lookup) {
+                Ok(value) => value,
+                Err(err) => {
+                    validator.error_at_value(
+                        &["services", name, "env", raw_key],
+                        format!("service `{name}`: {err}"),
+                    );
+                    continue;
+                }
+            };
+            if let Some(other) = resolved_keys.insert(key.clone(), raw_key) {
+                validator.error_at_key(
+                    &["services", name, "env", raw_key],
+                    format!(
+                        "service `{name}`: environment variables `{other}` and `{raw_key}` both resolve to `{key}`"
+                    ),
+                );
+            }
+            explicit_env.insert(key, value);
+        }
+
+        // The service's own variables are visible to `cmd` and `cwd` too.
+        let lookup = |var: &str| explicit_env.get(var).cloned().or_else(|| lookup(var));
+
+        let command_shell = self.command_shell(name, project_shell, validator);
+        let cmd = resolve_cmd(
+            &self.cmd,
+            command_shell.as_deref(),
+            &["services", name, "cmd"],
+            &format!("service `{name}`: "),
+            &lookup,
+            validator,
+        );
+
+        let cwd = self.cwd.as_ref().and_then(|cwd| {
+            interpolate(cwd, &lookup)
+                .map_err(|err| {
+                    validator.error_at_value(
+                        &["services", name, "cwd"],
+                        format!("service `{name}`: {err}"),
+                    );
+                })
+                .ok()
+        });
+        // Relative paths are taken from the file that set them, not the daemon's directory.
+        // Without a `cwd` the service runs in the directory of the file that defined it.
+        let cwd = Some(match cwd {
+            Some(cwd) => sources.locate(&["services", name, "cwd"]).0.dir().join(cwd),
+            None => sources.locate(&["services", name]).0.dir().to_path_buf(),
+        });
+
+        let restart = self
+            .restart
+            .as_ref()
+            .map(|policy| match policy {
+                RawRestart::Always => Restart::Always,
+                RawRestart::Never => Restart::Never,
+            })
+            .unwrap_or_default();
+
+        let hooks = self.to_hooks(name, command_shell.as_deref(), &lookup, validator);
+
+        let mut env = file_env;
+        env.extend(explicit_env);
+
+        Service {
+            cmd,
+            cwd,
+            env: (!env.is_empty()).then(|| env.into_iter().collect()),
+            inherit_env: self.inherit_env(),
+            profiles: self.profiles.clone().unwrap_or_default(),
+            deps: self.deps.clone().unwrap_or_default(),
+            hooks,
+            user: self.user.clone(),
+            group: self.group.clone(),
+            umask: self.umask(name, validator),
+            nice: self.nice,
+            rlimits: self.rlimits.clone().unwrap_or_default(),
+            tty: self.tty.unwrap_or_default(),
+            stdin_open: self.stdin_open.unwrap_or_default(),
+            log_format: self.log_format.unwrap_or_default(),
+            max_memory: self.max_memory(name, validator),
+            max_cpu_percent: self.max_cpu_percent,
+            limit_grace_period: self.limit_grace_period,
+            healthcheck: self.healthcheck,
+            restart,
+        }
+    }
+
+    fn inherit_env(&self) -> InheritEnv {
+        match &self.inherit_env {
+            None | Some(RawInheritEnv::All(true)) => InheritEnv::All,
+            Some(RawInheritEnv::All(false)) => InheritEnv::None,
+            Some(RawInheritEnv::Only(names)) => InheritEnv::Only(names.clone()),
+        }
+    }
+
+    fn umask(&self, name: &str, validator: &mut Validator<'_>) -> Option<u32> {
+        match self.umask.as_ref()? {
+            RawUmask::Mode(mode) => Some(*mode),
+            RawUmask::Octal(octal) => {
+                let digits = octal.strip_prefix("0o").unwrap_or(octal);
+                u32::from_str_radix(digits, 8)
+                    .map_err(|_| {
+                        validator.error_at_value(
+                            &["services", name, "umask"],
+                            format!(
+                                "service `{name}`: invalid umask `{octal}`, expected an octal mode such as \"022\""
+                            ),
+                        );
+                    })
+                    .ok()
+            }
+        }
+    }
+
+    fn max_memory(&self, name: &str, validator: &mut Validator<'_>) -> Option<u64> {
+        match self.max_memory.as_ref()? {
+            RawSize::Bytes(bytes) => Some(*bytes),
+            RawSize::Text(text) => parse_size(text)
+                .ok_or_else(|| {
+                    validator.error_at_value(
+                        &["services", name, "max_memory"],
+                        format!(
+                            "service `{name}`: invalid max_memory `{text}`, expected a size such as \"512M\" or \"2G\""
+                        ),
+                    );
+                })
+                .ok(),
+        }
+    }
+
+    /// The service's own `shell` if set, the project's otherwise.
+    fn command_shell(
+        &self,
+        name: &str,
+        project_shell: Option<&[String]>,
+        validator: &mut Validator<'_>,
+    ) -> Option<Vec<String>> {
+        match &self.shell {
+            Some(raw) => resolve_shell(
+                Some(raw),
+                &["services", name, "shell"],
+                &format!("service `{name}`: "),
+                validator,
+            ),
+            None => project_shell.map(<[String]>::to_vec),
+        }
+    }
+
+    /// Hooks run with the same variables and shell as `cmd`.
+    fn to_hooks<F: Fn(&str) -> Option<String>>(
+        &self,
+        name: &str,
+        command_shell: Option<&[String]>,
+        lookup: &F,
+        validator: &mut Validator<'_>,
+    ) -> Hooks {
+        let mut hooks = Hooks::default();
+        for kind in HookKind::ALL {
+            let Some(raw) = self.hooks.as_ref().and_then(|hooks| hooks.get(kind)) else {
+                continue;
+            };
+            let mut path = vec!["services", name, "hooks", kind.as_str()];
+            if let RawHook::Detailed { .. } = raw {
+                path.push("cmd");
+            }
+            let cmd = resolve_cmd(
+                raw.cmd(),
+                command_shell,
+                &path,
+                &format!("service `{name}`: {kind} hook: "),
+                lookup,
+                validator,
+            );
+            let (timeout, on_failure) = match raw {
+                RawHook::Cmd(_) => (None, None),
+                RawHook::Detailed {
+                    timeout,
+                    on_failure,
+                    ..
+                } => (*timeout, *on_failure),
+            };
+            *hooks.get_mut(kind) = Some(Hook {
+                cmd,
+                timeout: timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT),
+                on_failure: on_failure.unwrap_or_default(),
+            });
+        }
+
+        hooks
+    }
+}
+
+/// Parse a size such as `512M`, `1.5GiB` or `100kb`. Units are powers of 1024.
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let exponent = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 1,
+        "m" | "mb" | "mib" => 2,
+        "g" | "gb" | "gib" => 3,
+        "t" | "tb" | "tib" => 4,
+        _ => return None,
+    };
+    let bytes = (number * 1024_f64.powi(exponent)).round();
+    // Values too large for a `u64` saturate, which is as good as no limit.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    bytes.is_finite().then_some(bytes as u64)
+}
+
+/// The command string commands are appended to, `None` to split them into words instead.
+fn resolve_shell(
+    raw: Option<&RawShell>,
+    path: &[&str],
+    owner: &str,
+    validator: &mut Validator<'_>,
+) -> Option<Vec<String>> {
+    let default = || DEFAULT_SHELL.iter().map(ToString::to_string).collect();
+    match raw {
+        None | Some(RawShell::Enabled(true)) => Some(default()),
+        Some(RawShell::Enabled(false)) => None,
+        Some(RawShell::Command(command)) => match shell_words::split(command) {
+            Ok(words) if !words.is_empty() => Some(words),
+            Ok(_) => {
+                validator.error_at_value(path, format!("{owner}shell is empty"));
+                Some(default())
+            }
+            Err(err) => {
+                validator.error_at_value(path, format!("{owner}cannot parse shell: {err}"));
+                Some(default())
+            }
+        },
+    }
+}
+
+/// Interpolate a command into argv. A string is passed as a single argument to `command_shell`,
+/// or split into words without one.
+fn resolve_cmd<F: Fn(&str) -> Option<String>>(
+    cmd: &RawCmd,
+    command_shell: Option<&[String]>,
+    path: &[&str],
+    owner: &str,
+    lookup: &F,
+    validator: &mut Validator<'_>,
+) -> Vec<String> {
+    match cmd {
+        RawCmd::Argv(argv) => argv
+            .iter()
+            .enumerate()
+            .map(|(idx, arg)| {
+                interpolate(arg, lookup).unwrap_or_else(|err| {
+                    let idx = idx.to_string();
+                    let mut path = path.to_vec();
+                    path.push(&idx);
+                    validator.error_at_value(&path, format!("{owner}{err}"));
+                    arg.clone()
+                })
+            })
+            .collect(),
+        RawCmd::Shell(line) => {
+            let line = match interpolate(line, lookup) {
+                Ok(line) => line,
+                Err(err) => {
+                    validator.error_at_value(path, format!("{owner}{err}"));
+                    line.clone()
+                }
+            };
+            if line.trim().is_empty() {
+                return Vec::new();
+            }
+
+            // The shell would reject unbalanced quotes too, but only once the service starts.
+            let words = shell_words::split(&line).unwrap_or_else(|err| {
+                validator.error_at_value(path, format!("{owner}cannot parse cmd: {err}"));
+                line.split_whitespace().map(ToOwned::to_owned).collect()
+            });
+            match command_shell {
+                Some(command_shell) => {
+                    let mut argv = command_shell.to_vec();
+                    argv.push(line);
+                    argv
+                }
+                None => words,
+            }
+        }
+    }
+}
+
+/// Load `env_file` entries (relative to the file that listed them) into `vars`, later files win.
+fn load_env_files(
+    env_files: Option<&[RawEnvFile]>,
+    path: &[&str],
+    sources: &Sources,
+    shell: &HashMap<String, String>,
+    vars: &mut BTreeMap<String, String>,
+    validator: &mut Validator<'_>,
+) {
+    for (idx, env_file) in env_files.unwrap_or_default().iter().enumerate() {
+        let idx = idx.to_string();
+        let mut entry = path.to_vec();
+        entry.push(&idx);
+
+        let file = sources.locate(&entry).0.dir().join(env_file.path());
+        let text = match std::fs::read_to_string(&file) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && !env_file.required() => {
+                continue;
+            }
+            Err(err) => {
+                let owner = match path {
+                    ["services", name, ..] => format!("service `{name}`: "),
+                    _ => String::new(),
+                };
+                validator.error_at_value(
+                    &entry,
+                    format!("{owner}cannot read env file `{}`: {err}", file.display()),
+                );
+                continue;
+            }
+        };
+
+        if let Err(diagnostic) = env_file::parse(&text, &file, shell, vars) {
+            validator.push(diagnostic);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, path::PathBuf};
+    use std::path::{Path, PathBuf};
 
-    use crate::raw::RawService;
+    use crate::{
+        diagnostic::SourceMap,
+        merge::{compose, Document},
+        raw::{RawProject, RawService},
+    };
+    use tutti_types::LogFormat;
 
     use super::*;
 
+    fn to_project(raw: RawProject) -> Result<Project, ConfigError> {
+        let document = Document::new(Path::new("/tmp/tutti.toml"), SourceMap::default(), raw);
+        compose(document).to_project(&HashMap::new())
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("100kb"), Some(100 * 1024));
+        assert_eq!(parse_size("512M"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_size("1.5 GiB"), Some(1536 * 1024 * 1024));
+        assert_eq!(parse_size("2G"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("2X"), None);
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
     #[test]
     fn test_raw_to_project_success() {
         let raw = {
@@ -71,31 +479,31 @@ mod tests {
             services.insert(
                 "full_service".into(),
                 RawService {
-                    cmd: vec!["echo".to_owned(), "hello".to_owned()],
+                    cmd: vec!["echo".to_owned(), "hello".to_owned()].into(),
                     cwd: Some("/tmp".to_owned()),
-                    env: Some(HashMap::from_iter(vec![(
+                    env: Some(BTreeMap::from_iter(vec![(
                         "KEY".to_owned(),
                         "Value".to_owned(),
                     )])),
                     deps: Some(vec!["empty_service".to_owned()]),
-                    healthcheck: None,
                     restart: Some(RawRestart::Always),
+                    ..RawService::default()
                 },
             );
             services.insert(
                 "empty_service".into(),
                 RawService {
-                    cmd: vec!["echo".to_owned(), "hello".to_owned()],
-                    cwd: None,
-                    env: None,
-                    deps: None,
-                    healthcheck: None,
-                    restart: None,
+                    cmd: vec!["echo".to_owned(), "hello".to_owned()].into(),
+                    ..RawService::default()
                 },
             );
             RawProject {
                 version: 1,
-                services: services,
+                env_file: None,
+                include: None,
+                shell: None,
+                services,
+                groups: None,
             }
         };
         let expected = {
@@ -109,7 +517,21 @@ mod tests {
                         "KEY".to_owned(),
                         "Value".to_owned(),
                     )])),
+                    inherit_env: InheritEnv::All,
+                    profiles: vec![],
                     deps: vec!["empty_service".to_owned()],
+                    hooks: Hooks::default(),
+                    user: None,
+                    group: None,
+                    umask: None,
+                    nice: None,
+                    rlimits: BTreeMap::new(),
+                    tty: false,
+                    stdin_open: false,
+                    log_format: LogFormat::Text,
+                    max_memory: None,
+                    max_cpu_percent: None,
+                    limit_grace_period: None,
                     healthcheck: None,
                     restart: Restart::Always,
                 },
@@ -118,21 +540,36 @@ mod tests {
                 "empty_service".into(),
                 Service {
                     cmd: vec!["echo".to_owned(), "hello".to_owned()],
-                    cwd: None,
+                    cwd: Some(PathBuf::from("/tmp")),
                     env: None,
+                    inherit_env: InheritEnv::All,
+                    profiles: vec![],
                     deps: vec![],
+                    hooks: Hooks::default(),
+                    user: None,
+                    group: None,
+                    umask: None,
+                    nice: None,
+                    rlimits: BTreeMap::new(),
+                    tty: false,
+                    stdin_open: false,
+                    log_format: LogFormat::Text,
+                    max_memory: None,
+                    max_cpu_percent: None,
+                    limit_grace_period: None,
                     healthcheck: None,
                     restart: Restart::Never,
                 },
             );
             Project {
-                id: ProjectId("test".into()),
+                id: ProjectId("/tmp/tutti.toml".into()),
                 version: 1,
-                services: services,
+                services,
+                groups: BTreeMap::new(),
             }
         };
 
-        let actual = raw.to_project(&PathBuf::from("test")).unwrap();
+        let actual = to_project(raw).unwrap();
         assert_eq!(actual, expected);
     }
 
@@ -144,20 +581,20 @@ mod tests {
                 services.insert(
                     "test".into(),
                     RawService {
-                        cmd: vec![],
-                        cwd: None,
-                        env: None,
-                        deps: None,
-                        healthcheck: None,
-                        restart: None,
+                        cmd: vec![].into(),
+                        ..RawService::default()
                     },
                 );
                 RawProject {
                     version: 1,
-                    services: services,
+                    env_file: None,
+                    include: None,
+                    shell: None,
+                    services,
+                    groups: None,
                 }
             };
-            let result = raw.to_project(&PathBuf::from("test"));
+            let result = to_project(raw);
             assert!(result.is_err());
         }
         {
@@ -166,20 +603,20 @@ mod tests {
                 services.insert(
                     "test".into(),
                     RawService {
-                        cmd: vec!["echo".to_owned(), "".to_owned()],
-                        cwd: None,
-                        env: None,
-                        deps: None,
-                        healthcheck: None,
-                        restart: None,
+                        cmd: vec!["echo".to_owned(), String::new()].into(),
+                        ..RawService::default()
                     },
                 );
                 RawProject {
                     version: 1,
-                    services: services,
+                    env_file: None,
+                    include: None,
+                    shell: None,
+                    services,
+                    groups: None,
                 }
             };
-            let result = raw.to_project(&PathBuf::from("test"));
+            let result = to_project(raw);
             assert!(result.is_err());
         }
     }
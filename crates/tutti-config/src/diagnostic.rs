@@ -0,0 +1,212 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// Location of a problem inside a configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column number (in characters).
+    pub column: usize,
+    /// Length of the highlighted region (in characters), at least 1.
+    pub length: usize,
+    /// The full text of the line the span starts on.
+    pub source_line: String,
+}
+
+/// A single configuration problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub file: PathBuf,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(file: &Path, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            message: message.into(),
+            file: file.to_path_buf(),
+            span,
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.span {
+            Some(span) => write!(
+                f,
+                "{}:{}:{}: {}",
+                self.file.display(),
+                span.line,
+                span.column,
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+/// Maps key paths of a parsed document (e.g. `services.api.deps.0`) to byte ranges.
+#[derive(Debug, Default)]
+pub(crate) struct SourceMap {
+    text: String,
+    keys: HashMap<Vec<String>, Range<usize>>,
+    values: HashMap<Vec<String>, Range<usize>>,
+}
+
+impl SourceMap {
+    /// Source map without any known locations.
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Self {
+        let mut source = Self::new(text);
+        if let Ok(root) = toml::de::DeTable::parse(text) {
+            source.walk_toml_table(&mut Vec::new(), root.get_ref());
+        }
+        source
+    }
+
+    #[cfg(feature = "toml")]
+    fn walk_toml_table(&mut self, path: &mut Vec<String>, table: &toml::de::DeTable<'_>) {
+        for (key, value) in table {
+            path.push(key.get_ref().to_string());
+            self.keys.insert(path.clone(), key.span());
+            self.walk_toml_value(path, value);
+            path.pop();
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    fn walk_toml_value(
+        &mut self,
+        path: &mut Vec<String>,
+        value: &toml::Spanned<toml::de::DeValue<'_>>,
+    ) {
+        self.values.insert(path.clone(), value.span());
+        match value.get_ref() {
+            toml::de::DeValue::Table(table) => self.walk_toml_table(path, table),
+            toml::de::DeValue::Array(array) => {
+                for (idx, item) in array.iter().enumerate() {
+                    path.push(idx.to_string());
+                    self.walk_toml_value(path, item);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Location of the value stored under `path`.
+    pub fn value(&self, path: &[&str]) -> Option<Span> {
+        self.lookup(&self.values, path)
+    }
+
+    /// Location of the key stored under `path`.
+    pub fn key(&self, path: &[&str]) -> Option<Span> {
+        self.lookup(&self.keys, path)
+    }
+
+    fn lookup(&self, map: &HashMap<Vec<String>, Range<usize>>, path: &[&str]) -> Option<Span> {
+        let path: Vec<String> = path.iter().map(|s| (*s).to_owned()).collect();
+        map.get(&path).map(|range| self.span(range.clone()))
+    }
+
+    /// Convert a 1-based line/column pair into a span.
+    #[cfg(any(feature = "yaml", feature = "json"))]
+    pub fn location(&self, line: usize, column: usize) -> Span {
+        let line_start: usize = self
+            .text
+            .split_inclusive('\n')
+            .take(line.saturating_sub(1))
+            .map(str::len)
+            .sum();
+        let offset = self.text[line_start..]
+            .char_indices()
+            .nth(column.saturating_sub(1))
+            .map_or(self.text.len(), |(idx, _)| line_start + idx);
+        self.span(offset..offset)
+    }
+
+    /// Convert a byte range into a line/column span.
+    pub fn span(&self, range: Range<usize>) -> Span {
+        let start = range.start.min(self.text.len());
+        let end = range.end.clamp(start, self.text.len());
+
+        let line_start = self.text[..start].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = self.text[start..]
+            .find('\n')
+            .map_or(self.text.len(), |idx| start + idx);
+        let source_line = self.text[line_start..line_end].trim_end_matches('\r');
+
+        let line = self.text[..start].matches('\n').count() + 1;
+        let column = self.text[line_start..start].chars().count() + 1;
+        let length = self.text[start..end.min(line_end)].chars().count().max(1);
+
+        Span {
+            line,
+            column,
+            length,
+            source_line: source_line.to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_line_and_column() {
+        let source = SourceMap::new("a = 1\nbb = [\"x\"]\n");
+        let span = source.span(12..15);
+        assert_eq!(span.line, 2);
+        assert_eq!(span.column, 7);
+        assert_eq!(span.length, 3);
+        assert_eq!(span.source_line, "bb = [\"x\"]");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_paths() {
+        let source = SourceMap::from_toml("[services.api]\ncmd = [\"echo\", \"hi\"]\n");
+        let span = source.value(&["services", "api", "cmd", "1"]).unwrap();
+        assert_eq!(span.line, 2);
+        assert_eq!(span.column, 16);
+        let span = source.key(&["services", "api"]).unwrap();
+        assert_eq!(span.line, 1);
+        assert_eq!(span.column, 11);
+        assert!(source.value(&["services", "db"]).is_none());
+    }
+
+    #[cfg(any(feature = "yaml", feature = "json"))]
+    #[test]
+    fn test_location() {
+        let source = SourceMap::new("a: 1\nbb: [x]\n");
+        let span = source.location(2, 5);
+        assert_eq!(span.line, 2);
+        assert_eq!(span.column, 5);
+        assert_eq!(span.source_line, "bb: [x]");
+    }
+
+    #[test]
+    fn test_display() {
+        let diagnostic = Diagnostic {
+            message: "oops".to_owned(),
+            file: PathBuf::from("tutti.toml"),
+            span: Some(SourceMap::new("x").span(0..1)),
+        };
+        assert_eq!(diagnostic.to_string(), "tutti.toml:1:1: oops");
+    }
+}
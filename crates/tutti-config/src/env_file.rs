@@ -0,0 +1,193 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use crate::{
+    diagnostic::{Diagnostic, SourceMap},
+    interpolate::interpolate,
+};
+
+/// Parse a `.env` file and add its variables to `vars`.
+///
+/// Supports `KEY=value`, `export KEY=value`, `#` comments, single-quoted (literal) and
+/// double-quoted (escapes, may span lines) values. Unquoted and double-quoted values are
+/// interpolated against the shell environment first, then against the variables loaded so far.
+pub(crate) fn parse(
+    text: &str,
+    path: &Path,
+    shell: &HashMap<String, String>,
+    vars: &mut BTreeMap<String, String>,
+) -> Result<(), Diagnostic> {
+    let source = SourceMap::new(text);
+    let error = |message: &str, start: usize, end: usize| {
+        Diagnostic::new(path, message, Some(source.span(start..end)))
+    };
+
+    let mut pos = 0;
+    while pos < text.len() {
+        let line_end = text[pos..].find('\n').map_or(text.len(), |idx| pos + idx);
+        let line = &text[pos..line_end];
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            pos = line_end + 1;
+            continue;
+        }
+
+        let body = trimmed
+            .strip_prefix("export ")
+            .map_or(trimmed, str::trim_start);
+        let body_start = line_end - line.len() + (line.len() - body.len());
+
+        let Some(eq) = body.find('=') else {
+            return Err(error("expected `KEY=VALUE`", body_start, line_end));
+        };
+        let key = body[..eq].trim();
+        if !is_valid_key(key) {
+            return Err(error(
+                &format!("invalid variable name `{key}`"),
+                body_start,
+                body_start + eq,
+            ));
+        }
+
+        let value_start = body_start + eq + 1;
+        let value_start = value_start + text[value_start..line_end].len()
+            - text[value_start..line_end]
+                .trim_start_matches([' ', '\t'])
+                .len();
+
+        let lookup = |name: &str| shell.get(name).or_else(|| vars.get(name)).cloned();
+        let (value, value_end) = match text[value_start..].chars().next() {
+            Some('\'') => {
+                let Some(close) = text[value_start + 1..].find('\'') else {
+                    return Err(error("unclosed `'`", value_start, line_end));
+                };
+                let end = value_start + 1 + close;
+                (text[value_start + 1..end].to_owned(), end + 1)
+            }
+            Some('"') => {
+                let Some((raw, end)) = double_quoted(&text[value_start + 1..]) else {
+                    return Err(error("unclosed `\"`", value_start, line_end));
+                };
+                let value = interpolate(&raw, &lookup).map_err(|err| {
+                    error(&err.to_string(), value_start, value_start + 1 + end + 1)
+                })?;
+                (value, value_start + 1 + end + 1)
+            }
+            _ => {
+                let raw = &text[value_start..line_end];
+                let raw = raw
+                    .find(" #")
+                    .or_else(|| raw.find("\t#"))
+                    .map_or(raw, |idx| &raw[..idx])
+                    .trim_end();
+                let value = interpolate(raw, &lookup)
+                    .map_err(|err| error(&err.to_string(), value_start, line_end))?;
+                (value, line_end)
+            }
+        };
+
+        let tail_end = text[value_end..]
+            .find('\n')
+            .map_or(text.len(), |idx| value_end + idx);
+        let tail = text[value_end..tail_end].trim();
+        if !tail.is_empty() && !tail.starts_with('#') {
+            return Err(error(
+                "unexpected characters after value",
+                value_end,
+                tail_end,
+            ));
+        }
+
+        vars.insert(key.to_owned(), value);
+        pos = tail_end + 1;
+    }
+
+    Ok(())
+}
+
+/// Unescape a double-quoted value, returning it and the position of the closing quote.
+fn double_quoted(input: &str) -> Option<(String, usize)> {
+    let mut value = String::new();
+    let mut chars = input.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, idx)),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                other => value.push(other),
+            },
+            other => value.push(other),
+        }
+    }
+    None
+}
+
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str) -> Result<BTreeMap<String, String>, Diagnostic> {
+        let shell = HashMap::from_iter([("USER".to_owned(), "alice".to_owned())]);
+        let mut vars = BTreeMap::new();
+        parse(text, Path::new(".env"), &shell, &mut vars)?;
+        Ok(vars)
+    }
+
+    #[test]
+    fn test_parse() {
+        let vars = run(r#"
+# comment
+PORT=3000
+export HOST = localhost # inline comment
+URL=http://${HOST}:${PORT}
+LITERAL='${PORT} # not a comment'
+QUOTED="line1\nline2 ${USER}"
+MULTI="a
+b"
+EMPTY=
+"#)
+        .unwrap();
+
+        assert_eq!(vars["PORT"], "3000");
+        assert_eq!(vars["HOST"], "localhost");
+        assert_eq!(vars["URL"], "http://localhost:3000");
+        assert_eq!(vars["LITERAL"], "${PORT} # not a comment");
+        assert_eq!(vars["QUOTED"], "line1\nline2 alice");
+        assert_eq!(vars["MULTI"], "a\nb");
+        assert_eq!(vars["EMPTY"], "");
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = run("PORT=3000\nnot a pair\n").unwrap_err();
+        assert_eq!(err.message, "expected `KEY=VALUE`");
+        assert_eq!(err.span.unwrap().line, 2);
+
+        let err = run("1PORT=3000\n").unwrap_err();
+        assert_eq!(err.message, "invalid variable name `1PORT`");
+
+        let err = run("A=\"unclosed\n").unwrap_err();
+        assert_eq!(err.message, "unclosed `\"`");
+
+        let err = run("A='x' y\n").unwrap_err();
+        assert_eq!(err.message, "unexpected characters after value");
+
+        let err = run("A=${SECRET:?missing}\n").unwrap_err();
+        assert_eq!(
+            err.message,
+            "required variable `SECRET` is not set: missing"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+/// Whether `pattern` contains wildcards.
+pub(crate) fn is_pattern(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Expand a `/`-separated pattern relative to `base` into the files it matches, sorted.
+///
+/// `*` and `?` match within a single path component and `**` matches any number of directories.
+/// Like in a shell, wildcards do not match names starting with `.` unless the pattern does.
+pub(crate) fn expand(base: &Path, pattern: &str) -> Vec<PathBuf> {
+    let (root, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (PathBuf::from("/"), rest),
+        None => (base.to_path_buf(), pattern),
+    };
+
+    let mut current = vec![root];
+    for component in rest.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        let mut next = Vec::new();
+        for dir in &current {
+            if component == "**" {
+                next.push(dir.clone());
+                subdirectories(dir, &mut next);
+            } else if !is_pattern(component) {
+                let candidate = dir.join(component);
+                if candidate.symlink_metadata().is_ok() {
+                    next.push(candidate);
+                }
+            } else {
+                for name in entries(dir) {
+                    if matches(component, &name) {
+                        next.push(dir.join(name));
+                    }
+                }
+            }
+        }
+        next.sort();
+        next.dedup();
+        current = next;
+    }
+
+    current.retain(|path| path.is_file());
+    current
+}
+
+/// Names of the entries of `dir`.
+fn entries(dir: &Path) -> Vec<String> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    read_dir
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+}
+
+/// Every directory below `dir`, without following symlinks or entering hidden directories.
+fn subdirectories(dir: &Path, out: &mut Vec<PathBuf>) {
+    let read_from = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let Ok(read_dir) = std::fs::read_dir(read_from) else {
+        return;
+    };
+    for entry in read_dir.filter_map(Result::ok) {
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if is_dir && !hidden {
+            let path = dir.join(entry.file_name());
+            out.push(path.clone());
+            subdirectories(&path, out);
+        }
+    }
+}
+
+/// Match a single path component against a pattern with `*` and `?`.
+fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*", "tutti.toml"));
+        assert!(matches("*.toml", "tutti.toml"));
+        assert!(matches("tutti.?oml", "tutti.toml"));
+        assert!(matches("t*i*.toml", "tutti.toml"));
+        assert!(!matches("*.yaml", "tutti.toml"));
+        assert!(!matches("*", ".env"));
+        assert!(matches(".*", ".env"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let base = Path::new("../../tests/assets");
+        assert_eq!(
+            expand(base, "*.yaml"),
+            vec![base.join("correct_config.yaml")]
+        );
+        assert_eq!(
+            expand(base, "compose/services/*/tutti.toml"),
+            vec![
+                base.join("compose/services/api/tutti.toml"),
+                base.join("compose/services/worker/tutti.toml"),
+            ]
+        );
+        assert_eq!(
+            expand(base, "compose/**/tutti.toml"),
+            vec![
+                base.join("compose/services/api/tutti.toml"),
+                base.join("compose/services/worker/tutti.toml"),
+                base.join("compose/tutti.toml"),
+            ]
+        );
+        assert_eq!(
+            expand(base, "env_file/tutti.toml"),
+            vec![base.join("env_file/tutti.toml")]
+        );
+        assert!(expand(base, "missing/*.toml").is_empty());
+    }
+}
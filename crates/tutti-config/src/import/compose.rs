@@ -0,0 +1,501 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde_yaml_ng::{Mapping, Value};
+
+use crate::{
+    diagnostic::{Diagnostic, SourceMap},
+    raw::{RawEnvFile, RawProject, RawRestart, RawService},
+    ConfigError,
+};
+
+/// Keys that are translated into the tutti configuration.
+const SUPPORTED_KEYS: [&str; 8] = [
+    "command",
+    "entrypoint",
+    "environment",
+    "env_file",
+    "depends_on",
+    "working_dir",
+    "restart",
+    "healthcheck",
+];
+
+/// Convert a docker-compose file into a raw project.
+///
+/// Services without a `command` or `entrypoint` (e.g. image-only services) cannot run locally and
+/// are skipped. Every key that cannot be translated produces a warning.
+pub(crate) fn parse(
+    text: &str,
+    path: &Path,
+    warnings: &mut Vec<String>,
+) -> Result<RawProject, ConfigError> {
+    let source = SourceMap::new(text);
+    let document = serde_yaml_ng::from_str::<Value>(text).map_err(|err| {
+        let span = err
+            .location()
+            .map(|location| source.location(location.line(), location.column()));
+        ConfigError::Parse(Diagnostic::new(path, err.to_string(), span))
+    })?;
+
+    let Some(compose_services) = document.get("services").and_then(Value::as_mapping) else {
+        return Err(ConfigError::Parse(Diagnostic::new(
+            path,
+            "compose file has no `services` section",
+            None,
+        )));
+    };
+
+    if let Some(root) = document.as_mapping() {
+        for key in root.keys().filter_map(Value::as_str) {
+            if !matches!(key, "services" | "version" | "name") {
+                warnings.push(format!("top-level `{key}` is not supported, ignored"));
+            }
+        }
+    }
+
+    let mut services = BTreeMap::new();
+    for (name, definition) in compose_services {
+        let Some(name) = name.as_str() else {
+            continue;
+        };
+        let empty = Mapping::new();
+        let definition = definition.as_mapping().unwrap_or(&empty);
+        if let Some(service) = convert_service(name, definition, warnings) {
+            services.insert(name.to_owned(), service);
+        }
+    }
+
+    let converted: Vec<String> = services.keys().cloned().collect();
+    for (name, service) in &mut services {
+        if let Some(deps) = &mut service.deps {
+            deps.retain(|dep| {
+                if converted.contains(dep) {
+                    return true;
+                }
+                if compose_services.contains_key(dep.as_str()) {
+                    warnings.push(format!(
+                        "service `{name}`: dependency `{dep}` was skipped, dropped"
+                    ));
+                } else {
+                    warnings.push(format!(
+                        "service `{name}`: dependency `{dep}` is not defined, dropped"
+                    ));
+                }
+                false
+            });
+            if deps.is_empty() {
+                service.deps = None;
+            }
+        }
+    }
+
+    Ok(RawProject {
+        version: 1,
+        env_file: None,
+        include: None,
+        shell: None,
+        services,
+        groups: None,
+    })
+}
+
+fn convert_service(
+    name: &str,
+    definition: &Mapping,
+    warnings: &mut Vec<String>,
+) -> Option<RawService> {
+    for key in definition.keys().filter_map(Value::as_str) {
+        if !SUPPORTED_KEYS.contains(&key) {
+            warnings.push(format!(
+                "service `{name}`: `{key}` is not supported, ignored"
+            ));
+        }
+    }
+
+    let entrypoint = definition
+        .get("entrypoint")
+        .and_then(|value| command_part(name, "entrypoint", value, warnings));
+    let command = definition
+        .get("command")
+        .and_then(|value| command_part(name, "command", value, warnings));
+    let cmd = match (entrypoint, command) {
+        (None, None) => Vec::new(),
+        (Some(CommandPart::Words(words)), None) | (None, Some(CommandPart::Words(words))) => words,
+        (Some(CommandPart::Words(mut entrypoint)), Some(CommandPart::Words(command))) => {
+            entrypoint.extend(command);
+            entrypoint
+        }
+        (entrypoint, command) => {
+            let script = [entrypoint, command]
+                .into_iter()
+                .flatten()
+                .map(CommandPart::into_script)
+                .collect::<Vec<_>>()
+                .join(" ");
+            vec!["sh".to_owned(), "-c".to_owned(), script]
+        }
+    };
+    if cmd.is_empty() {
+        warnings.push(format!(
+            "service `{name}`: no `command` or `entrypoint`, skipped (image-only services cannot run locally)"
+        ));
+        return None;
+    }
+
+    let env = definition
+        .get("environment")
+        .map(|environment| environment_map(name, environment, warnings))
+        .filter(|env| !env.is_empty());
+
+    let env_file = definition
+        .get("env_file")
+        .map(env_files)
+        .filter(|env_files| !env_files.is_empty());
+
+    let deps = definition
+        .get("depends_on")
+        .map(|depends_on| dependencies(name, depends_on, warnings))
+        .filter(|deps| !deps.is_empty());
+
+    let restart = definition
+        .get("restart")
+        .and_then(Value::as_str)
+        .and_then(|restart| restart_policy(name, restart, warnings));
+
+    if definition.contains_key("healthcheck") {
+        warnings.push(format!(
+            "service `{name}`: `healthcheck` is not supported yet, ignored"
+        ));
+    }
+
+    Some(RawService {
+        cmd: cmd.into(),
+        cwd: definition
+            .get("working_dir")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned),
+        env,
+        env_file,
+        deps,
+        restart,
+        ..RawService::default()
+    })
+}
+
+/// Compose accepts a single path, a list of paths or a list of `{path, required}` entries.
+fn env_files(env_file: &Value) -> Vec<RawEnvFile> {
+    let entry = |value: &Value| match value {
+        Value::String(path) => Some(RawEnvFile::Path(path.clone())),
+        Value::Mapping(entry) => Some(RawEnvFile::Detailed {
+            path: entry.get("path")?.as_str()?.to_owned(),
+            required: entry
+                .get("required")
+                .and_then(Value::as_bool)
+                .unwrap_or(true),
+        }),
+        _ => None,
+    };
+    match env_file {
+        Value::Sequence(items) => items.iter().filter_map(entry).collect(),
+        value => entry(value).into_iter().collect(),
+    }
+}
+
+/// Compose accepts both `[a, b]` and `{a: {condition: ...}}` for dependencies.
+fn dependencies(name: &str, depends_on: &Value, warnings: &mut Vec<String>) -> Vec<String> {
+    match depends_on {
+        Value::Sequence(items) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(ToOwned::to_owned)
+            .collect(),
+        Value::Mapping(items) => {
+            for (dep, options) in items {
+                let condition = options.get("condition").and_then(Value::as_str);
+                if let (Some(dep), Some(condition)) = (dep.as_str(), condition) {
+                    if condition != "service_started" && condition != "service_healthy" {
+                        warnings.push(format!(
+                            "service `{name}`: depends_on `{dep}` condition `{condition}` is not supported, treated as service_started"
+                        ));
+                    }
+                }
+            }
+            items
+                .keys()
+                .filter_map(Value::as_str)
+                .map(ToOwned::to_owned)
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn restart_policy(name: &str, restart: &str, warnings: &mut Vec<String>) -> Option<RawRestart> {
+    match restart {
+        "no" => Some(RawRestart::Never),
+        "always" | "unless-stopped" => Some(RawRestart::Always),
+        other => {
+            let policy = other.split(':').next().unwrap_or(other);
+            if policy == "on-failure" {
+                warnings.push(format!(
+                    "service `{name}`: restart `{other}` is approximated by `always`"
+                ));
+                Some(RawRestart::Always)
+            } else {
+                warnings.push(format!(
+                    "service `{name}`: restart `{other}` is not supported, ignored"
+                ));
+                None
+            }
+        }
+    }
+}
+
+/// Compose accepts both `["a", "b"]` and shell strings for commands.
+enum CommandPart {
+    Words(Vec<String>),
+    Shell(String),
+}
+
+impl CommandPart {
+    fn into_script(self) -> String {
+        match self {
+            CommandPart::Words(words) => shell_join(&words),
+            CommandPart::Shell(script) => script,
+        }
+    }
+}
+
+fn command_part(
+    name: &str,
+    key: &str,
+    value: &Value,
+    warnings: &mut Vec<String>,
+) -> Option<CommandPart> {
+    match value {
+        Value::String(command) => Some(CommandPart::Shell(command.clone())),
+        Value::Sequence(items) => Some(CommandPart::Words(
+            items.iter().filter_map(scalar_to_string).collect(),
+        )),
+        _ => {
+            warnings.push(format!("service `{name}`: cannot read `{key}`, ignored"));
+            None
+        }
+    }
+}
+
+/// Compose accepts both `{KEY: value}` and `["KEY=value"]` for the environment.
+fn environment_map(
+    name: &str,
+    value: &Value,
+    warnings: &mut Vec<String>,
+) -> BTreeMap<String, String> {
+    let mut env = BTreeMap::new();
+    match value {
+        Value::Mapping(items) => {
+            for (key, value) in items {
+                let Some(key) = key.as_str() else {
+                    continue;
+                };
+                match scalar_to_string(value) {
+                    Some(value) => {
+                        env.insert(key.to_owned(), value);
+                    }
+                    None => warnings.push(format!(
+                        "service `{name}`: environment `{key}` has no value, ignored"
+                    )),
+                }
+            }
+        }
+        Value::Sequence(items) => {
+            for item in items.iter().filter_map(Value::as_str) {
+                match item.split_once('=') {
+                    Some((key, value)) => {
+                        env.insert(key.to_owned(), value.to_owned());
+                    }
+                    None => warnings.push(format!(
+                        "service `{name}`: environment `{item}` has no value, ignored"
+                    )),
+                }
+            }
+        }
+        _ => warnings.push(format!(
+            "service `{name}`: cannot read `environment`, ignored"
+        )),
+    }
+    env
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn shell_join(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|word| {
+            if word
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c))
+                && !word.is_empty()
+            {
+                word.clone()
+            } else {
+                format!("'{}'", word.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE: &str = r#"
+version: "3.8"
+services:
+  db:
+    image: postgres:16
+    ports: ["5432:5432"]
+  redis:
+    command: ["redis-server", "--port", "6379"]
+    restart: unless-stopped
+  api:
+    command: python -m api
+    working_dir: ./api
+    environment:
+      PORT: 8000
+      DEBUG: true
+      SECRET:
+    depends_on:
+      db:
+        condition: service_healthy
+      redis:
+        condition: service_started
+    restart: on-failure
+    healthcheck:
+      test: ["CMD", "curl", "-f", "http://localhost:8000"]
+  worker:
+    entrypoint: ["python", "-m"]
+    command: ["worker", "--queue", "default"]
+    environment:
+      - QUEUE=default
+      - FROM_HOST
+    depends_on: [api]
+    restart: "no"
+volumes:
+  data: {}
+"#;
+
+    #[test]
+    fn test_parse_compose() {
+        let mut warnings = Vec::new();
+        let raw = parse(COMPOSE, Path::new("docker-compose.yml"), &mut warnings).unwrap();
+
+        assert_eq!(
+            raw.services.keys().collect::<Vec<_>>(),
+            vec!["api", "redis", "worker"]
+        );
+
+        let api = &raw.services["api"];
+        assert_eq!(api.cmd, vec!["sh", "-c", "python -m api"]);
+        assert_eq!(api.cwd.as_deref(), Some("./api"));
+        assert_eq!(
+            api.env,
+            Some(BTreeMap::from_iter(vec![
+                ("DEBUG".to_owned(), "true".to_owned()),
+                ("PORT".to_owned(), "8000".to_owned()),
+            ]))
+        );
+        assert_eq!(api.deps, Some(vec!["redis".to_owned()]));
+        assert!(matches!(api.restart, Some(RawRestart::Always)));
+
+        let redis = &raw.services["redis"];
+        assert_eq!(redis.cmd, vec!["redis-server", "--port", "6379"]);
+        assert!(matches!(redis.restart, Some(RawRestart::Always)));
+
+        let worker = &raw.services["worker"];
+        assert_eq!(
+            worker.cmd,
+            vec!["python", "-m", "worker", "--queue", "default"]
+        );
+        assert_eq!(
+            worker.env,
+            Some(BTreeMap::from_iter(vec![(
+                "QUEUE".to_owned(),
+                "default".to_owned()
+            )]))
+        );
+        assert_eq!(worker.deps, Some(vec!["api".to_owned()]));
+        assert!(matches!(worker.restart, Some(RawRestart::Never)));
+
+        assert_eq!(
+            warnings,
+            vec![
+                "top-level `volumes` is not supported, ignored",
+                "service `db`: `image` is not supported, ignored",
+                "service `db`: `ports` is not supported, ignored",
+                "service `db`: no `command` or `entrypoint`, skipped (image-only services cannot run locally)",
+                "service `api`: environment `SECRET` has no value, ignored",
+                "service `api`: restart `on-failure` is approximated by `always`",
+                "service `api`: `healthcheck` is not supported yet, ignored",
+                "service `worker`: environment `FROM_HOST` has no value, ignored",
+                "service `api`: dependency `db` was skipped, dropped",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_env_file() {
+        let mut warnings = Vec::new();
+        let raw = parse(
+            "services:\n  app:\n    command: node server.js\n    env_file:\n      - .env\n      - path: .env.local\n        required: false\n",
+            Path::new("compose.yml"),
+            &mut warnings,
+        )
+        .unwrap();
+        let env_file = raw.services["app"].env_file.as_ref().unwrap();
+        assert_eq!(env_file.len(), 2);
+        assert_eq!(env_file[0].path(), ".env");
+        assert!(env_file[0].required());
+        assert_eq!(env_file[1].path(), ".env.local");
+        assert!(!env_file[1].required());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_compose_without_services() {
+        let mut warnings = Vec::new();
+        assert!(parse("version: '3'\n", Path::new("compose.yml"), &mut warnings).is_err());
+    }
+
+    #[test]
+    fn test_shell_entrypoint() {
+        let mut warnings = Vec::new();
+        let raw = parse(
+            "services:\n  app:\n    entrypoint: [\"node\", \"--inspect\"]\n    command: server.js --port 80\n",
+            Path::new("compose.yml"),
+            &mut warnings,
+        )
+        .unwrap();
+        assert_eq!(
+            raw.services["app"].cmd,
+            vec!["sh", "-c", "node --inspect server.js --port 80"]
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_shell_join() {
+        assert_eq!(
+            shell_join(&["echo".to_owned(), "it's".to_owned(), String::new()]),
+            r"echo 'it'\''s' ''"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+#[cfg(all(feature = "toml", feature = "yaml"))]
+mod compose;
+pub(crate) mod procfile;
+
+#[cfg(feature = "toml")]
+use std::path::Path;
+
+#[cfg(feature = "toml")]
+use crate::{raw::RawProject, ConfigError};
+
+/// A configuration converted from another tool's format.
+#[cfg(feature = "toml")]
+#[derive(Debug)]
+pub struct Import {
+    /// The converted configuration, rendered as `tutti.toml`.
+    pub config: String,
+    /// Everything that could not be translated.
+    pub warnings: Vec<String>,
+}
+
+#[cfg(feature = "toml")]
+fn render(raw: &RawProject, warnings: Vec<String>) -> Result<Import, ConfigError> {
+    let config = toml::to_string(raw)?;
+    Ok(Import { config, warnings })
+}
+
+/// Convert a foreman/honcho style `Procfile` into a `tutti.toml` configuration.
+///
+/// # Errors
+///
+/// Returns a `ConfigError` if the Procfile cannot be parsed.
+#[cfg(feature = "toml")]
+pub fn import_procfile(text: &str, path: &Path) -> Result<Import, ConfigError> {
+    let raw = procfile::parse(text, path)?;
+    render(&raw, Vec::new())
+}
+
+/// Convert a docker-compose file into a `tutti.toml` configuration.
+///
+/// # Errors
+///
+/// Returns a `ConfigError` if the compose file cannot be parsed.
+#[cfg(all(feature = "toml", feature = "yaml"))]
+pub fn import_compose(text: &str, path: &Path) -> Result<Import, ConfigError> {
+    let mut warnings = Vec::new();
+    let raw = compose::parse(text, path, &mut warnings)?;
+    render(&raw, warnings)
+}
@@ -0,0 +1,101 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    diagnostic::{Diagnostic, SourceMap},
+    raw::{RawProject, RawService},
+    ConfigError,
+};
+
+/// Parse a foreman/honcho style `Procfile`.
+///
+/// Every `name: command` line becomes a service whose command runs through `sh -c`,
+/// exactly like foreman does.
+pub(crate) fn parse(text: &str, path: &Path) -> Result<RawProject, ConfigError> {
+    let source = SourceMap::new(text);
+    let mut services = BTreeMap::new();
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        let Some((name, command)) = trimmed.split_once(':') else {
+            return Err(ConfigError::Parse(Diagnostic::new(
+                path,
+                "expected `name: command`",
+                Some(source.span(start + indent..start + indent + trimmed.len())),
+            )));
+        };
+
+        let (name, command) = (name.trim(), command.trim());
+        if name.is_empty() || command.is_empty() {
+            return Err(ConfigError::Parse(Diagnostic::new(
+                path,
+                "process name and command cannot be empty",
+                Some(source.span(start + indent..start + indent + trimmed.len())),
+            )));
+        }
+        if services.contains_key(name) {
+            return Err(ConfigError::Parse(Diagnostic::new(
+                path,
+                format!("process `{name}` is defined more than once"),
+                Some(source.span(start + indent..start + indent + name.len())),
+            )));
+        }
+
+        services.insert(
+            name.to_owned(),
+            RawService {
+                cmd: vec!["sh".to_owned(), "-c".to_owned(), command.to_owned()].into(),
+                ..RawService::default()
+            },
+        );
+    }
+
+    Ok(RawProject {
+        version: 1,
+        env_file: None,
+        include: None,
+        shell: None,
+        services,
+        groups: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_procfile() {
+        let text = "# comment\nweb: bundle exec rails s -p $PORT\n\nworker:bundle exec sidekiq\n";
+        let raw = parse(text, Path::new("Procfile")).unwrap();
+        assert_eq!(raw.services.len(), 2);
+        assert_eq!(
+            raw.services["web"].cmd,
+            vec!["sh", "-c", "bundle exec rails s -p $PORT"]
+        );
+        assert_eq!(
+            raw.services["worker"].cmd,
+            vec!["sh", "-c", "bundle exec sidekiq"]
+        );
+    }
+
+    #[test]
+    fn test_parse_procfile_errors() {
+        let err = parse("web: ok\nbroken line\n", Path::new("Procfile")).unwrap_err();
+        let ConfigError::Parse(diagnostic) = err else {
+            panic!("expected parse error");
+        };
+        assert_eq!(diagnostic.span.unwrap().line, 2);
+
+        assert!(parse("web: a\nweb: b\n", Path::new("Procfile")).is_err());
+        assert!(parse("web:\n", Path::new("Procfile")).is_err());
+    }
+}
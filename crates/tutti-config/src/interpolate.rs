@@ -0,0 +1,182 @@
+/// Error produced while expanding `${...}` expressions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum InterpolationError {
+    /// `${VAR:?message}` with `VAR` unset (or empty).
+    Required { name: String, message: String },
+    /// `${` without a matching `}`.
+    Unclosed,
+    /// `${}` or a name with invalid characters.
+    InvalidName(String),
+}
+
+impl std::fmt::Display for InterpolationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterpolationError::Required { name, message } if message.is_empty() => {
+                write!(f, "required variable `{name}` is not set")
+            }
+            InterpolationError::Required { name, message } => {
+                write!(f, "required variable `{name}` is not set: {message}")
+            }
+            InterpolationError::Unclosed => write!(f, "unclosed `${{` in interpolation"),
+            InterpolationError::InvalidName(name) => {
+                write!(f, "invalid variable name `{name}` in interpolation")
+            }
+        }
+    }
+}
+
+/// Expand `${VAR}`, `${VAR:-default}`, `${VAR-default}`, `${VAR:?error}` and `${VAR?error}`.
+///
+/// Unset variables without a default expand to an empty string. `$${` produces a literal `${`,
+/// any other `$` is kept as is so that shell snippets like `$HOME` keep working.
+pub(crate) fn interpolate<F>(input: &str, lookup: &F) -> Result<String, InterpolationError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(idx) = rest.find('$') {
+        output.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        if rest.starts_with("$${") {
+            output.push_str("${");
+            rest = &rest[3..];
+            continue;
+        }
+        if !rest.starts_with("${") {
+            output.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let end = closing_brace(&rest[2..]).ok_or(InterpolationError::Unclosed)?;
+        let expression = &rest[2..2 + end];
+        output.push_str(&expand(expression, lookup)?);
+        rest = &rest[2 + end + 1..];
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// Position of the `}` closing an expression, taking nested `${...}` into account.
+fn closing_brace(input: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let bytes = input.as_bytes();
+    for (idx, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'{' if idx > 0 && bytes[idx - 1] == b'$' => depth += 1,
+            b'}' if depth == 0 => return Some(idx),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn expand<F>(expression: &str, lookup: &F) -> Result<String, InterpolationError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let name_end = expression
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(expression.len());
+    let (name, modifier) = expression.split_at(name_end);
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(InterpolationError::InvalidName(expression.to_owned()));
+    }
+
+    let value = lookup(name);
+    let (operator, argument) = if let Some(argument) = modifier.strip_prefix(":-") {
+        (":-", argument)
+    } else if let Some(argument) = modifier.strip_prefix(":?") {
+        (":?", argument)
+    } else if let Some(argument) = modifier.strip_prefix('-') {
+        ("-", argument)
+    } else if let Some(argument) = modifier.strip_prefix('?') {
+        ("?", argument)
+    } else if modifier.is_empty() {
+        ("", "")
+    } else {
+        return Err(InterpolationError::InvalidName(expression.to_owned()));
+    };
+
+    let missing = match operator {
+        ":-" | ":?" => value.as_deref().is_none_or(str::is_empty),
+        _ => value.is_none(),
+    };
+
+    match operator {
+        ":-" | "-" if missing => interpolate(argument, lookup),
+        ":?" | "?" if missing => Err(InterpolationError::Required {
+            name: name.to_owned(),
+            message: interpolate(argument, lookup)?,
+        }),
+        _ => Ok(value.unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn run(input: &str) -> Result<String, InterpolationError> {
+        let vars: HashMap<&str, &str> =
+            HashMap::from_iter([("PORT", "3000"), ("EMPTY", ""), ("HOST", "localhost")]);
+        interpolate(input, &|name: &str| vars.get(name).map(|v| (*v).to_owned()))
+    }
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(run("no variables").unwrap(), "no variables");
+        assert_eq!(run("${HOST}:${PORT}").unwrap(), "localhost:3000");
+        assert_eq!(run("${MISSING}").unwrap(), "");
+        assert_eq!(run("$HOME and $").unwrap(), "$HOME and $");
+        assert_eq!(run("$${PORT}").unwrap(), "${PORT}");
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert_eq!(run("${MISSING:-8080}").unwrap(), "8080");
+        assert_eq!(run("${EMPTY:-8080}").unwrap(), "8080");
+        assert_eq!(run("${EMPTY-8080}").unwrap(), "");
+        assert_eq!(run("${PORT:-8080}").unwrap(), "3000");
+        assert_eq!(run("${MISSING:-${HOST}}").unwrap(), "localhost");
+    }
+
+    #[test]
+    fn test_required() {
+        assert_eq!(
+            run("${SECRET:?set it in .env}"),
+            Err(InterpolationError::Required {
+                name: "SECRET".to_owned(),
+                message: "set it in .env".to_owned()
+            })
+        );
+        assert!(run("${EMPTY:?}").is_err());
+        assert_eq!(run("${EMPTY?}").unwrap(), "");
+        assert_eq!(run("${PORT:?}").unwrap(), "3000");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(run("${PORT"), Err(InterpolationError::Unclosed));
+        assert!(matches!(
+            run("${}"),
+            Err(InterpolationError::InvalidName(_))
+        ));
+        assert!(matches!(
+            run("${1ST}"),
+            Err(InterpolationError::InvalidName(_))
+        ));
+        assert!(matches!(
+            run("${PORT+x}"),
+            Err(InterpolationError::InvalidName(_))
+        ));
+    }
+}
@@ -1,20 +1,69 @@
 use tutti_types::Project;
 
 mod adapter;
+mod diagnostic;
+mod env_file;
+mod glob;
+mod import;
+mod interpolate;
+mod merge;
 mod raw;
+mod shell_words;
+mod validate;
+
+pub use diagnostic::{Diagnostic, Span};
+#[cfg(all(feature = "toml", feature = "yaml"))]
+pub use import::import_compose;
+#[cfg(feature = "toml")]
+pub use import::{import_procfile, Import};
 
 /// Error type for configuration parsing.
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
-    #[cfg(feature = "toml")]
-    #[error("toml parse error: {0}")]
-    Toml(#[from] toml::de::Error),
+    #[error("parse error: {0}")]
+    Parse(Diagnostic),
 
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("validation error(s): {0}")]
-    Validation(String),
+    #[error("unsupported config format: {0}")]
+    UnsupportedFormat(String),
+
+    #[cfg(feature = "toml")]
+    #[error("toml render error: {0}")]
+    TomlRender(#[from] toml::ser::Error),
+
+    #[error("validation error(s): {}", display_diagnostics(.0))]
+    Validation(Vec<Diagnostic>),
+}
+
+impl ConfigError {
+    /// Diagnostics attached to this error, if any.
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            ConfigError::Parse(diagnostic) => std::slice::from_ref(diagnostic),
+            ConfigError::Validation(diagnostics) => diagnostics,
+            ConfigError::Io(_) | ConfigError::UnsupportedFormat(_) => &[],
+            #[cfg(feature = "toml")]
+            ConfigError::TomlRender(_) => &[],
+        }
+    }
+}
+
+fn display_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Environment of the current process, used for `${VAR}` interpolation.
+fn shell_env() -> std::collections::HashMap<String, String> {
+    std::env::vars_os()
+        .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+        .collect()
 }
 
 /// Load a project configuration from a file path.
@@ -29,18 +78,45 @@ pub fn load_from_path(path: &std::path::Path) -> Result<Project, ConfigError> {
 
 /// Parse a project configuration from a string.
 ///
+/// Files referenced by `include` and the override file next to `path` are read from disk.
+///
 /// # Errors
 ///
 /// Returns a `ConfigError` if the configuration string cannot be parsed.
 pub fn parse_auto(text: &str, path: &std::path::Path) -> Result<Project, ConfigError> {
+    into_project(document(text, path)?)
+}
+
+/// Parse a single configuration file, picking the format from its name.
+fn document(text: &str, path: &std::path::Path) -> Result<merge::Document, ConfigError> {
+    if path.file_name().and_then(|s| s.to_str()) == Some("Procfile") {
+        return procfile_document(text, path);
+    }
+
     let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
     match ext {
         #[cfg(feature = "toml")]
-        "toml" => parse_toml(text, path),
-        _ => Err(ConfigError::Validation("unknown config extension".into())),
+        "toml" => toml_document(text, path),
+        #[cfg(feature = "yaml")]
+        "yaml" | "yml" => yaml_document(text, path),
+        #[cfg(feature = "json")]
+        "json" => json_document(text, path),
+        _ => Err(ConfigError::UnsupportedFormat(format!(
+            "unknown config extension `{ext}`"
+        ))),
     }
 }
 
+/// Read and parse a single configuration file.
+pub(crate) fn read_document(path: &std::path::Path) -> Result<merge::Document, ConfigError> {
+    let text = std::fs::read_to_string(path)?;
+    document(&text, path)
+}
+
+fn into_project(document: merge::Document) -> Result<Project, ConfigError> {
+    merge::compose(document).to_project(&shell_env())
+}
+
 /// Parse a project configuration from a string.
 ///
 /// # Errors
@@ -48,15 +124,83 @@ pub fn parse_auto(text: &str, path: &std::path::Path) -> Result<Project, ConfigE
 /// Returns a `ConfigError` if the configuration string cannot be parsed.
 #[cfg(feature = "toml")]
 pub fn parse_toml(config: &str, path: &std::path::Path) -> Result<Project, ConfigError> {
-    let raw_project = toml::from_str::<raw::RawProject>(config)?;
-    raw_project.to_project(path)
+    into_project(toml_document(config, path)?)
+}
+
+#[cfg(feature = "toml")]
+fn toml_document(config: &str, path: &std::path::Path) -> Result<merge::Document, ConfigError> {
+    let source = diagnostic::SourceMap::from_toml(config);
+    let raw_project = toml::from_str::<raw::RawProject>(config).map_err(|err| {
+        let span = err.span().map(|range| source.span(range));
+        ConfigError::Parse(Diagnostic::new(path, err.message().trim_end(), span))
+    })?;
+    Ok(merge::Document::new(path, source, raw_project))
+}
+
+/// Parse a project configuration from a `Procfile`.
+///
+/// # Errors
+///
+/// Returns a `ConfigError` if the Procfile cannot be parsed.
+pub fn parse_procfile(config: &str, path: &std::path::Path) -> Result<Project, ConfigError> {
+    into_project(procfile_document(config, path)?)
+}
+
+fn procfile_document(config: &str, path: &std::path::Path) -> Result<merge::Document, ConfigError> {
+    let source = diagnostic::SourceMap::new(config);
+    let raw_project = import::procfile::parse(config, path)?;
+    Ok(merge::Document::new(path, source, raw_project))
+}
+
+/// Parse a project configuration from a YAML string.
+///
+/// # Errors
+///
+/// Returns a `ConfigError` if the configuration string cannot be parsed.
+#[cfg(feature = "yaml")]
+pub fn parse_yaml(config: &str, path: &std::path::Path) -> Result<Project, ConfigError> {
+    into_project(yaml_document(config, path)?)
+}
+
+#[cfg(feature = "yaml")]
+fn yaml_document(config: &str, path: &std::path::Path) -> Result<merge::Document, ConfigError> {
+    let source = diagnostic::SourceMap::new(config);
+    let raw_project = serde_yaml_ng::from_str::<raw::RawProject>(config).map_err(|err| {
+        let span = err
+            .location()
+            .map(|location| source.location(location.line(), location.column()));
+        ConfigError::Parse(Diagnostic::new(path, err.to_string(), span))
+    })?;
+    Ok(merge::Document::new(path, source, raw_project))
+}
+
+/// Parse a project configuration from a JSON string.
+///
+/// # Errors
+///
+/// Returns a `ConfigError` if the configuration string cannot be parsed.
+#[cfg(feature = "json")]
+pub fn parse_json(config: &str, path: &std::path::Path) -> Result<Project, ConfigError> {
+    into_project(json_document(config, path)?)
+}
+
+#[cfg(feature = "json")]
+fn json_document(config: &str, path: &std::path::Path) -> Result<merge::Document, ConfigError> {
+    let source = diagnostic::SourceMap::new(config);
+    let raw_project = serde_json::from_str::<raw::RawProject>(config).map_err(|err| {
+        let span = (err.line() > 0).then(|| source.location(err.line(), err.column()));
+        ConfigError::Parse(Diagnostic::new(path, err.to_string(), span))
+    })?;
+    Ok(merge::Document::new(path, source, raw_project))
 }
 
 #[cfg(test)]
 mod tests {
     use std::{collections::HashMap, path::PathBuf};
 
-    use tutti_types::Restart;
+    use tutti_types::{
+        Hook, HookFailure, InheritEnv, LogFormat, ProjectId, Restart, Rlimit, RlimitValue,
+    };
 
     use super::*;
 
@@ -67,20 +211,20 @@ mod tests {
 
             [services.api]
             cmd = ["cargo","run","--bin","api"]
-            cwd = "/home/user"
+            cwd = "/tmp"
             env = { RUST_LOG = "info" }
             deps = ["db"]
             restart = "always"
 
             [services.db]
-            cmd = ["postgres","-D",".pg"]
+            cmd = ["sh","-c","postgres -D .pg"]
             restart = "never"
         "#;
-        let p = parse_toml(txt, std::path::Path::new("config.toml")).unwrap();
+        let p = parse_toml(txt, std::path::Path::new("/tmp/config.toml")).unwrap();
         assert!(p.services.contains_key("api"));
-        assert_eq!(p.version, 100500);
+        assert_eq!(p.version, 100_500);
         assert_eq!(p.services["api"].cmd, vec!["cargo", "run", "--bin", "api"]);
-        assert_eq!(p.services["api"].cwd, Some(PathBuf::from("/home/user")));
+        assert_eq!(p.services["api"].cwd, Some(PathBuf::from("/tmp")));
         assert_eq!(
             p.services["api"].env,
             Some(HashMap::from_iter(vec![(
@@ -90,8 +234,8 @@ mod tests {
         );
         assert_eq!(p.services["api"].deps, vec!["db"]);
         assert_eq!(p.services["api"].restart, Restart::Always);
-        assert_eq!(p.services["db"].cmd, vec!["postgres", "-D", ".pg"]);
-        assert_eq!(p.services["db"].cwd, None);
+        assert_eq!(p.services["db"].cmd, vec!["sh", "-c", "postgres -D .pg"]);
+        assert_eq!(p.services["db"].cwd, Some(PathBuf::from("/tmp")));
         assert_eq!(p.services["db"].env, None);
         assert!(p.services["db"].deps.is_empty());
         assert_eq!(p.services["db"].restart, Restart::Never);
@@ -104,7 +248,7 @@ mod tests {
             cmd = ["cargo","run","--bin","api"]
 
             [services.db]
-            cmd = ["postgres","-D",".pg"]
+            cmd = ["sh","-c","postgres -D .pg"]
         "#;
         let p = parse_auto(txt, std::path::Path::new("config.toml")).unwrap();
         assert!(p.services.contains_key("api"));
@@ -112,11 +256,202 @@ mod tests {
         assert_eq!(p.version, 1);
     }
 
+    #[cfg(feature = "yaml")]
     #[test]
-    fn parse_auto_unknown_format() {
+    fn parse_yaml_ok() {
         let txt = r#"
-            UnknownFormat
+version: 100500
+
+services:
+  api:
+    cmd: ["cargo", "run", "--bin", "api"]
+    cwd: /tmp
+    env:
+      RUST_LOG: info
+    deps: ["db"]
+    restart: always
+
+  db:
+    cmd: ["sh", "-c", "postgres -D .pg"]
+    restart: never
+"#;
+        let p = parse_yaml(txt, std::path::Path::new("/tmp/config.yaml")).unwrap();
+        assert!(p.services.contains_key("api"));
+        assert_eq!(p.version, 100_500);
+        assert_eq!(p.services["api"].cmd, vec!["cargo", "run", "--bin", "api"]);
+        assert_eq!(p.services["api"].cwd, Some(PathBuf::from("/tmp")));
+        assert_eq!(
+            p.services["api"].env,
+            Some(HashMap::from_iter(vec![(
+                "RUST_LOG".to_string(),
+                "info".to_string()
+            )]))
+        );
+        assert_eq!(p.services["api"].deps, vec!["db"]);
+        assert_eq!(p.services["api"].restart, Restart::Always);
+        assert_eq!(p.services["db"].cmd, vec!["sh", "-c", "postgres -D .pg"]);
+        assert_eq!(p.services["db"].cwd, Some(PathBuf::from("/tmp")));
+        assert_eq!(p.services["db"].env, None);
+        assert!(p.services["db"].deps.is_empty());
+        assert_eq!(p.services["db"].restart, Restart::Never);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn parse_json_ok() {
+        let txt = r#"
+            {
+                "version": 100500,
+                "services": {
+                    "api": {
+                        "cmd": ["cargo", "run", "--bin", "api"],
+                        "cwd": "/tmp",
+                        "env": { "RUST_LOG": "info" },
+                        "deps": ["db"],
+                        "restart": "always"
+                    },
+                    "db": {
+                        "cmd": ["sh", "-c", "postgres -D .pg"],
+                        "restart": "never"
+                    }
+                }
+            }
+        "#;
+        let p = parse_json(txt, std::path::Path::new("/tmp/config.json")).unwrap();
+        assert!(p.services.contains_key("api"));
+        assert_eq!(p.version, 100_500);
+        assert_eq!(p.services["api"].cmd, vec!["cargo", "run", "--bin", "api"]);
+        assert_eq!(p.services["api"].cwd, Some(PathBuf::from("/tmp")));
+        assert_eq!(
+            p.services["api"].env,
+            Some(HashMap::from_iter(vec![(
+                "RUST_LOG".to_string(),
+                "info".to_string()
+            )]))
+        );
+        assert_eq!(p.services["api"].deps, vec!["db"]);
+        assert_eq!(p.services["api"].restart, Restart::Always);
+        assert_eq!(p.services["db"].cmd, vec!["sh", "-c", "postgres -D .pg"]);
+        assert_eq!(p.services["db"].cwd, Some(PathBuf::from("/tmp")));
+        assert_eq!(p.services["db"].env, None);
+        assert!(p.services["db"].deps.is_empty());
+        assert_eq!(p.services["db"].restart, Restart::Never);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn parse_auto_yaml_ok() {
+        let txt = r#"
+services:
+  api:
+    cmd: ["cargo", "run", "--bin", "api"]
+  db:
+    cmd: ["sh", "-c", "postgres -D .pg"]
+"#;
+        for name in ["config.yaml", "config.yml"] {
+            let p = parse_auto(txt, std::path::Path::new(name)).unwrap();
+            assert!(p.services.contains_key("api"));
+            assert_eq!(p.services["api"].cmd, vec!["cargo", "run", "--bin", "api"]);
+            assert_eq!(p.version, 1);
+        }
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn parse_auto_json_ok() {
+        let txt = r#"
+            {
+                "services": {
+                    "api": { "cmd": ["cargo", "run", "--bin", "api"] },
+                    "db": { "cmd": ["sh", "-c", "postgres -D .pg"] }
+                }
+            }
         "#;
+        let p = parse_auto(txt, std::path::Path::new("config.json")).unwrap();
+        assert!(p.services.contains_key("api"));
+        assert_eq!(p.services["api"].cmd, vec!["cargo", "run", "--bin", "api"]);
+        assert_eq!(p.version, 1);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn parse_yaml_unknown_field() {
+        let txt = "services:\n  api:\n    cmd: [sh]\n    comand: [sh]\n";
+        let err = parse_yaml(txt, std::path::Path::new("tutti.yaml")).unwrap_err();
+        let ConfigError::Parse(diagnostic) = &err else {
+            panic!("expected parse error, got {err:?}");
+        };
+        assert!(diagnostic.message.contains("unknown field `comand`"));
+        assert_eq!(diagnostic.span.as_ref().unwrap().line, 4);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn parse_json_unknown_field() {
+        let txt = "{\n  \"services\": {\n    \"api\": {\n      \"cmd\": [\"sh\"],\n      \"comand\": [\"sh\"]\n    }\n  }\n}\n";
+        let err = parse_json(txt, std::path::Path::new("tutti.json")).unwrap_err();
+        let ConfigError::Parse(diagnostic) = &err else {
+            panic!("expected parse error, got {err:?}");
+        };
+        assert!(diagnostic.message.contains("unknown field `comand`"));
+        assert_eq!(diagnostic.span.as_ref().unwrap().line, 5);
+    }
+
+    #[test]
+    fn parse_toml_collects_all_errors() {
+        let txt = r#"
+[services.api]
+cmd = ["sh", "-c", "exit 0"]
+deps = ["api", "dbx"]
+cwd = "/definitely/not/a/dir"
+
+[services."bad name"]
+cmd = ["definitely-not-a-command"]
+"#;
+        let err = parse_toml(txt, std::path::Path::new("tutti.toml")).unwrap_err();
+        let ConfigError::Validation(diagnostics) = &err else {
+            panic!("expected validation error, got {err:?}");
+        };
+        let located: Vec<_> = diagnostics
+            .iter()
+            .map(|d| {
+                let span = d.span.as_ref().unwrap();
+                (d.message.as_str(), span.line, span.column)
+            })
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                ("service `api`: cwd `/definitely/not/a/dir` does not exist or is not a directory", 5, 7),
+                ("service `api`: depends on itself", 4, 9),
+                ("service `api`: depends on unknown service `dbx`", 4, 16),
+                ("service `bad name`: invalid name, expected ASCII letters, digits, `-`, `_` or `.` starting with a letter or digit", 7, 11),
+                ("service `bad name`: command `definitely-not-a-command` not found or not executable", 8, 8),
+            ]
+        );
+        assert_eq!(err.diagnostics().len(), 5);
+    }
+
+    #[test]
+    fn parse_toml_unknown_field() {
+        let txt = r#"
+[services.api]
+cmd = ["sh"]
+comand = ["sh"]
+"#;
+        let err = parse_toml(txt, std::path::Path::new("tutti.toml")).unwrap_err();
+        let ConfigError::Parse(diagnostic) = &err else {
+            panic!("expected parse error, got {err:?}");
+        };
+        assert!(diagnostic.message.contains("unknown field `comand`"));
+        assert_eq!(diagnostic.span.as_ref().unwrap().line, 4);
+    }
+
+    #[test]
+    fn parse_auto_unknown_format() {
+        let txt = r"
+            UnknownFormat
+        ";
         let result = parse_auto(txt, std::path::Path::new("config.unknown"));
         assert!(result.is_err());
     }
@@ -128,6 +463,487 @@ mod tests {
         assert!(p.services.contains_key("service"));
     }
 
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn load_from_path_yaml_ok() {
+        let path = PathBuf::from("../../tests/assets/correct_config.yaml");
+        let p = load_from_path(&path).unwrap();
+        assert!(p.services.contains_key("service"));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn load_from_path_json_ok() {
+        let path = PathBuf::from("../../tests/assets/correct_config.json");
+        let p = load_from_path(&path).unwrap();
+        assert!(p.services.contains_key("service"));
+    }
+
+    #[test]
+    fn load_from_path_resolves_relative_paths() {
+        let assets = std::fs::canonicalize("../../tests/assets").unwrap();
+        let p = load_from_path(&PathBuf::from("../../tests/assets/relative_cwd.toml")).unwrap();
+        assert_eq!(p.id, ProjectId(assets.join("relative_cwd.toml")));
+        assert_eq!(p.services["api"].cwd, Some(assets.join("env_file")));
+        assert_eq!(p.services["root"].cwd, Some(assets.clone()));
+
+        let same =
+            load_from_path(&PathBuf::from("../../tests/assets/./relative_cwd.toml")).unwrap();
+        assert_eq!(same.id, p.id);
+    }
+
+    #[test]
+    fn load_from_path_merges_includes_and_override() {
+        let dir = std::fs::canonicalize("../../tests/assets/compose").unwrap();
+        let p = load_from_path(&PathBuf::from("../../tests/assets/compose/tutti.toml")).unwrap();
+        assert_eq!(
+            p.services.keys().collect::<Vec<_>>(),
+            vec!["api", "web", "worker"]
+        );
+
+        let api = &p.services["api"];
+        assert_eq!(api.cmd, vec!["echo", "api"]);
+        assert_eq!(api.cwd, Some(dir.join("services/api")));
+        let env = api.env.as_ref().unwrap();
+        assert_eq!(env["PORT"], "3000");
+        assert_eq!(env["LOG"], "debug");
+
+        let web = &p.services["web"];
+        assert_eq!(web.cmd, vec!["echo", "web"]);
+        assert_eq!(web.env, api.env);
+        assert_eq!(web.cwd, Some(dir.clone()));
+
+        let worker = &p.services["worker"];
+        assert_eq!(worker.deps, vec!["api"]);
+        assert_eq!(worker.env.as_ref().unwrap()["QUEUE"], "local");
+        assert_eq!(worker.cwd, Some(dir.join("services/worker")));
+    }
+
+    #[test]
+    fn load_from_path_reports_errors_in_included_files() {
+        let path = PathBuf::from("../../tests/assets/compose_error/tutti.toml");
+        let err = load_from_path(&path).unwrap_err();
+        let diagnostics = err.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+
+        assert_eq!(diagnostics[0].file, path);
+        assert_eq!(
+            diagnostics[0].message,
+            "included file `missing.toml` does not exist"
+        );
+        assert_eq!(diagnostics[0].span.as_ref().unwrap().column, 31);
+
+        assert_eq!(
+            diagnostics[1].file,
+            PathBuf::from("../../tests/assets/compose_error/team/tutti.toml")
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "service `worker`: depends on unknown service `db`"
+        );
+        assert_eq!(diagnostics[1].span.as_ref().unwrap().line, 3);
+    }
+
+    #[test]
+    fn load_from_path_env_file() {
+        let path = PathBuf::from("../../tests/assets/env_file/tutti.toml");
+        let p = load_from_path(&path).unwrap();
+
+        let api = &p.services["api"];
+        assert_eq!(api.cmd, vec!["echo", "listening on localhost:4000"]);
+        let env = api.env.as_ref().unwrap();
+        assert_eq!(env["HOST"], "localhost");
+        assert_eq!(env["PORT"], "4000");
+        assert_eq!(env["URL"], "http://localhost:4000");
+        assert_eq!(env["LOG_LEVEL"], "info");
+
+        let worker = &p.services["worker"];
+        assert_eq!(worker.cmd, vec!["echo", "default"]);
+        assert_eq!(worker.env.as_ref().unwrap()["PORT"], "3000");
+    }
+
+    #[test]
+    fn parse_toml_inherit_env() {
+        let toml = r#"
+[services.default]
+cmd = ["echo"]
+
+[services.clean]
+cmd = ["echo"]
+inherit_env = false
+
+[services.allowed]
+cmd = ["echo"]
+inherit_env = ["PATH", "HOME"]
+"#;
+        let p = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap();
+        assert_eq!(p.services["default"].inherit_env, InheritEnv::All);
+        assert_eq!(p.services["clean"].inherit_env, InheritEnv::None);
+        assert_eq!(
+            p.services["allowed"].inherit_env,
+            InheritEnv::Only(vec!["PATH".to_owned(), "HOME".to_owned()])
+        );
+    }
+
+    #[test]
+    fn parse_toml_profiles_and_groups() {
+        let toml = r#"
+[groups]
+backend = ["api", "worker"]
+
+[services.api]
+cmd = ["echo"]
+
+[services.worker]
+cmd = ["echo"]
+profiles = ["backend", "debug"]
+"#;
+        let p = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap();
+        assert!(p.services["api"].profiles.is_empty());
+        assert_eq!(p.services["worker"].profiles, vec!["backend", "debug"]);
+        assert_eq!(p.groups["backend"], vec!["api", "worker"]);
+
+        let toml = r#"
+[groups]
+backend = ["api", "missing"]
+
+[services.api]
+cmd = ["echo"]
+"#;
+        let err = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap_err();
+        let diagnostics = err.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "group `backend`: unknown service `missing`"
+        );
+        assert!(diagnostics[0].span.is_some());
+    }
+
+    #[test]
+    fn parse_toml_hooks() {
+        let toml = r#"
+[services.api]
+cmd = ["echo"]
+env = { SOCKET = "api.sock" }
+
+[services.api.hooks]
+pre_start = ["npm", "install"]
+post_stop = { cmd = ["rm", "-f", "${SOCKET}"], timeout = 5, on_failure = "continue" }
+"#;
+        let p = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap();
+        let hooks = &p.services["api"].hooks;
+        assert_eq!(
+            hooks.pre_start,
+            Some(Hook {
+                cmd: vec!["npm".to_owned(), "install".to_owned()],
+                timeout: 60,
+                on_failure: HookFailure::Fail,
+            })
+        );
+        assert_eq!(
+            hooks.post_stop,
+            Some(Hook {
+                cmd: vec!["rm".to_owned(), "-f".to_owned(), "api.sock".to_owned()],
+                timeout: 5,
+                on_failure: HookFailure::Continue,
+            })
+        );
+        assert!(hooks.post_start.is_none());
+
+        let toml = r#"
+[services.api]
+cmd = ["echo"]
+hooks = { pre_stop = { cmd = [], timeout = 0 } }
+"#;
+        let err = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap_err();
+        let messages: Vec<&str> = err
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "service `api`: pre_stop hook cmd is empty or contains empty element",
+                "service `api`: pre_stop hook timeout must be greater than zero",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_toml_abstract_services() {
+        let toml = r#"
+[services.rust]
+abstract = true
+env = { RUST_LOG = "info" }
+
+[services.api]
+extends = "rust"
+cmd = ["cargo", "run", "--bin", "api"]
+
+[services.worker]
+extends = "rust"
+cmd = ["cargo", "run", "--bin", "worker"]
+deps = ["api"]
+"#;
+        let p = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap();
+        assert_eq!(p.services.keys().collect::<Vec<_>>(), vec!["api", "worker"]);
+        assert_eq!(
+            p.services["worker"].env.as_ref().unwrap()["RUST_LOG"],
+            "info"
+        );
+
+        let toml = r#"
+[services.rust]
+abstract = true
+
+[services.api]
+cmd = ["echo"]
+deps = ["rust"]
+"#;
+        let err = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap_err();
+        let messages: Vec<&str> = err
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec!["service `api`: depends on unknown service `rust`"]
+        );
+    }
+
+    #[test]
+    fn parse_toml_shell_cmd() {
+        let toml = r#"
+shell = "/bin/sh -ec"
+
+[services.web]
+cmd = "echo ${GREETING:-hi} | tee web.log"
+
+[services.api]
+cmd = "echo 'my api' \"$HOME\""
+shell = false
+
+[services.worker]
+cmd = "echo worker > out.txt"
+shell = true
+"#;
+        let p = parse_toml(toml, &PathBuf::from("/tmp/tutti.toml")).unwrap();
+        assert_eq!(
+            p.services["web"].cmd,
+            vec!["/bin/sh", "-ec", "echo hi | tee web.log"]
+        );
+        assert_eq!(p.services["api"].cmd, vec!["echo", "my api", "$HOME"]);
+        assert_eq!(
+            p.services["worker"].cmd,
+            vec!["sh", "-c", "echo worker > out.txt"]
+        );
+
+        let toml = r#"
+[services.web]
+cmd = "echo 'unbalanced"
+"#;
+        let err = parse_toml(toml, &PathBuf::from("/tmp/tutti.toml")).unwrap_err();
+        let diagnostics = err.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "service `web`: cannot parse cmd: unbalanced single quote"
+        );
+        assert!(diagnostics[0].span.is_some());
+    }
+
+    #[test]
+    fn parse_toml_process_settings() {
+        let toml = r#"
+[services.api]
+cmd = ["echo"]
+user = "www-data"
+umask = "027"
+nice = 10
+rlimits = { nofile = 65536, core = "unlimited" }
+
+[services.worker]
+cmd = ["echo"]
+group = "1000"
+umask = 0o022
+"#;
+        let p = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap();
+        let api = &p.services["api"];
+        assert_eq!(api.user.as_deref(), Some("www-data"));
+        assert_eq!(api.group, None);
+        assert_eq!(api.umask, Some(0o027));
+        assert_eq!(api.nice, Some(10));
+        assert_eq!(
+            api.rlimits.iter().collect::<Vec<_>>(),
+            vec![
+                (&Rlimit::Nofile, &RlimitValue::Limited(65536)),
+                (&Rlimit::Core, &RlimitValue::Unlimited),
+            ]
+        );
+        let worker = &p.services["worker"];
+        assert_eq!(worker.group.as_deref(), Some("1000"));
+        assert_eq!(worker.umask, Some(0o022));
+        assert!(worker.rlimits.is_empty());
+
+        let toml = r#"
+[services.api]
+cmd = ["echo"]
+user = ""
+umask = "0999"
+nice = 20
+"#;
+        let err = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap_err();
+        let messages: Vec<&str> = err
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "service `api`: invalid umask `0999`, expected an octal mode such as \"022\"",
+                "service `api`: user cannot be empty",
+                "service `api`: nice 20 is out of range, expected -20 to 19",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_toml_limits() {
+        let toml = r#"
+[services.api]
+cmd = ["echo"]
+max_memory = "1.5G"
+max_cpu_percent = 150
+limit_grace_period = 30
+
+[services.worker]
+cmd = ["echo"]
+max_memory = 1048576
+"#;
+        let p = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap();
+        let api = &p.services["api"];
+        assert_eq!(api.max_memory, Some(1536 * 1024 * 1024));
+        assert_eq!(api.max_cpu_percent, Some(150));
+        assert_eq!(api.limit_grace_period, Some(30));
+        let worker = &p.services["worker"];
+        assert_eq!(worker.max_memory, Some(1024 * 1024));
+        assert_eq!(worker.max_cpu_percent, None);
+        assert_eq!(worker.limit_grace_period, None);
+
+        let toml = r#"
+[services.api]
+cmd = ["echo"]
+max_memory = "2X"
+max_cpu_percent = 0
+"#;
+        let err = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap_err();
+        let messages: Vec<&str> = err
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "service `api`: invalid max_memory `2X`, expected a size such as \"512M\" or \"2G\"",
+                "service `api`: max_cpu_percent must be greater than zero",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_toml_log_format() {
+        let toml = r#"
+[services.api]
+cmd = ["node", "server.js"]
+log_format = "json"
+
+[services.proxy]
+cmd = ["echo"]
+"#;
+        let p = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap();
+        assert_eq!(p.services["api"].log_format, LogFormat::Json);
+        assert_eq!(p.services["proxy"].log_format, LogFormat::Text);
+
+        let invalid = r#"
+[services.api]
+cmd = ["echo"]
+log_format = "xml"
+"#;
+        assert!(parse_toml(invalid, &PathBuf::from("tutti.toml")).is_err());
+    }
+
+    #[test]
+    fn parse_toml_tty() {
+        let toml = r#"
+[services.web]
+cmd = ["npm", "run", "dev"]
+tty = true
+
+[services.api]
+cmd = ["echo"]
+"#;
+        let p = parse_toml(toml, &PathBuf::from("tutti.toml")).unwrap();
+        assert!(p.services["web"].tty);
+        assert!(!p.services["api"].tty);
+    }
+
+    #[test]
+    fn parse_toml_missing_required_variable() {
+        let toml = r#"
+[services.api]
+cmd = ["echo", "${TUTTI_TEST_UNSET_SECRET:?set it in .env}"]
+env_file = ["does-not-exist.env"]
+"#;
+        let err = parse_toml(toml, &PathBuf::from("/tmp/tutti.toml")).unwrap_err();
+        let messages: Vec<&str> = err
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0]
+            .starts_with("service `api`: cannot read env file `/tmp/does-not-exist.env`"));
+        assert_eq!(
+            messages[1],
+            "service `api`: required variable `TUTTI_TEST_UNSET_SECRET` is not set: set it in .env"
+        );
+        assert_eq!(err.diagnostics()[1].span.as_ref().unwrap().line, 3);
+    }
+
+    #[test]
+    fn parse_auto_procfile_ok() {
+        let txt = "web: echo hello\nworker: sleep 1\n";
+        let p = parse_auto(txt, std::path::Path::new("/tmp/Procfile")).unwrap();
+        assert_eq!(p.services["web"].cmd, vec!["sh", "-c", "echo hello"]);
+        assert_eq!(p.services["worker"].cmd, vec!["sh", "-c", "sleep 1"]);
+    }
+
+    #[test]
+    fn import_procfile_ok() {
+        let txt = "web: echo hello\n";
+        let import = import_procfile(txt, std::path::Path::new("Procfile")).unwrap();
+        assert!(import.warnings.is_empty());
+        let p = parse_toml(&import.config, std::path::Path::new("tutti.toml")).unwrap();
+        assert_eq!(p.services["web"].cmd, vec!["sh", "-c", "echo hello"]);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn import_compose_ok() {
+        let txt = "services:\n  db:\n    command: [sh, -c, 'exec sleep 10']\n    restart: always\n  api:\n    command: echo api\n    environment: [PORT=8000]\n    depends_on: [db]\n";
+        let import = import_compose(txt, std::path::Path::new("docker-compose.yml")).unwrap();
+        assert!(import.warnings.is_empty());
+        let p = parse_toml(&import.config, std::path::Path::new("tutti.toml")).unwrap();
+        assert_eq!(p.services["api"].cmd, vec!["sh", "-c", "echo api"]);
+        assert_eq!(p.services["api"].deps, vec!["db"]);
+        assert_eq!(p.services["db"].restart, Restart::Always);
+    }
+
     #[test]
     fn load_from_path_unknown_format() {
         let path = PathBuf::from("../../tests/assets/unknown_format.toml");
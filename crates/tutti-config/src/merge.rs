@@ -0,0 +1,612 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use tutti_types::HookKind;
+
+use crate::{
+    diagnostic::{Diagnostic, SourceMap},
+    glob,
+    raw::{RawHooks, RawProject, RawService},
+    read_document, ConfigError,
+};
+
+/// A single configuration file.
+#[derive(Debug)]
+pub(crate) struct Document {
+    /// Path as given, used when reporting errors.
+    pub path: PathBuf,
+    /// Absolute, symlink-free path.
+    pub canonical: PathBuf,
+    pub source: SourceMap,
+    pub raw: RawProject,
+}
+
+impl Document {
+    pub fn new(path: &Path, source: SourceMap, raw: RawProject) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            canonical: canonical_path(path),
+            source,
+            raw,
+        }
+    }
+
+    /// Directory relative paths in this file are resolved against.
+    pub fn dir(&self) -> &Path {
+        self.canonical.parent().unwrap_or_else(|| Path::new("/"))
+    }
+}
+
+/// Where a setting of the merged project was written.
+#[derive(Debug, Clone)]
+struct Origin {
+    document: usize,
+    path: Vec<String>,
+}
+
+/// Every file that contributed to a project, and which of them each setting came from.
+#[derive(Debug)]
+pub(crate) struct Sources {
+    documents: Vec<Document>,
+    main: usize,
+    origins: HashMap<Vec<String>, Origin>,
+}
+
+impl Sources {
+    /// The file the main configuration was loaded from.
+    pub fn main(&self) -> &Document {
+        &self.documents[self.main]
+    }
+
+    /// Find the file that defined `path` of the merged project, and the path of that setting
+    /// within the file. Settings without a recorded origin belong to the main file.
+    pub fn locate(&self, path: &[&str]) -> (&Document, Vec<String>) {
+        for len in (1..=path.len()).rev() {
+            let prefix: Vec<String> = path[..len].iter().map(|s| (*s).to_owned()).collect();
+            if let Some(origin) = self.origins.get(&prefix) {
+                let mut source_path = origin.path.clone();
+                source_path.extend(path[len..].iter().map(|s| (*s).to_owned()));
+                return (&self.documents[origin.document], source_path);
+            }
+        }
+        (self.main(), path.iter().map(|s| (*s).to_owned()).collect())
+    }
+
+    fn record(&mut self, path: Vec<String>, document: usize, source_path: Vec<String>) {
+        self.origins.insert(
+            path,
+            Origin {
+                document,
+                path: source_path,
+            },
+        );
+    }
+}
+
+/// A project merged from its main file, included files and the override file.
+#[derive(Debug)]
+pub(crate) struct Composition {
+    pub sources: Sources,
+    pub project: RawProject,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Merge `main` with everything it includes and its override file.
+///
+/// Precedence, lowest first: included files (in the order they are listed, glob matches sorted
+/// by path), the file including them, the override file. A service defined several times is
+/// merged field by field: `env` maps are merged key by key, every other field is replaced by the
+/// file loaded last. `extends` is resolved afterwards, on the merged services.
+pub(crate) fn compose(main: Document) -> Composition {
+    let override_path = override_path(&main.path);
+
+    let mut loader = Loader::default();
+    loader.add(main);
+    let main = loader.documents.len() - 1;
+
+    if let Some(path) = override_path.filter(|path| path.is_file()) {
+        match read_document(&path) {
+            Ok(document) => loader.add(document),
+            Err(err) => loader
+                .diagnostics
+                .extend(error_diagnostics(&err, &path, None)),
+        }
+    }
+
+    let mut sources = Sources {
+        documents: loader.documents,
+        main,
+        origins: HashMap::new(),
+    };
+    let mut diagnostics = loader.diagnostics;
+
+    let mut project = RawProject {
+        version: sources.main().raw.version,
+        ..RawProject::default()
+    };
+    for idx in 0..sources.documents.len() {
+        merge_document(&mut project, &mut sources, idx);
+    }
+
+    for (name, message) in resolve_extends(&mut project.services, &mut sources) {
+        let (document, path) = sources.locate(&["services", &name, "extends"]);
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        let span = document.source.value(&path);
+        diagnostics.push(Diagnostic::new(&document.path, message, span));
+    }
+    // Templates have served their purpose, and need not be complete services.
+    project
+        .services
+        .retain(|_, service| service.is_abstract != Some(true));
+
+    Composition {
+        sources,
+        project,
+        diagnostics,
+    }
+}
+
+/// `tutti.toml` is overridden by `tutti.override.toml`, `tutti.yaml` by `tutti.override.yaml`...
+fn override_path(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let extension = path.extension()?.to_str()?;
+    Some(path.with_file_name(format!("{stem}.override.{extension}")))
+}
+
+/// Absolute, symlink-free form of the config path, so that every spelling of it maps to the same
+/// project. Falls back to a lexically absolute path for files that do not exist on disk.
+fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Loads documents depth-first, so that included files come before the file including them.
+#[derive(Default)]
+struct Loader {
+    documents: Vec<Document>,
+    diagnostics: Vec<Diagnostic>,
+    loaded: HashSet<PathBuf>,
+    stack: Vec<PathBuf>,
+}
+
+impl Loader {
+    fn add(&mut self, document: Document) {
+        self.loaded.insert(document.canonical.clone());
+        self.stack.push(document.canonical.clone());
+
+        let base = document.path.parent().unwrap_or_else(|| Path::new(""));
+        for (idx, pattern) in document.raw.include.iter().flatten().enumerate() {
+            let idx = idx.to_string();
+            let span = document.source.value(&["include", idx.as_str()]);
+            let error = |message: String| Diagnostic::new(&document.path, message, span.clone());
+
+            let paths = glob::expand(base, pattern);
+            if paths.is_empty() && !glob::is_pattern(pattern) {
+                self.diagnostics
+                    .push(error(format!("included file `{pattern}` does not exist")));
+                continue;
+            }
+
+            for path in paths {
+                let canonical = canonical_path(&path);
+                if self.stack.contains(&canonical) {
+                    self.diagnostics.push(error(format!(
+                        "`{}` is included recursively",
+                        path.display()
+                    )));
+                    continue;
+                }
+                if self.loaded.contains(&canonical) {
+                    continue;
+                }
+                match read_document(&path) {
+                    Ok(included) => self.add(included),
+                    Err(err) => self.diagnostics.extend(error_diagnostics(
+                        &err,
+                        &document.path,
+                        span.clone(),
+                    )),
+                }
+            }
+        }
+
+        self.stack.pop();
+        self.documents.push(document);
+    }
+}
+
+/// Diagnostics for a file that failed to load; errors without a location of their own are
+/// reported at `span` in `file`.
+fn error_diagnostics(
+    err: &ConfigError,
+    file: &Path,
+    span: Option<crate::diagnostic::Span>,
+) -> Vec<Diagnostic> {
+    if err.diagnostics().is_empty() {
+        vec![Diagnostic::new(file, err.to_string(), span)]
+    } else {
+        err.diagnostics().to_vec()
+    }
+}
+
+fn merge_document(project: &mut RawProject, sources: &mut Sources, idx: usize) {
+    let document = &sources.documents[idx];
+    let env_files = document.raw.env_file.clone().unwrap_or_default();
+    let services = document.raw.services.clone();
+    let groups = document.raw.groups.clone().unwrap_or_default();
+
+    if let Some(shell) = document.raw.shell.clone() {
+        let path = vec!["shell".to_owned()];
+        sources.record(path.clone(), idx, path);
+        project.shell = Some(shell);
+    }
+
+    let merged_env_files = project.env_file.get_or_insert_with(Vec::new);
+    for (file_idx, env_file) in env_files.into_iter().enumerate() {
+        sources.record(
+            vec!["env_file".to_owned(), merged_env_files.len().to_string()],
+            idx,
+            vec!["env_file".to_owned(), file_idx.to_string()],
+        );
+        merged_env_files.push(env_file);
+    }
+    if merged_env_files.is_empty() {
+        project.env_file = None;
+    }
+
+    for (name, members) in groups {
+        let path = vec!["groups".to_owned(), name.clone()];
+        sources.record(path.clone(), idx, path);
+        project
+            .groups
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name, members);
+    }
+
+    for (name, layer) in services {
+        let service_path = vec!["services".to_owned(), name.clone()];
+        let target = project.services.entry(name.clone()).or_insert_with(|| {
+            sources.record(service_path.clone(), idx, service_path.clone());
+            RawService::default()
+        });
+        for field in overlay(target, &layer) {
+            let mut path = service_path.clone();
+            path.extend(field);
+            sources.record(path.clone(), idx, path);
+        }
+    }
+}
+
+/// Apply the settings of `layer` on top of `target` and return the paths (relative to the
+/// service) that were set. `env`, `rlimits` and `hooks` are merged key by key, every other field
+/// is replaced.
+fn overlay(target: &mut RawService, layer: &RawService) -> Vec<Vec<String>> {
+    let mut set = Vec::new();
+    let mut replace = |field: &str, is_set: bool| {
+        if is_set {
+            set.push(vec![field.to_owned()]);
+        }
+        is_set
+    };
+
+    if replace("cmd", !layer.cmd.is_empty()) {
+        target.cmd.clone_from(&layer.cmd);
+    }
+    if replace("shell", layer.shell.is_some()) {
+        target.shell.clone_from(&layer.shell);
+    }
+    if replace("extends", layer.extends.is_some()) {
+        target.extends.clone_from(&layer.extends);
+    }
+    if replace("abstract", layer.is_abstract.is_some()) {
+        target.is_abstract = layer.is_abstract;
+    }
+    if replace("cwd", layer.cwd.is_some()) {
+        target.cwd.clone_from(&layer.cwd);
+    }
+    if replace("env_file", layer.env_file.is_some()) {
+        target.env_file.clone_from(&layer.env_file);
+    }
+    if replace("inherit_env", layer.inherit_env.is_some()) {
+        target.inherit_env.clone_from(&layer.inherit_env);
+    }
+    if replace("profiles", layer.profiles.is_some()) {
+        target.profiles.clone_from(&layer.profiles);
+    }
+    if replace("deps", layer.deps.is_some()) {
+        target.deps.clone_from(&layer.deps);
+    }
+    if replace("user", layer.user.is_some()) {
+        target.user.clone_from(&layer.user);
+    }
+    if replace("group", layer.group.is_some()) {
+        target.group.clone_from(&layer.group);
+    }
+    if replace("umask", layer.umask.is_some()) {
+        target.umask.clone_from(&layer.umask);
+    }
+    if replace("nice", layer.nice.is_some()) {
+        target.nice = layer.nice;
+    }
+    if replace("tty", layer.tty.is_some()) {
+        target.tty = layer.tty;
+    }
+    if replace("stdin_open", layer.stdin_open.is_some()) {
+        target.stdin_open = layer.stdin_open;
+    }
+    if replace("log_format", layer.log_format.is_some()) {
+        target.log_format = layer.log_format;
+    }
+    if replace("max_memory", layer.max_memory.is_some()) {
+        target.max_memory.clone_from(&layer.max_memory);
+    }
+    if replace("max_cpu_percent", layer.max_cpu_percent.is_some()) {
+        target.max_cpu_percent = layer.max_cpu_percent;
+    }
+    if replace("limit_grace_period", layer.limit_grace_period.is_some()) {
+        target.limit_grace_period = layer.limit_grace_period;
+    }
+    if replace("restart", layer.restart.is_some()) {
+        target.restart.clone_from(&layer.restart);
+    }
+    if replace("healthcheck", layer.healthcheck.is_some()) {
+        target.healthcheck = layer.healthcheck;
+    }
+
+    if let Some(env) = &layer.env {
+        set.push(vec!["env".to_owned()]);
+        let target_env = target.env.get_or_insert_with(BTreeMap::new);
+        for (key, value) in env {
+            set.push(vec!["env".to_owned(), key.clone()]);
+            target_env.insert(key.clone(), value.clone());
+        }
+    }
+
+    if let Some(rlimits) = &layer.rlimits {
+        set.push(vec!["rlimits".to_owned()]);
+        let target_rlimits = target.rlimits.get_or_insert_with(BTreeMap::new);
+        for (resource, value) in rlimits {
+            set.push(vec!["rlimits".to_owned(), resource.to_string()]);
+            target_rlimits.insert(*resource, *value);
+        }
+    }
+
+    if let Some(hooks) = &layer.hooks {
+        set.push(vec!["hooks".to_owned()]);
+        let target_hooks = target.hooks.get_or_insert_with(RawHooks::default);
+        for kind in HookKind::ALL {
+            if let Some(hook) = hooks.get(kind) {
+                set.push(vec!["hooks".to_owned(), kind.to_string()]);
+                *target_hooks.get_mut(kind) = Some(hook.clone());
+            }
+        }
+    }
+
+    set
+}
+
+/// Replace every service that `extends` another one by the combination of both, returning the
+/// services whose `extends` could not be resolved along with the reason.
+fn resolve_extends(
+    services: &mut BTreeMap<String, RawService>,
+    sources: &mut Sources,
+) -> Vec<(String, String)> {
+    let mut resolver = ExtendsResolver {
+        services,
+        sources,
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+        errors: Vec::new(),
+    };
+    let names: Vec<String> = resolver.services.keys().cloned().collect();
+    for name in names {
+        resolver.resolve(&name);
+    }
+    resolver.errors
+}
+
+struct ExtendsResolver<'a> {
+    services: &'a mut BTreeMap<String, RawService>,
+    sources: &'a mut Sources,
+    resolved: HashMap<String, bool>,
+    stack: Vec<String>,
+    errors: Vec<(String, String)>,
+}
+
+impl ExtendsResolver<'_> {
+    /// Resolve `name` and return whether it (and everything it extends) is valid.
+    fn resolve(&mut self, name: &str) -> bool {
+        if self.stack.iter().any(|entry| entry == name) {
+            let mut chain = self.stack.clone();
+            chain.push(name.to_owned());
+            self.errors.push((
+                name.to_owned(),
+                format!("service `{name}`: `extends` cycle {}", chain.join(" -> ")),
+            ));
+            return false;
+        }
+        if let Some(valid) = self.resolved.get(name) {
+            return *valid;
+        }
+
+        let valid = self.extend(name);
+        self.resolved.insert(name.to_owned(), valid);
+        valid
+    }
+
+    fn extend(&mut self, name: &str) -> bool {
+        let Some(base) = self.services.get(name).and_then(|s| s.extends.clone()) else {
+            return true;
+        };
+        if !self.services.contains_key(&base) {
+            self.errors.push((
+                name.to_owned(),
+                format!("service `{name}`: extends unknown service `{base}`"),
+            ));
+            return false;
+        }
+
+        self.stack.push(name.to_owned());
+        let valid = self.resolve(&base);
+        self.stack.pop();
+        if !valid {
+            return false;
+        }
+
+        let own = self.services[name].clone();
+        let mut combined = self.services[&base].clone();
+        overlay(&mut combined, &own);
+        combined.extends = None;
+        // Extending a template makes a regular service, unless it says otherwise.
+        combined.is_abstract = own.is_abstract;
+        self.services.insert(name.to_owned(), combined);
+        self.inherit_origins(name, &base);
+        true
+    }
+
+    /// Settings copied from `base` keep pointing at where they were written in `base`.
+    fn inherit_origins(&mut self, name: &str, base: &str) {
+        let within = |path: &Vec<String>, service: &str| {
+            path.len() > 2 && path[0] == "services" && path[1] == service
+        };
+
+        let own: Vec<(Vec<String>, Origin)> = self
+            .sources
+            .origins
+            .iter()
+            .filter(|(path, _)| within(path, name))
+            .map(|(path, origin)| (path.clone(), origin.clone()))
+            .collect();
+        let inherited: Vec<(Vec<String>, Origin)> = self
+            .sources
+            .origins
+            .iter()
+            .filter(|(path, _)| within(path, base))
+            .map(|(path, origin)| {
+                let mut path = path.clone();
+                name.clone_into(&mut path[1]);
+                (path, origin.clone())
+            })
+            .collect();
+
+        self.sources.origins.extend(inherited);
+        self.sources.origins.extend(own);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::RawCmd;
+
+    use super::*;
+
+    fn service(cmd: &[&str], env: &[(&str, &str)]) -> RawService {
+        RawService {
+            cmd: RawCmd::Argv(cmd.iter().map(|s| (*s).to_owned()).collect()),
+            env: (!env.is_empty()).then(|| {
+                env.iter()
+                    .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+                    .collect()
+            }),
+            ..RawService::default()
+        }
+    }
+
+    #[test]
+    fn test_override_path() {
+        assert_eq!(
+            override_path(Path::new("dir/tutti.toml")),
+            Some(PathBuf::from("dir/tutti.override.toml"))
+        );
+        assert_eq!(
+            override_path(Path::new("tutti.config.yaml")),
+            Some(PathBuf::from("tutti.config.override.yaml"))
+        );
+        assert_eq!(override_path(Path::new("Procfile")), None);
+    }
+
+    #[test]
+    fn test_overlay() {
+        let mut target = service(&["echo", "base"], &[("A", "1"), ("B", "2")]);
+        let layer = RawService {
+            cwd: Some("api".to_owned()),
+            ..service(&[], &[("B", "3")])
+        };
+
+        let set = overlay(&mut target, &layer);
+        assert_eq!(target.cmd, vec!["echo", "base"]);
+        assert_eq!(target.cwd.as_deref(), Some("api"));
+        let env = target.env.unwrap();
+        assert_eq!(env["A"], "1");
+        assert_eq!(env["B"], "3");
+        assert_eq!(
+            set,
+            vec![
+                vec!["cwd".to_owned()],
+                vec!["env".to_owned()],
+                vec!["env".to_owned(), "B".to_owned()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extends() {
+        let mut project = RawProject::default();
+        project
+            .services
+            .insert("base".to_owned(), service(&["cargo", "run"], &[("A", "1")]));
+        project.services.insert(
+            "api".to_owned(),
+            RawService {
+                extends: Some("base".to_owned()),
+                ..service(&[], &[("B", "2")])
+            },
+        );
+        project.services.insert(
+            "loop".to_owned(),
+            RawService {
+                extends: Some("loop".to_owned()),
+                ..service(&["echo"], &[])
+            },
+        );
+        project.services.insert(
+            "orphan".to_owned(),
+            RawService {
+                extends: Some("missing".to_owned()),
+                ..service(&["echo"], &[])
+            },
+        );
+
+        let mut sources = Sources {
+            documents: vec![Document::new(
+                Path::new("tutti.toml"),
+                SourceMap::default(),
+                RawProject::default(),
+            )],
+            main: 0,
+            origins: HashMap::new(),
+        };
+        let errors = resolve_extends(&mut project.services, &mut sources);
+
+        let api = &project.services["api"];
+        assert_eq!(api.cmd, vec!["cargo", "run"]);
+        assert_eq!(api.env.as_ref().unwrap().len(), 2);
+        assert!(api.extends.is_none());
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    "loop".to_owned(),
+                    "service `loop`: `extends` cycle loop -> loop".to_owned()
+                ),
+                (
+                    "orphan".to_owned(),
+                    "service `orphan`: extends unknown service `missing`".to_owned()
+                ),
+            ]
+        );
+    }
+}
@@ -1,19 +1,60 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use tutti_types::{HookFailure, HookKind, LogFormat, Rlimit, RlimitValue};
 
-#[derive(Deserialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct RawProject {
     #[serde(default = "default_version")]
     pub version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<Vec<RawEnvFile>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<RawShell>,
+    #[serde(default)]
     pub services: BTreeMap<String, RawService>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<BTreeMap<String, Vec<String>>>,
 }
 
 fn default_version() -> u32 {
     1
 }
 
-#[derive(Deserialize)]
+fn default_required() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum RawEnvFile {
+    Path(String),
+    Detailed {
+        path: String,
+        #[serde(default = "default_required")]
+        required: bool,
+    },
+}
+
+impl RawEnvFile {
+    pub fn path(&self) -> &str {
+        match self {
+            RawEnvFile::Path(path) | RawEnvFile::Detailed { path, .. } => path,
+        }
+    }
+
+    pub fn required(&self) -> bool {
+        match self {
+            RawEnvFile::Path(_) => true,
+            RawEnvFile::Detailed { required, .. } => *required,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub(crate) enum RawRestart {
     #[serde(rename = "always")]
     Always,
@@ -21,12 +62,175 @@ pub(crate) enum RawRestart {
     Never,
 }
 
-#[derive(Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum RawInheritEnv {
+    All(bool),
+    Only(Vec<String>),
+}
+
+/// A command, either as argv or as a single string run through the shell.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum RawCmd {
+    Argv(Vec<String>),
+    Shell(String),
+}
+
+impl Default for RawCmd {
+    fn default() -> Self {
+        RawCmd::Argv(Vec::new())
+    }
+}
+
+impl From<Vec<String>> for RawCmd {
+    fn from(argv: Vec<String>) -> Self {
+        RawCmd::Argv(argv)
+    }
+}
+
+#[cfg(test)]
+impl PartialEq<Vec<&str>> for RawCmd {
+    fn eq(&self, other: &Vec<&str>) -> bool {
+        matches!(self, RawCmd::Argv(argv) if argv == other)
+    }
+}
+
+impl RawCmd {
+    pub fn is_empty(&self) -> bool {
+        matches!(self, RawCmd::Argv(argv) if argv.is_empty())
+    }
+}
+
+/// `true` for the default shell, `false` to split string commands into words, or the shell
+/// command line the string is appended to.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum RawShell {
+    Enabled(bool),
+    Command(String),
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct RawService {
-    pub cmd: Vec<String>,
+    #[serde(default, skip_serializing_if = "RawCmd::is_empty")]
+    pub cmd: RawCmd,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<RawShell>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// Whether the service is only a base for others to `extends`, which is never started.
+    #[serde(rename = "abstract", skip_serializing_if = "Option::is_none")]
+    pub is_abstract: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub cwd: Option<String>,
-    pub env: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<Vec<RawEnvFile>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inherit_env: Option<RawInheritEnv>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub deps: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<RawHooks>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub umask: Option<RawUmask>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rlimits: Option<BTreeMap<Rlimit, RlimitValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tty: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdin_open: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_format: Option<LogFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<RawSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cpu_percent: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_grace_period: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub restart: Option<RawRestart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub healthcheck: Option<()>, // TODO
 }
+
+/// A umask, either as a number (`0o022` in TOML) or as an octal string (`"022"`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum RawUmask {
+    Mode(u32),
+    Octal(String),
+}
+
+/// A size in bytes, either as a number or as a string with a unit such as `"512M"`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum RawSize {
+    Bytes(u64),
+    Text(String),
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RawHooks {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_start: Option<RawHook>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_start: Option<RawHook>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_stop: Option<RawHook>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_stop: Option<RawHook>,
+}
+
+impl RawHooks {
+    pub fn get(&self, kind: HookKind) -> Option<&RawHook> {
+        match kind {
+            HookKind::PreStart => self.pre_start.as_ref(),
+            HookKind::PostStart => self.post_start.as_ref(),
+            HookKind::PreStop => self.pre_stop.as_ref(),
+            HookKind::PostStop => self.post_stop.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: HookKind) -> &mut Option<RawHook> {
+        match kind {
+            HookKind::PreStart => &mut self.pre_start,
+            HookKind::PostStart => &mut self.post_start,
+            HookKind::PreStop => &mut self.pre_stop,
+            HookKind::PostStop => &mut self.post_stop,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum RawHook {
+    Cmd(RawCmd),
+    Detailed {
+        cmd: RawCmd,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        timeout: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        on_failure: Option<HookFailure>,
+    },
+}
+
+impl RawHook {
+    pub fn cmd(&self) -> &RawCmd {
+        match self {
+            RawHook::Cmd(cmd) | RawHook::Detailed { cmd, .. } => cmd,
+        }
+    }
+}
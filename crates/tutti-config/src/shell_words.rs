@@ -0,0 +1,124 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum SplitError {
+    UnterminatedSingleQuote,
+    UnterminatedDoubleQuote,
+    TrailingBackslash,
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitError::UnterminatedSingleQuote => write!(f, "unbalanced single quote"),
+            SplitError::UnterminatedDoubleQuote => write!(f, "unbalanced double quote"),
+            SplitError::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+/// Split a command line into words the way a POSIX shell would, without expanding anything.
+///
+/// Single quotes keep everything literally, double quotes only treat `\` before `"`, `\`, `$`,
+/// `` ` `` and newlines as an escape. Outside of quotes a backslash escapes any character and
+/// unquoted whitespace separates words.
+pub(crate) fn split(input: &str) -> Result<Vec<String>, SplitError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedSingleQuote),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(SplitError::UnterminatedDoubleQuote),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedDoubleQuote),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+                None => return Err(SplitError::TrailingBackslash),
+            },
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split("cargo run --bin api").unwrap(),
+            vec!["cargo", "run", "--bin", "api"]
+        );
+        assert_eq!(split("  echo   'a  b' ").unwrap(), vec!["echo", "a  b"]);
+        assert_eq!(
+            split(r#"echo "say \"hi\" to $USER" it\'s"#).unwrap(),
+            vec!["echo", "say \"hi\" to $USER", "it's"]
+        );
+        assert_eq!(
+            split(r#"echo "a\b" '' x"y"z"#).unwrap(),
+            vec!["echo", "a\\b", "", "xyz"]
+        );
+        assert_eq!(split("echo a\\\nb").unwrap(), vec!["echo", "ab"]);
+        assert!(split("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_split_errors() {
+        assert_eq!(
+            split("echo 'unterminated"),
+            Err(SplitError::UnterminatedSingleQuote)
+        );
+        assert_eq!(
+            split("echo \"unterminated"),
+            Err(SplitError::UnterminatedDoubleQuote)
+        );
+        assert_eq!(split("echo \\"), Err(SplitError::TrailingBackslash));
+    }
+}
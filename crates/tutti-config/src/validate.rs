@@ -0,0 +1,340 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use tutti_types::{HookKind, InheritEnv, Service};
+
+use crate::{diagnostic::Diagnostic, merge::Sources, ConfigError};
+
+/// Collects every problem found in a configuration instead of stopping at the first one.
+///
+/// Each problem is reported in the file that introduced the offending setting.
+pub(crate) struct Validator<'a> {
+    sources: &'a Sources,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(sources: &'a Sources) -> Self {
+        Self {
+            sources,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn error_at_key(&mut self, path: &[&str], message: impl Into<String>) {
+        let (document, path) = self.sources.locate(path);
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        let span = document.source.key(&path);
+        self.diagnostics
+            .push(Diagnostic::new(&document.path, message, span));
+    }
+
+    pub fn error_at_value(&mut self, path: &[&str], message: impl Into<String>) {
+        let (document, path) = self.sources.locate(path);
+        let path: Vec<&str> = path.iter().map(String::as_str).collect();
+        let span = document
+            .source
+            .value(&path)
+            .or_else(|| document.source.key(&path));
+        self.diagnostics
+            .push(Diagnostic::new(&document.path, message, span));
+    }
+
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn validate_services(&mut self, services: &BTreeMap<String, Service>) {
+        for (name, service) in services {
+            self.validate_service(name, service, services);
+        }
+    }
+
+    fn validate_service(
+        &mut self,
+        name: &str,
+        service: &Service,
+        services: &BTreeMap<String, Service>,
+    ) {
+        if !is_valid_service_name(name) {
+            self.error_at_key(
+                &["services", name],
+                format!(
+                    "service `{name}`: invalid name, expected ASCII letters, digits, `-`, `_` or `.` starting with a letter or digit"
+                ),
+            );
+        }
+
+        for (idx, profile) in service.profiles.iter().enumerate() {
+            if !is_valid_service_name(profile) {
+                let idx = idx.to_string();
+                self.error_at_value(
+                    &["services", name, "profiles", idx.as_str()],
+                    format!("service `{name}`: invalid profile name `{profile}`"),
+                );
+            }
+        }
+
+        self.validate_cmd(name, service);
+        self.validate_cwd(name, service);
+        self.validate_env(name, service);
+        self.validate_hooks(name, service);
+        self.validate_process(name, service);
+
+        for (idx, dep) in service.deps.iter().enumerate() {
+            let idx = idx.to_string();
+            let path = ["services", name, "deps", idx.as_str()];
+            if dep == name {
+                self.error_at_value(&path, format!("service `{name}`: depends on itself"));
+            } else if !services.contains_key(dep) {
+                self.error_at_value(
+                    &path,
+                    format!("service `{name}`: depends on unknown service `{dep}`"),
+                );
+            }
+        }
+    }
+
+    fn validate_cmd(&mut self, name: &str, service: &Service) {
+        if service.cmd.is_empty() {
+            self.error_at_value(
+                &["services", name, "cmd"],
+                format!("service `{name}`: cmd is missing or empty"),
+            );
+            return;
+        }
+
+        let mut has_empty = false;
+        for (idx, arg) in service.cmd.iter().enumerate() {
+            if arg.trim().is_empty() {
+                has_empty = true;
+                let idx = idx.to_string();
+                self.error_at_value(
+                    &["services", name, "cmd", idx.as_str()],
+                    format!("service `{name}`: cmd contains empty element"),
+                );
+            }
+        }
+        if has_empty {
+            return;
+        }
+
+        let program = &service.cmd[0];
+        let path_var = service
+            .env
+            .as_ref()
+            .and_then(|env| env.get("PATH").cloned())
+            .or_else(|| std::env::var("PATH").ok());
+        if find_executable(program, service.cwd.as_deref(), path_var.as_deref()).is_none() {
+            self.error_at_value(
+                &["services", name, "cmd", "0"],
+                format!("service `{name}`: command `{program}` not found or not executable"),
+            );
+        }
+    }
+
+    fn validate_hooks(&mut self, name: &str, service: &Service) {
+        for kind in HookKind::ALL {
+            let Some(hook) = service.hooks.get(kind) else {
+                continue;
+            };
+            let path = ["services", name, "hooks", kind.as_str()];
+            if hook.cmd.is_empty() || hook.cmd.iter().any(|arg| arg.trim().is_empty()) {
+                self.error_at_value(
+                    &path,
+                    format!("service `{name}`: {kind} hook cmd is empty or contains empty element"),
+                );
+            }
+            if hook.timeout == 0 {
+                self.error_at_value(
+                    &path,
+                    format!("service `{name}`: {kind} hook timeout must be greater than zero"),
+                );
+            }
+        }
+    }
+
+    fn validate_process(&mut self, name: &str, service: &Service) {
+        for (key, value) in [("user", &service.user), ("group", &service.group)] {
+            if value.as_ref().is_some_and(|value| value.trim().is_empty()) {
+                self.error_at_value(
+                    &["services", name, key],
+                    format!("service `{name}`: {key} cannot be empty"),
+                );
+            }
+        }
+        if let Some(umask) = service.umask.filter(|umask| *umask > 0o777) {
+            self.error_at_value(
+                &["services", name, "umask"],
+                format!("service `{name}`: umask {umask:o} is out of range, expected at most 777"),
+            );
+        }
+        for (key, value) in [
+            ("max_memory", service.max_memory),
+            ("max_cpu_percent", service.max_cpu_percent.map(u64::from)),
+        ] {
+            if value == Some(0) {
+                self.error_at_value(
+                    &["services", name, key],
+                    format!("service `{name}`: {key} must be greater than zero"),
+                );
+            }
+        }
+        if let Some(nice) = service.nice.filter(|nice| !(-20..=19).contains(nice)) {
+            self.error_at_value(
+                &["services", name, "nice"],
+                format!("service `{name}`: nice {nice} is out of range, expected -20 to 19"),
+            );
+        }
+    }
+
+    fn validate_cwd(&mut self, name: &str, service: &Service) {
+        let Some(cwd) = &service.cwd else {
+            return;
+        };
+        if !cwd.is_dir() {
+            self.error_at_value(
+                &["services", name, "cwd"],
+                format!(
+                    "service `{name}`: cwd `{}` does not exist or is not a directory",
+                    cwd.display()
+                ),
+            );
+        }
+    }
+
+    fn validate_env(&mut self, name: &str, service: &Service) {
+        if let InheritEnv::Only(names) = &service.inherit_env {
+            for (idx, key) in names.iter().enumerate() {
+                if key.is_empty() || key.contains('=') || key.contains('\0') {
+                    let idx = idx.to_string();
+                    self.error_at_value(
+                        &["services", name, "inherit_env", idx.as_str()],
+                        format!("service `{name}`: invalid environment variable name `{key}`"),
+                    );
+                }
+            }
+        }
+
+        let Some(env) = &service.env else {
+            return;
+        };
+        let mut keys: Vec<&String> = env.keys().collect();
+        keys.sort();
+        for key in keys {
+            if key.is_empty() || key.contains('=') || key.contains('\0') {
+                self.error_at_key(
+                    &["services", name, "env", key],
+                    format!("service `{name}`: invalid environment variable name `{key}`"),
+                );
+            }
+        }
+    }
+
+    pub fn validate_groups(
+        &mut self,
+        groups: &BTreeMap<String, Vec<String>>,
+        services: &BTreeMap<String, Service>,
+    ) {
+        for (group, members) in groups {
+            if !is_valid_service_name(group) {
+                self.error_at_key(
+                    &["groups", group],
+                    format!(
+                        "group `{group}`: invalid name, expected ASCII letters, digits, `-`, `_` or `.` starting with a letter or digit"
+                    ),
+                );
+            }
+            for (idx, member) in members.iter().enumerate() {
+                if !services.contains_key(member) {
+                    let idx = idx.to_string();
+                    self.error_at_value(
+                        &["groups", group, idx.as_str()],
+                        format!("group `{group}`: unknown service `{member}`"),
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<(), ConfigError> {
+        if self.diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Validation(self.diagnostics))
+        }
+    }
+}
+
+fn is_valid_service_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphanumeric())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Resolve `program` the way `execvp` would: paths containing `/` are taken relative to `cwd`,
+/// bare names are looked up in `path_var`.
+pub(crate) fn find_executable(
+    program: &str,
+    cwd: Option<&Path>,
+    path_var: Option<&str>,
+) -> Option<PathBuf> {
+    if program.contains('/') {
+        let candidate = match cwd {
+            Some(cwd) if Path::new(program).is_relative() => cwd.join(program),
+            _ => PathBuf::from(program),
+        };
+        return is_executable(&candidate).then_some(candidate);
+    }
+
+    std::env::split_paths(path_var?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    if !metadata.is_file() {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_names() {
+        assert!(is_valid_service_name("api"));
+        assert!(is_valid_service_name("api-v2.internal_1"));
+        assert!(is_valid_service_name("1st"));
+        assert!(!is_valid_service_name(""));
+        assert!(!is_valid_service_name("-api"));
+        assert!(!is_valid_service_name("@frontend"));
+        assert!(!is_valid_service_name("my service"));
+    }
+
+    #[test]
+    fn test_find_executable() {
+        assert!(find_executable("sh", None, Some("/usr/bin:/bin")).is_some());
+        assert!(find_executable("definitely-not-a-command", None, Some("/usr/bin:/bin")).is_none());
+        assert!(find_executable("sh", None, None).is_none());
+        assert!(find_executable("/bin/sh", None, None).is_some());
+        assert!(find_executable("./sh", Some(Path::new("/bin")), None).is_some());
+        assert!(find_executable("/tmp", None, None).is_none());
+    }
+}
@@ -8,12 +8,29 @@ pub enum Error {
     Internal(String),
     #[error("IO error: {0}")]
     IO(std::io::Error),
+    #[error("cannot start `{program}`: {source}")]
+    Spawn {
+        program: String,
+        source: std::io::Error,
+    },
+    #[error("user `{0}` not found")]
+    UnknownUser(String),
+    #[error("group `{0}` not found")]
+    UnknownGroup(String),
+    #[error("cannot run as {0}: the daemon is not running as root")]
+    NotPermitted(String),
     #[error("wait error")]
     Wait,
     #[error("project {0} not found")]
     ProjectNotFound(ProjectId),
     #[error("service {1} not found in project {0}")]
     ServiceNotFound(ProjectId, String),
+    #[error("service {0} is not running")]
+    ServiceNotRunning(String),
+    #[error("service {0} does not read input, set `stdin_open` or `tty` to send it some")]
+    NoStdin(String),
+    #[error("service {0} is not reading its input")]
+    InputFull(String),
     #[error("circular dependency detected")]
     CircularDependencyDetected,
 }
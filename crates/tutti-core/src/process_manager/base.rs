@@ -1,4 +1,6 @@
-use std::time::Duration;
+use std::{collections::HashMap, time::Duration};
+
+use tutti_types::{ProcessStats, WindowSize};
 
 use crate::{
     error::Result,
@@ -15,4 +17,12 @@ pub trait ProcessManager: Send + Sync {
     async fn wait(&mut self, id: ProcId, d: Duration) -> Result<Option<i32>>;
     /// Forcefully kill a process.
     async fn kill(&mut self, id: ProcId) -> Result<()>;
+    /// Resize the pseudo-terminal of a process started with `tty`. Does nothing for other
+    /// processes.
+    async fn resize(&mut self, id: ProcId, size: WindowSize) -> Result<()>;
+    /// Sample the resource usage of processes and everything they started, all at once.
+    ///
+    /// Processes that have exited are left out, and so are all of them if sampling is not
+    /// supported on this platform.
+    async fn stats(&mut self, ids: &[ProcId]) -> Result<HashMap<ProcId, ProcessStats>>;
 }
@@ -0,0 +1,227 @@
+use std::ffi::{c_char, c_int, CStr, CString};
+
+use libc::{gid_t, uid_t};
+
+use crate::error::{Error, Result};
+
+/// Largest buffer handed to the `get*_r` functions before giving up on an entry.
+const MAX_BUFFER: usize = 1 << 20;
+
+/// Identity a process switches to before it is executed.
+#[derive(Debug, Clone)]
+pub(crate) struct Credentials {
+    uid: uid_t,
+    gid: gid_t,
+    /// Supplementary groups, replaced when a user is given.
+    groups: Option<Vec<gid_t>>,
+}
+
+struct User {
+    uid: uid_t,
+    gid: Option<gid_t>,
+    name: Option<CString>,
+}
+
+impl Credentials {
+    /// Look up `user` and `group` (names or numeric ids).
+    ///
+    /// Without a `group` the user's primary group is used. Returns `None` if neither is given.
+    pub fn resolve(user: Option<&str>, group: Option<&str>) -> Result<Option<Self>> {
+        if user.is_none() && group.is_none() {
+            return Ok(None);
+        }
+
+        let user_entry = user
+            .map(|user| find_user(user).ok_or_else(|| Error::UnknownUser(user.to_owned())))
+            .transpose()?;
+        let gid = match group {
+            Some(group) => {
+                find_group(group).ok_or_else(|| Error::UnknownGroup(group.to_owned()))?
+            }
+            None => user_entry
+                .as_ref()
+                .and_then(|user| user.gid)
+                .unwrap_or_else(current_gid),
+        };
+        let uid = user_entry
+            .as_ref()
+            .map_or_else(current_uid, |user| user.uid);
+        let groups = user_entry.map(|user| match &user.name {
+            Some(name) => supplementary_groups(name, gid),
+            None => vec![gid],
+        });
+
+        if current_uid() != 0 && (uid != current_uid() || gid != current_gid()) {
+            let identity = match (user, group) {
+                (Some(user), _) => format!("user `{user}`"),
+                (None, group) => format!("group `{}`", group.unwrap_or_default()),
+            };
+            return Err(Error::NotPermitted(identity));
+        }
+
+        Ok(Some(Self { uid, gid, groups }))
+    }
+
+    /// Switch the calling process to these credentials. Meant to run between `fork` and `exec`,
+    /// so it only makes system calls.
+    pub fn apply(&self) -> std::io::Result<()> {
+        #[allow(unsafe_code)]
+        unsafe {
+            if let Some(groups) = self.groups.as_ref().filter(|_| libc::geteuid() == 0) {
+                if libc::setgroups(groups.len() as _, groups.as_ptr()) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if self.gid != libc::getegid() && libc::setgid(self.gid) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if self.uid != libc::geteuid() && libc::setuid(self.uid) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn current_uid() -> uid_t {
+    #[allow(unsafe_code)]
+    unsafe {
+        libc::geteuid()
+    }
+}
+
+fn current_gid() -> gid_t {
+    #[allow(unsafe_code)]
+    unsafe {
+        libc::getegid()
+    }
+}
+
+/// Look up a user by name or uid. A uid without a passwd entry is still accepted.
+fn find_user(user: &str) -> Option<User> {
+    let uid: Option<uid_t> = user.parse().ok();
+    let name = CString::new(user).ok()?;
+    let mut buffer: Vec<c_char> = vec![0; 1024];
+    loop {
+        #[allow(unsafe_code)]
+        let (rc, entry) = unsafe {
+            let mut entry: libc::passwd = std::mem::zeroed();
+            let mut result = std::ptr::null_mut();
+            let rc = match uid {
+                Some(uid) => libc::getpwuid_r(
+                    uid,
+                    &raw mut entry,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &raw mut result,
+                ),
+                None => libc::getpwnam_r(
+                    name.as_ptr(),
+                    &raw mut entry,
+                    buffer.as_mut_ptr(),
+                    buffer.len(),
+                    &raw mut result,
+                ),
+            };
+            let entry = (!result.is_null()).then(|| User {
+                uid: entry.pw_uid,
+                gid: Some(entry.pw_gid),
+                name: Some(CStr::from_ptr(entry.pw_name).to_owned()),
+            });
+            (rc, entry)
+        };
+
+        if rc == libc::ERANGE && buffer.len() < MAX_BUFFER {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        return entry.or_else(|| {
+            uid.map(|uid| User {
+                uid,
+                gid: None,
+                name: None,
+            })
+        });
+    }
+}
+
+/// Look up a group by name or gid.
+fn find_group(group: &str) -> Option<gid_t> {
+    if let Ok(gid) = group.parse() {
+        return Some(gid);
+    }
+
+    let name = CString::new(group).ok()?;
+    let mut buffer: Vec<c_char> = vec![0; 1024];
+    loop {
+        #[allow(unsafe_code)]
+        let (rc, gid) = unsafe {
+            let mut entry: libc::group = std::mem::zeroed();
+            let mut result = std::ptr::null_mut();
+            let rc = libc::getgrnam_r(
+                name.as_ptr(),
+                &raw mut entry,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &raw mut result,
+            );
+            (rc, (!result.is_null()).then_some(entry.gr_gid))
+        };
+
+        if rc == libc::ERANGE && buffer.len() < MAX_BUFFER {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        return gid;
+    }
+}
+
+/// Every group `name` is a member of, including `gid`.
+#[cfg(target_os = "linux")]
+fn supplementary_groups(name: &CStr, gid: gid_t) -> Vec<gid_t> {
+    let mut groups: Vec<gid_t> = vec![0; 64];
+    loop {
+        let mut count = c_int::try_from(groups.len()).unwrap_or(c_int::MAX);
+        #[allow(unsafe_code)]
+        let rc =
+            unsafe { libc::getgrouplist(name.as_ptr(), gid, groups.as_mut_ptr(), &raw mut count) };
+        let count = usize::try_from(count).unwrap_or_default();
+
+        if rc >= 0 {
+            groups.truncate(count);
+            return groups;
+        }
+        if count <= groups.len() || count > MAX_BUFFER {
+            return vec![gid];
+        }
+        groups.resize(count, 0);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn supplementary_groups(_name: &CStr, gid: gid_t) -> Vec<gid_t> {
+    vec![gid]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        assert!(Credentials::resolve(None, None).unwrap().is_none());
+
+        let uid = current_uid().to_string();
+        let credentials = Credentials::resolve(Some(&uid), None).unwrap().unwrap();
+        assert_eq!(credentials.uid, current_uid());
+
+        assert!(matches!(
+            Credentials::resolve(Some("tutti-no-such-user"), None),
+            Err(Error::UnknownUser(_))
+        ));
+        assert!(matches!(
+            Credentials::resolve(None, Some("tutti-no-such-group")),
+            Err(Error::UnknownGroup(_))
+        ));
+    }
+}
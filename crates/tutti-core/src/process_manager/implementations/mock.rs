@@ -1,35 +1,133 @@
-use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use async_trait::async_trait;
+use tokio::io::DuplexStream;
 use tokio_stream::wrappers::ReceiverStream;
+use tutti_types::{ProcessStats, WindowSize};
 
 use crate::{error::Result, CommandSpec, ProcId, ProcessManager, Spawned};
 
 #[derive(Default)]
 pub struct MockProcessManager {
     storage: Vec<CommandSpec>,
+    exit_codes: HashMap<String, i32>,
+    running: HashSet<String>,
+    lingering: HashSet<String>,
+    killed: HashSet<u64>,
+    stats: HashMap<String, ProcessStats>,
+    samples: usize,
+    stdin: HashMap<String, DuplexStream>,
+}
+
+impl MockProcessManager {
+    /// Make processes spawned under `name` exit with `code` instead of 0.
+    pub fn with_exit_code(mut self, name: &str, code: i32) -> Self {
+        self.exit_codes.insert(name.to_owned(), code);
+        self
+    }
+
+    /// Keep processes spawned under `name` running until they are killed.
+    pub fn with_running(mut self, name: &str) -> Self {
+        self.running.insert(name.to_owned());
+        self
+    }
+
+    /// Keep processes spawned under `name` with `with_running` running after they are killed,
+    /// until `exit_lingering` is called.
+    pub fn with_lingering(mut self, name: &str) -> Self {
+        self.lingering.insert(name.to_owned());
+        self
+    }
+
+    /// Let the killed processes spawned under `name` exit.
+    pub fn exit_lingering(&mut self, name: &str) {
+        self.lingering.remove(name);
+    }
+
+    /// Report `stats` for processes spawned under `name`.
+    pub fn with_stats(mut self, name: &str, stats: ProcessStats) -> Self {
+        self.stats.insert(name.to_owned(), stats);
+        self
+    }
+
+    /// Reading end of the stdin of the last process spawned under `name` with `stdin`.
+    pub fn take_stdin(&mut self, name: &str) -> Option<DuplexStream> {
+        self.stdin.remove(name)
+    }
+
+    /// How many times `stats` was called.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Names of the spawned processes, in order.
+    pub fn spawned(&self) -> Vec<&str> {
+        self.storage.iter().map(|spec| spec.name.as_str()).collect()
+    }
 }
 
 #[async_trait]
 impl ProcessManager for MockProcessManager {
     async fn spawn(&mut self, spec: CommandSpec) -> Result<Spawned> {
+        let id = ProcId(self.storage.len() as u64);
+        let stdin = spec.stdin.then(|| {
+            let (writer, reader) = tokio::io::duplex(1024);
+            self.stdin.insert(spec.name.clone(), reader);
+            Box::pin(writer) as _
+        });
         self.storage.push(spec);
         let (_, stdout) = tokio::sync::mpsc::channel(1);
         let (_, stderr) = tokio::sync::mpsc::channel(1);
         Ok(Spawned {
-            id: ProcId(0),
+            id,
             stdout: Box::pin(ReceiverStream::new(stdout)),
             stderr: Box::pin(ReceiverStream::new(stderr)),
+            stdin,
             pid: None,
         })
     }
     async fn shutdown(&mut self, _id: ProcId) -> Result<()> {
         todo!()
     }
-    async fn wait(&mut self, _id: ProcId, _d: Duration) -> Result<Option<i32>> {
-        todo!()
+    async fn wait(&mut self, id: ProcId, _d: Duration) -> Result<Option<i32>> {
+        let name = usize::try_from(id.0)
+            .ok()
+            .and_then(|idx| self.storage.get(idx))
+            .map(|spec| spec.name.as_str())
+            .unwrap_or_default();
+        if self.running.contains(name)
+            && (!self.killed.contains(&id.0) || self.lingering.contains(name))
+        {
+            return Ok(None);
+        }
+        Ok(Some(self.exit_codes.get(name).copied().unwrap_or_default()))
     }
-    async fn kill(&mut self, _id: ProcId) -> Result<()> {
-        todo!()
+    async fn kill(&mut self, id: ProcId) -> Result<()> {
+        self.killed.insert(id.0);
+        Ok(())
+    }
+    async fn resize(&mut self, _id: ProcId, _size: WindowSize) -> Result<()> {
+        Ok(())
+    }
+    async fn stats(&mut self, ids: &[ProcId]) -> Result<HashMap<ProcId, ProcessStats>> {
+        self.samples += 1;
+        Ok(ids
+            .iter()
+            .map(|id| {
+                let name = usize::try_from(id.0)
+                    .ok()
+                    .and_then(|idx| self.storage.get(idx))
+                    .map(|spec| spec.name.as_str())
+                    .unwrap_or_default();
+                let stats = self.stats.get(name).cloned().unwrap_or(ProcessStats {
+                    pid: u32::try_from(id.0).unwrap_or_default(),
+                    ..ProcessStats::default()
+                });
+                (*id, stats)
+            })
+            .collect())
     }
 }
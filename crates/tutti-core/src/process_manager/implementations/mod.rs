@@ -1,5 +1,11 @@
+#[cfg(unix)]
+mod credentials;
 #[cfg(test)]
 mod mock;
+#[cfg(target_os = "linux")]
+mod procfs;
+#[cfg(unix)]
+mod pty;
 #[cfg(unix)]
 mod unix;
 
@@ -0,0 +1,244 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+use libc::pid_t;
+
+/// Fields of `/proc/<pid>/stat` needed to describe the processes of a service.
+#[derive(Debug, Clone, PartialEq)]
+struct ProcStat {
+    pid: pid_t,
+    pgrp: pid_t,
+    session: pid_t,
+    /// `utime + stime + cutime + cstime`, so the time of exited children stays accounted for.
+    cpu_ticks: u64,
+    threads: u64,
+    start_ticks: u64,
+    rss_pages: u64,
+}
+
+/// Totals for a process and everything it started.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TreeSample {
+    /// CPU time of the tree in seconds.
+    pub cpu_seconds: f64,
+    /// Seconds since the root process started.
+    pub age_seconds: f64,
+    pub memory_rss: u64,
+    pub threads: u64,
+    pub open_fds: u64,
+    pub children: u64,
+}
+
+/// Sample the processes `roots` and everything they started, reading the process table once for
+/// all of them. Services run in a session of their own with their root as leader, so the
+/// processes are grouped by their session and process group rather than by walking parents: that
+/// way children reparented after a double fork, or after the shell that started them exited, are
+/// still counted. Roots none of whose processes exist anymore are left out.
+pub(crate) fn sample_trees(roots: &[pid_t]) -> HashMap<pid_t, TreeSample> {
+    let roots: HashSet<pid_t> = roots.iter().copied().collect();
+    let mut trees: HashMap<pid_t, Vec<ProcStat>> = HashMap::new();
+    for stat in all_processes() {
+        let root = [stat.pid, stat.session, stat.pgrp]
+            .into_iter()
+            .find(|id| roots.contains(id));
+        if let Some(root) = root {
+            trees.entry(root).or_default().push(stat);
+        }
+    }
+
+    let ticks = clock_ticks();
+    let uptime = uptime();
+    trees
+        .into_iter()
+        .map(|(root, tree)| (root, sample_tree(root, &tree, ticks, uptime)))
+        .collect()
+}
+
+fn sample_tree(root: pid_t, tree: &[ProcStat], ticks: u64, uptime: Option<f64>) -> TreeSample {
+    let started = tree.iter().map(|stat| stat.start_ticks).min().unwrap_or(0);
+    let children = tree.iter().filter(|stat| stat.pid != root).count();
+
+    TreeSample {
+        cpu_seconds: seconds(tree.iter().map(|stat| stat.cpu_ticks).sum(), ticks),
+        age_seconds: uptime.map_or(0.0, |uptime| (uptime - seconds(started, ticks)).max(0.0)),
+        memory_rss: tree.iter().map(|stat| stat.rss_pages).sum::<u64>() * page_size(),
+        threads: tree.iter().map(|stat| stat.threads).sum(),
+        open_fds: tree.iter().map(|stat| open_fds(stat.pid)).sum(),
+        children: children as u64,
+    }
+}
+
+fn all_processes() -> Vec<ProcStat> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            entry.file_name().to_str()?.parse::<pid_t>().ok()?;
+            // Processes can exit while the directory is being read.
+            parse_stat(&fs::read_to_string(entry.path().join("stat")).ok()?)
+        })
+        .collect()
+}
+
+/// Parse `/proc/<pid>/stat`. The command name is skipped by looking for the last `)`, as it may
+/// contain spaces and parentheses itself.
+fn parse_stat(content: &str) -> Option<ProcStat> {
+    let (pid, rest) = content.split_once(" (")?;
+    let (_, rest) = rest.rsplit_once(") ")?;
+    // Fields from `state` (the third one) onwards.
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let field = |n: usize| -> Option<u64> { fields.get(n - 3)?.parse().ok() };
+
+    Some(ProcStat {
+        pid: pid.trim().parse().ok()?,
+        pgrp: fields.get(2)?.parse().ok()?,
+        session: fields.get(3)?.parse().ok()?,
+        cpu_ticks: field(14)? + field(15)? + field(16)? + field(17)?,
+        threads: field(20)?,
+        start_ticks: field(22)?,
+        rss_pages: field(24)?,
+    })
+}
+
+fn open_fds(pid: pid_t) -> u64 {
+    // Unreadable for processes of other users; they are counted as having none.
+    fs::read_dir(format!("/proc/{pid}/fd")).map_or(0, |entries| entries.count() as u64)
+}
+
+/// Seconds since boot.
+fn uptime() -> Option<f64> {
+    fs::read_to_string("/proc/uptime")
+        .ok()?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn seconds(ticks: u64, ticks_per_second: u64) -> f64 {
+    ticks as f64 / ticks_per_second as f64
+}
+
+fn clock_ticks() -> u64 {
+    #[allow(unsafe_code)]
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    u64::try_from(ticks)
+        .ok()
+        .filter(|ticks| *ticks > 0)
+        .unwrap_or(100)
+}
+
+fn page_size() -> u64 {
+    #[allow(unsafe_code)]
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    u64::try_from(size).unwrap_or(4096)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat() {
+        let content = "4242 (my (odd) name) S 1 4242 4242 0 -1 4194560 2130 0 0 0 \
+                       120 30 5 2 20 0 3 0 987654 123456789 2048 18446744073709551615";
+        assert_eq!(
+            parse_stat(content),
+            Some(ProcStat {
+                pid: 4242,
+                pgrp: 4242,
+                session: 4242,
+                cpu_ticks: 157,
+                threads: 3,
+                start_ticks: 987_654,
+                rss_pages: 2048,
+            })
+        );
+        assert_eq!(parse_stat("4242 (truncated) S 1"), None);
+    }
+
+    #[test]
+    fn test_sample_trees() {
+        let pid = pid_t::try_from(std::process::id()).unwrap();
+        let samples = sample_trees(&[pid, pid_t::MAX]);
+        let sample = &samples[&pid];
+        assert!(sample.memory_rss > 0);
+        assert!(sample.threads >= 1);
+        assert!(sample.open_fds > 0);
+
+        assert!(!samples.contains_key(&pid_t::MAX));
+    }
+
+    #[test]
+    fn test_sample_tree_reparented() {
+        use std::{os::unix::process::CommandExt, process::Command, thread, time::Duration};
+
+        // The inner shell exits at once, leaving its `sleep` to be reparented.
+        let mut child = Command::new("sh")
+            .args(["-c", "sh -c 'sleep 30 &'; sleep 30"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pgid = pid_t::try_from(child.id()).unwrap();
+
+        let mut children = 0;
+        for _ in 0..50 {
+            children = sample_trees(&[pgid])[&pgid].children;
+            if children == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        #[allow(unsafe_code)]
+        unsafe {
+            libc::killpg(pgid, libc::SIGKILL);
+        }
+        child.wait().unwrap();
+        assert_eq!(children, 2);
+    }
+
+    #[test]
+    fn test_sample_trees_grouped() {
+        use std::{os::unix::process::CommandExt, process::Command, thread, time::Duration};
+
+        let mut one = Command::new("sh")
+            .args(["-c", "sleep 30 & sleep 30 & wait"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let mut other = Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let roots = [
+            pid_t::try_from(one.id()).unwrap(),
+            pid_t::try_from(other.id()).unwrap(),
+        ];
+
+        let mut children = Vec::new();
+        for _ in 0..50 {
+            let samples = sample_trees(&roots);
+            children = roots.iter().map(|root| samples[root].children).collect();
+            if children == [2, 0] {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        for (root, child) in roots.into_iter().zip([&mut one, &mut other]) {
+            #[allow(unsafe_code)]
+            unsafe {
+                libc::killpg(root, libc::SIGKILL);
+            }
+            child.wait().unwrap();
+        }
+        assert_eq!(children, [2, 0]);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use tokio::io::{unix::AsyncFd, AsyncRead, AsyncWrite, ReadBuf};
+use tutti_types::WindowSize;
+
+/// Size of a new terminal if the client did not tell its own.
+const DEFAULT_WINDOW_SIZE: WindowSize = WindowSize { cols: 80, rows: 24 };
+
+/// Both ends of a freshly opened pseudo-terminal.
+#[derive(Debug)]
+pub(crate) struct Pty {
+    pub master: OwnedFd,
+    pub slave: OwnedFd,
+}
+
+impl Pty {
+    /// Open a pseudo-terminal of the given size.
+    ///
+    /// Both ends are close-on-exec, so only the process that gets the slave as its stdio keeps
+    /// it open. Newline translation is turned off to keep the output as the process wrote it.
+    pub fn open(size: Option<WindowSize>) -> io::Result<Self> {
+        let mut winsize = to_winsize(size.unwrap_or(DEFAULT_WINDOW_SIZE));
+        let mut master: libc::c_int = -1;
+        let mut slave: libc::c_int = -1;
+
+        #[allow(unsafe_code)]
+        let pty = unsafe {
+            if libc::openpty(
+                &raw mut master,
+                &raw mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &raw mut winsize,
+            ) == -1
+            {
+                return Err(io::Error::last_os_error());
+            }
+            Self {
+                master: OwnedFd::from_raw_fd(master),
+                slave: OwnedFd::from_raw_fd(slave),
+            }
+        };
+
+        set_cloexec(pty.master.as_raw_fd())?;
+        set_cloexec(pty.slave.as_raw_fd())?;
+        disable_onlcr(pty.slave.as_raw_fd())?;
+
+        Ok(pty)
+    }
+}
+
+/// Tell the terminal behind `master` about a new size; the kernel sends `SIGWINCH` to the
+/// processes using it.
+pub(crate) fn resize(master: &OwnedFd, size: WindowSize) -> io::Result<()> {
+    let winsize = to_winsize(size);
+    #[allow(unsafe_code)]
+    let rc = unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &raw const winsize) };
+    if rc == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn to_winsize(size: WindowSize) -> libc::winsize {
+    libc::winsize {
+        ws_row: size.rows,
+        ws_col: size.cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+fn set_cloexec(fd: RawFd) -> io::Result<()> {
+    #[allow(unsafe_code)]
+    let rc = unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    if rc == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    #[allow(unsafe_code)]
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn disable_onlcr(fd: RawFd) -> io::Result<()> {
+    #[allow(unsafe_code)]
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &raw mut termios) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        termios.c_oflag &= !libc::ONLCR;
+        if libc::tcsetattr(fd, libc::TCSANOW, &raw const termios) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Master end of a pseudo-terminal: reading gives the output of the process running on it,
+/// writing types into it.
+#[derive(Debug)]
+pub(crate) struct PtyMaster {
+    inner: AsyncFd<File>,
+}
+
+impl PtyMaster {
+    pub fn new(master: OwnedFd) -> io::Result<Self> {
+        set_nonblocking(master.as_raw_fd())?;
+        Ok(Self {
+            inner: AsyncFd::new(File::from(master))?,
+        })
+    }
+}
+
+impl AsyncRead for PtyMaster {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let mut guard = ready!(self.inner.poll_read_ready(cx))?;
+            let result = guard.try_io(|inner| {
+                let mut file = inner.get_ref();
+                file.read(buf.initialize_unfilled())
+            });
+            match result {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                // Linux reports `EIO` once every process holding the slave end has exited.
+                Ok(Err(err)) if err.raw_os_error() == Some(libc::EIO) => {
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) => return Poll::Ready(Err(err)),
+                Err(_would_block) => {}
+            }
+        }
+    }
+}
+
+impl AsyncWrite for PtyMaster {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = ready!(self.inner.poll_write_ready(cx))?;
+            match guard.try_io(|inner| inner.get_ref().write(buf)) {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => {}
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -1,4 +1,9 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    os::{fd::OwnedFd, unix::process::ExitStatusExt},
+    process::Stdio,
+    time::Duration,
+};
 
 use futures::StreamExt;
 use libc::{killpg, setsid, SIGINT, SIGKILL};
@@ -9,11 +14,17 @@ use tokio::{
 };
 use tokio_util::io::ReaderStream;
 
+use tutti_types::{ProcessStats, Rlimit, RlimitValue, WindowSize};
+
+use super::{
+    credentials::Credentials,
+    pty::{self, Pty, PtyMaster},
+};
 use crate::{
     error::{Error, Result},
     process_manager::{
         base::ProcessManager,
-        types::{CommandSpec, ProcId, Spawned},
+        types::{BoxStream, BoxWriter, CommandSpec, ProcId, Spawned},
     },
 };
 
@@ -21,6 +32,10 @@ use crate::{
 struct ChildRec {
     child: Child,
     pgid: libc::pid_t,
+    /// CPU time of the process tree at the previous `stats` call, to compute the usage since.
+    cpu_sample: Option<(f64, Instant)>,
+    /// Master end of the pseudo-terminal of a process started with `tty`.
+    pty_master: Option<OwnedFd>,
 }
 
 /// Unix-specific process manager.
@@ -55,40 +70,48 @@ impl ProcessManager for UnixProcessManager {
         if let Some(dir) = &spec.cwd {
             cmd.current_dir(dir);
         }
+        // The daemon's own environment is never leaked: `spec.env` is the complete environment.
+        cmd.env_clear();
         for (k, v) in &spec.env {
             cmd.env(k, v);
         }
 
-        #[allow(unsafe_code)]
-        unsafe {
-            cmd.pre_exec(|| {
-                if setsid() == -1 {
-                    return Err(std::io::Error::last_os_error());
-                }
-                Ok(())
-            });
-        }
-
-        cmd.stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped());
+        // Everything that can fail for a reason other than a system call is resolved before
+        // forking, so `pre_exec` only has to apply it.
+        let credentials = Credentials::resolve(spec.user.as_deref(), spec.group.as_deref())?;
+        let umask = spec
+            .umask
+            .map(|umask| {
+                libc::mode_t::try_from(umask).map_err(|_| {
+                    Error::IO(std::io::Error::other(format!("invalid umask {umask:o}")))
+                })
+            })
+            .transpose()?;
+        let pty = if spec.tty {
+            let pty = Pty::open(spec.window_size).map_err(Error::IO)?;
+            let slave = || pty.slave.try_clone().map(Stdio::from).map_err(Error::IO);
+            cmd.stdin(slave()?).stdout(slave()?).stderr(slave()?);
+            Some(pty)
+        } else {
+            if spec.stdin {
+                cmd.stdin(Stdio::piped());
+            }
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+            None
+        };
+        set_pre_exec(&mut cmd, &spec, credentials, umask);
 
-        let mut child = cmd.spawn().map_err(Error::IO)?;
+        let mut child = cmd.spawn().map_err(|source| Error::Spawn {
+            program: spec.cmd[0].clone(),
+            source,
+        })?;
+        // Close the parent's copies of the slave end, so reading the master ends with the
+        // process.
+        drop(cmd);
 
         let pid = child.id();
 
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| Error::IO(std::io::Error::other("stdout not piped")))?;
-        let stderr = child
-            .stderr
-            .take()
-            .ok_or_else(|| Error::IO(std::io::Error::other("stdout not piped")))?;
-
-        let out_stream = ReaderStream::new(BufReader::new(stdout))
-            .filter_map(|res| async move { res.ok().map(|b| b.to_vec()) });
-        let err_stream = ReaderStream::new(BufReader::new(stderr))
-            .filter_map(|res| async move { res.ok().map(|b| b.to_vec()) });
+        let (out_stream, err_stream, stdin, pty_master) = stdio(&mut child, pty)?;
 
         let id = ProcId(self.processes.len() as u64);
         self.processes.push(Some(ChildRec {
@@ -97,13 +120,16 @@ impl ProcessManager for UnixProcessManager {
                 pid.ok_or_else(|| Error::IO(std::io::Error::other("pid not available")))?,
             )
             .map_err(|_| Error::IO(std::io::Error::other("pid not available")))?,
+            cpu_sample: None,
+            pty_master,
         }));
 
         Ok(Spawned {
             id,
             pid,
-            stdout: Box::pin(out_stream),
-            stderr: Box::pin(err_stream),
+            stdout: out_stream,
+            stderr: err_stream,
+            stdin,
         })
     }
 
@@ -144,9 +170,14 @@ impl ProcessManager for UnixProcessManager {
 
         let start = Instant::now();
         loop {
-            if let Ok(Some(code)) = proc.child.try_wait() {
+            if let Ok(Some(status)) = proc.child.try_wait() {
                 self.processes[index] = None;
-                return Ok(Some(code.code().unwrap_or_default()));
+                // Like shells, report a process killed by a signal as 128 + the signal.
+                let code = status
+                    .code()
+                    .or_else(|| status.signal().map(|signal| 128 + signal))
+                    .unwrap_or_default();
+                return Ok(Some(code));
             }
 
             if start.elapsed() >= d {
@@ -178,4 +209,174 @@ impl ProcessManager for UnixProcessManager {
 
         Ok(())
     }
+
+    async fn resize(&mut self, id: ProcId, size: WindowSize) -> Result<()> {
+        let Some(master) = usize::try_from(id.0)
+            .ok()
+            .and_then(|index| self.processes.get(index))
+            .and_then(Option::as_ref)
+            .and_then(|proc| proc.pty_master.as_ref())
+        else {
+            return Ok(());
+        };
+        pty::resize(master, size).map_err(Error::IO)
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn stats(&mut self, ids: &[ProcId]) -> Result<HashMap<ProcId, ProcessStats>> {
+        let procs: Vec<(ProcId, &mut ChildRec)> = self
+            .processes
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, proc)| Some((ProcId(index as u64), proc.as_mut()?)))
+            .filter(|(id, _)| ids.contains(id))
+            .collect();
+        let pgids: Vec<libc::pid_t> = procs.iter().map(|(_, proc)| proc.pgid).collect();
+        let mut samples = super::procfs::sample_trees(&pgids);
+
+        let now = Instant::now();
+        let mut stats = HashMap::new();
+        for (id, proc) in procs {
+            let Some(sample) = samples.remove(&proc.pgid) else {
+                continue;
+            };
+            let (cpu_seconds, elapsed) = match proc.cpu_sample {
+                Some((previous, at)) => (
+                    sample.cpu_seconds - previous,
+                    now.duration_since(at).as_secs_f64(),
+                ),
+                None => (sample.cpu_seconds, sample.age_seconds),
+            };
+            proc.cpu_sample = Some((sample.cpu_seconds, now));
+
+            stats.insert(
+                id,
+                ProcessStats {
+                    pid: proc.pgid.unsigned_abs(),
+                    cpu_percent: if elapsed > 0.0 {
+                        (cpu_seconds / elapsed * 100.0).max(0.0)
+                    } else {
+                        0.0
+                    },
+                    memory_rss: sample.memory_rss,
+                    threads: sample.threads,
+                    open_fds: sample.open_fds,
+                    children: sample.children,
+                },
+            );
+        }
+        Ok(stats)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn stats(&mut self, _ids: &[ProcId]) -> Result<HashMap<ProcId, ProcessStats>> {
+        Ok(HashMap::new())
+    }
+}
+
+/// Set up the child between `fork` and `exec`: a new session, the controlling terminal, resource
+/// limits, priority, umask and finally the credentials, as they may take away the permission to
+/// change anything else.
+fn set_pre_exec(
+    cmd: &mut Command,
+    spec: &CommandSpec,
+    credentials: Option<Credentials>,
+    umask: Option<libc::mode_t>,
+) {
+    let rlimits = spec.rlimits.clone();
+    let nice = spec.nice;
+    let tty = spec.tty;
+
+    #[allow(unsafe_code)]
+    unsafe {
+        cmd.pre_exec(move || {
+            if setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // The new session gets the pseudo-terminal as its controlling terminal, so it
+            // receives job control signals and `SIGWINCH`.
+            if tty && libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            for (resource, value) in &rlimits {
+                let limit = match value {
+                    RlimitValue::Unlimited => libc::RLIM_INFINITY,
+                    RlimitValue::Limited(limit) => *limit as libc::rlim_t,
+                };
+                let limit = libc::rlimit {
+                    rlim_cur: limit,
+                    rlim_max: limit,
+                };
+                let resource = match resource {
+                    Rlimit::Nofile => libc::RLIMIT_NOFILE,
+                    Rlimit::Nproc => libc::RLIMIT_NPROC,
+                    Rlimit::Core => libc::RLIMIT_CORE,
+                    Rlimit::As => libc::RLIMIT_AS,
+                };
+                if libc::setrlimit(resource, &raw const limit) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(nice) = nice {
+                if libc::setpriority(libc::PRIO_PROCESS, 0, nice) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(umask) = umask {
+                libc::umask(umask);
+            }
+            if let Some(credentials) = &credentials {
+                credentials.apply()?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Stdout, stderr and stdin of a child, and the master end of its pseudo-terminal.
+type ChildStdio = (
+    BoxStream<Vec<u8>>,
+    BoxStream<Vec<u8>>,
+    Option<BoxWriter>,
+    Option<OwnedFd>,
+);
+
+/// Streams of a freshly spawned child.
+///
+/// Everything written to a pseudo-terminal comes out of the master end, so a process started
+/// with `tty` only has an stdout stream, and its input is written to the master end as well.
+fn stdio(child: &mut Child, pty: Option<Pty>) -> Result<ChildStdio> {
+    if let Some(Pty { master, slave }) = pty {
+        drop(slave);
+        let pty_master = master.try_clone().map_err(Error::IO)?;
+        let writer = PtyMaster::new(master.try_clone().map_err(Error::IO)?).map_err(Error::IO)?;
+        let reader = PtyMaster::new(master).map_err(Error::IO)?;
+        let out_stream =
+            ReaderStream::new(reader).filter_map(|res| async move { res.ok().map(|b| b.to_vec()) });
+        return Ok((
+            Box::pin(out_stream),
+            Box::pin(futures::stream::empty()),
+            Some(Box::pin(writer)),
+            Some(pty_master),
+        ));
+    }
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| Error::IO(std::io::Error::other("stdout not piped")))?;
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| Error::IO(std::io::Error::other("stdout not piped")))?;
+    let stdin = child
+        .stdin
+        .take()
+        .map(|stdin| -> BoxWriter { Box::pin(stdin) });
+
+    let out_stream = ReaderStream::new(BufReader::new(stdout))
+        .filter_map(|res| async move { res.ok().map(|b| b.to_vec()) });
+    let err_stream = ReaderStream::new(BufReader::new(stderr))
+        .filter_map(|res| async move { res.ok().map(|b| b.to_vec()) });
+    Ok((Box::pin(out_stream), Box::pin(err_stream), stdin, None))
 }
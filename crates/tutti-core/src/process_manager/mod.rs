@@ -7,4 +7,4 @@ pub use base::ProcessManager;
 pub use implementations::MockProcessManager;
 #[cfg(unix)]
 pub use implementations::UnixProcessManager;
-pub use types::{CommandSpec, ProcId, Spawned};
+pub use types::{service_env, BoxWriter, CommandSpec, ProcId, Spawned};
@@ -1,15 +1,81 @@
-use std::{fmt::Debug, path::PathBuf, pin::Pin};
+use std::{collections::HashMap, fmt::Debug, path::PathBuf, pin::Pin};
 
 use futures::Stream;
+use tokio::io::AsyncWrite;
+use tutti_types::{Rlimit, RlimitValue, Service, WindowSize};
 
 pub type BoxStream<T> = Pin<Box<dyn Stream<Item = T> + Send>>;
+pub type BoxWriter = Pin<Box<dyn AsyncWrite + Send>>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct CommandSpec {
     pub name: String,
     pub cmd: Vec<String>,
     pub cwd: Option<PathBuf>,
+    /// Complete environment of the process, nothing is inherited from the daemon.
     pub env: Vec<(String, String)>,
+    /// User (name or uid) to run the process as. Switching users requires the daemon to run as
+    /// root.
+    pub user: Option<String>,
+    /// Group (name or gid) to run the process as, defaults to the primary group of `user`.
+    pub group: Option<String>,
+    pub umask: Option<u32>,
+    pub nice: Option<i32>,
+    pub rlimits: Vec<(Rlimit, RlimitValue)>,
+    /// Run the process on a pseudo-terminal. Its output then all comes through `stdout`.
+    pub tty: bool,
+    /// Initial size of the pseudo-terminal.
+    pub window_size: Option<WindowSize>,
+    /// Pipe the process' stdin and return it as `Spawned::stdin`. A process running on a
+    /// pseudo-terminal always has one.
+    pub stdin: bool,
+}
+
+impl CommandSpec {
+    /// Describe a process run on behalf of `service`, in its directory, with its environment
+    /// `env` and its process settings. The process has no terminal; callers set `tty` and
+    /// `window_size` themselves.
+    #[must_use]
+    pub fn for_service(
+        service: &Service,
+        name: String,
+        cmd: &[String],
+        env: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            name,
+            cmd: cmd.to_vec(),
+            cwd: service.cwd.clone(),
+            env,
+            user: service.user.clone(),
+            group: service.group.clone(),
+            umask: service.umask,
+            nice: service.nice,
+            rlimits: service.rlimits.iter().map(|(k, v)| (*k, *v)).collect(),
+            tty: false,
+            window_size: None,
+            stdin: service.stdin_open,
+        }
+    }
+}
+
+/// Full environment of a service: the part of `client_env` it inherits, overridden by the
+/// service's own variables. Sorted by name.
+#[must_use]
+pub fn service_env<S: std::hash::BuildHasher>(
+    service: &Service,
+    client_env: &HashMap<String, String, S>,
+) -> Vec<(String, String)> {
+    let mut env: HashMap<String, String> = client_env
+        .iter()
+        .filter(|(key, _)| service.inherit_env.allows(key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    env.extend(service.env.clone().unwrap_or_default());
+
+    let mut env: Vec<(String, String)> = env.into_iter().collect();
+    env.sort();
+    env
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -20,6 +86,7 @@ pub struct Spawned {
     pub pid: Option<u32>,
     pub stdout: BoxStream<Vec<u8>>,
     pub stderr: BoxStream<Vec<u8>>,
+    pub stdin: Option<BoxWriter>,
 }
 
 impl Debug for Spawned {
@@ -29,6 +96,7 @@ impl Debug for Spawned {
             .field("pid", &self.pid)
             .field("stdout", &"<stream>")
             .field("stderr", &"<stream>")
+            .field("stdin", &self.stdin.as_ref().map(|_| "<writer>"))
             .finish()
     }
 }
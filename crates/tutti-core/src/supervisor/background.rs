@@ -1,14 +1,36 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
 
-use futures::StreamExt;
-use tutti_types::{Project, ProjectId, Restart, Service};
+use futures::{Stream, StreamExt};
+use tokio::{io::AsyncWriteExt, sync::oneshot};
+use tutti_types::{
+    HookKind, Project, ProjectId, Restart, Service, ServiceExit, ServiceState, ServiceStats,
+    ServiceStatus, WindowSize,
+};
 
 use crate::{
     error::{Error, Result},
-    supervisor::{commands::SupervisorEvent, SupervisorCommand},
+    process_manager::{service_env, BoxWriter},
+    supervisor::{
+        commands::SupervisorEvent,
+        hooks::{AfterHook, AfterStop, HookResult, KilledProcess, RunningHook},
+        limits::LIMIT_CHECK_INTERVAL,
+        lines::LineBuffer,
+        SupervisorCommand,
+    },
     CommandSpec, ProcId, ProcessManager,
 };
 
+/// How long to wait for the exit code of a service whose output ended.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long an unterminated line, such as a prompt or a progress bar, is held back before it
+/// is passed on as it is.
+const PARTIAL_LINE_TIMEOUT: Duration = Duration::from_millis(100);
+/// How many chunks of input may wait for a service to read them.
+const STDIN_BUFFER: usize = 64;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Status {
     Waiting { wait_for: Vec<String> },
@@ -22,18 +44,49 @@ pub struct RunningService {
     pub name: String,
     pub pid: Option<ProcId>,
     pub status: Status,
+    pub restarts: u32,
+}
+
+/// What the status report needs to know about a spawned process.
+#[derive(Debug, Clone, Copy)]
+struct ProcessInfo {
+    pid: Option<u32>,
+    started_at: Instant,
 }
 
 #[derive(Debug)]
 pub struct SupervisorBackground<P: ProcessManager> {
-    process_manager: P,
-    storage: HashMap<ProjectId, Vec<RunningService>>,
-    config: HashMap<ProjectId, Project>,
-
-    commands_tx: tokio::sync::mpsc::Sender<SupervisorCommand>,
+    pub(super) process_manager: P,
+    pub(super) storage: HashMap<ProjectId, Vec<RunningService>>,
+    pub(super) config: HashMap<ProjectId, Project>,
+    /// Environment of the client that last started each project.
+    client_env: HashMap<ProjectId, HashMap<String, String>>,
+    /// Terminal size of the client of each project, for services running on a pseudo-terminal.
+    window_sizes: HashMap<ProjectId, WindowSize>,
+    /// Since when each process has been over one of its service's limits.
+    pub(super) limit_breaches: HashMap<ProcId, Instant>,
+    /// Input for the processes that have a stdin, written by a task per process.
+    stdin: HashMap<ProcId, tokio::sync::mpsc::Sender<Vec<u8>>>,
+    /// Processes of the commands started with `Exec`.
+    pub(super) execs: HashMap<u32, ProcId>,
+    /// Processes of the services, until their output ends or they are stopped.
+    processes: HashMap<ProcId, ProcessInfo>,
+    /// Hooks that are running, by id.
+    pub(super) hooks: HashMap<u32, RunningHook>,
+    pub(super) next_hook_id: u32,
+    /// Processes killed on request that have a `post_stop` hook to run once they exited.
+    pub(super) killed: HashMap<ProcId, KilledProcess>,
+    /// How many services of each project being stopped with `down` still have to stop.
+    stopping: HashMap<ProjectId, usize>,
+    /// Requests to shut down, answered once every service is stopped.
+    shutdown_waiters: Vec<oneshot::Sender<Result<()>>>,
+    /// How the last process of each service ended, until the service is started again.
+    exits: HashMap<ProjectId, HashMap<String, ServiceExit>>,
+
+    pub(super) commands_tx: tokio::sync::mpsc::Sender<SupervisorCommand>,
     commands_rx: tokio::sync::mpsc::Receiver<SupervisorCommand>,
 
-    output_tx: tokio::sync::mpsc::Sender<SupervisorEvent>,
+    pub(super) output_tx: tokio::sync::mpsc::Sender<SupervisorEvent>,
 }
 
 impl<P: ProcessManager> SupervisorBackground<P> {
@@ -50,6 +103,18 @@ impl<P: ProcessManager> SupervisorBackground<P> {
                 process_manager,
                 storage: HashMap::new(),
                 config: HashMap::new(),
+                client_env: HashMap::new(),
+                window_sizes: HashMap::new(),
+                limit_breaches: HashMap::new(),
+                stdin: HashMap::new(),
+                execs: HashMap::new(),
+                processes: HashMap::new(),
+                hooks: HashMap::new(),
+                next_hook_id: 0,
+                killed: HashMap::new(),
+                stopping: HashMap::new(),
+                shutdown_waiters: Vec::new(),
+                exits: HashMap::new(),
                 commands_tx,
                 commands_rx,
                 output_tx,
@@ -61,6 +126,21 @@ impl<P: ProcessManager> SupervisorBackground<P> {
     pub async fn run(&mut self) {
         tracing::info!("SupervisorBackground started");
 
+        let commands_tx = self.commands_tx.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(LIMIT_CHECK_INTERVAL);
+            loop {
+                ticker.tick().await;
+                if commands_tx
+                    .send(SupervisorCommand::CheckLimits)
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
         while let Some(command) = self.commands_rx.recv().await {
             tracing::debug!("Received command: {:?}", command);
 
@@ -73,65 +153,100 @@ impl<P: ProcessManager> SupervisorBackground<P> {
     async fn handle_commands(&mut self, command: SupervisorCommand) -> Result<()> {
         tracing::debug!("Handling command: {:?}", command);
 
+        // Responses are sent without checking: the requester may have given up waiting.
         match command {
-            SupervisorCommand::UpdateConfig { project_id, config } => {
-                tracing::debug!("Updating config for project {project_id:?}");
-
-                self.update_config(project_id, config);
-                Ok(())
-            }
+            SupervisorCommand::UpdateConfig {
+                project_id,
+                config,
+                env,
+            } => self.update_config(project_id, config, env),
             SupervisorCommand::Up {
                 project_id,
                 services,
+            } => return self.up(project_id, services).await,
+            SupervisorCommand::Down { project_id } => return self.down(project_id).await,
+            SupervisorCommand::Shutdown { response } => self.shutdown(response).await,
+            SupervisorCommand::EndOfLogs {
+                project_id,
+                service,
+                pid,
+            } => return self.end_of_logs(project_id, service, pid).await,
+            SupervisorCommand::HealthCheckSuccess {
+                project_id,
+                service,
+            } => return self.health_check_success(project_id, service).await,
+            SupervisorCommand::Resize { project_id, size } => self.resize(project_id, size).await,
+            SupervisorCommand::Input {
+                project_id,
+                service,
+                data,
+                response,
             } => {
-                tracing::debug!(
-                    "Starting services for project {project_id:?} with services: {services:?}",
-                );
-
-                self.up(project_id, services).await?;
-                Ok(())
+                let _ = response.send(self.input(project_id, service, data));
             }
-            SupervisorCommand::Down { project_id } => {
-                tracing::debug!("Stopping services for project {project_id:?}");
-
-                self.down(project_id).await?;
-                Ok(())
+            SupervisorCommand::Exec {
+                exec_id,
+                project_id,
+                service,
+                config,
+                cmd,
+                env,
+                response,
+            } => {
+                tracing::debug!("Running {cmd:?} for service {service:?} of {project_id:?}");
+                let _ = response.send(self.exec(exec_id, &service, &config, &cmd, &env).await);
             }
-            SupervisorCommand::Shutdown => {
-                tracing::debug!("Shutting down supervisor");
-
-                self.shutdown().await?;
-                Ok(())
+            SupervisorCommand::ExecOutputEnded { exec_id } => self.exec_output_ended(exec_id).await,
+            SupervisorCommand::ExecKill { exec_id } => return self.exec_kill(exec_id).await,
+            SupervisorCommand::CheckHook { hook_id } => self.check_hook(hook_id).await,
+            SupervisorCommand::HookEnded { hook_id, result } => {
+                return self.hook_ended(hook_id, result).await;
             }
-            SupervisorCommand::EndOfLogs {
+            SupervisorCommand::CheckKilled { pid } => return self.check_killed(pid).await,
+            SupervisorCommand::StopService {
                 project_id,
                 service,
+                response,
             } => {
-                tracing::debug!(
-                    "Getting end of logs for project {project_id:?} and service {service:?}"
-                );
-
-                self.end_of_logs(project_id, service).await?;
-                Ok(())
+                let _ = response.send(self.stop_service(project_id, service).await);
             }
-            SupervisorCommand::HealthCheckSuccess {
+            SupervisorCommand::RestartService {
                 project_id,
                 service,
+                response,
             } => {
-                tracing::debug!(
-                    "Health check success for project {project_id:?} and service {service:?}"
-                );
-
-                self.health_check_success(project_id, service).await?;
-                Ok(())
+                let _ = response.send(self.restart_service(project_id, service).await);
+            }
+            SupervisorCommand::Status {
+                project_id,
+                response,
+            } => {
+                let _ = response.send(self.status(project_id));
+            }
+            SupervisorCommand::Projects { response } => {
+                let _ = response.send(self.projects());
+            }
+            SupervisorCommand::CheckLimits => self.check_limits().await,
+            SupervisorCommand::Stats {
+                project_id,
+                response,
+            } => {
+                let _ = response.send(self.stats(project_id.as_ref()).await);
             }
         }
+        Ok(())
     }
 
-    fn update_config(&mut self, project_id: ProjectId, new_config: Project) {
+    fn update_config(
+        &mut self,
+        project_id: ProjectId,
+        new_config: Project,
+        env: HashMap<String, String>,
+    ) {
         tracing::info!("Updating config for project {project_id:?}");
 
-        self.config.insert(project_id, new_config);
+        self.config.insert(project_id.clone(), new_config);
+        self.client_env.insert(project_id, env);
     }
 
     async fn up(&mut self, project_id: ProjectId, services: Vec<String>) -> Result<()> {
@@ -167,6 +282,7 @@ impl<P: ProcessManager> SupervisorBackground<P> {
             .get(&project_id)
             .map(|v| v.iter().map(|s| s.name.clone()).collect())
             .unwrap_or_default();
+        let mut blocked = HashSet::new();
 
         // TODO: Recalculate dependencies
         for service_name in services {
@@ -185,20 +301,34 @@ impl<P: ProcessManager> SupervisorBackground<P> {
                 tracing::info!("Service {service_name:?} is already running");
                 continue;
             }
+            if let Some(exits) = self.exits.get_mut(&project_id) {
+                exits.remove(&service_name);
+            }
 
-            if service.deps.is_empty() {
-                let proc_id = self
-                    .start_service(service.clone(), service_name.clone(), project_id.clone())
-                    .await?;
+            if let Some(dep) = service.deps.iter().find(|dep| blocked.contains(*dep)) {
+                self.block_service(&project_id, &service_name, dependency_blocked(dep))
+                    .await;
+                blocked.insert(service_name);
+                continue;
+            }
 
+            if service.deps.is_empty() {
                 self.storage
                     .entry(project_id.clone())
                     .or_default()
                     .push(RunningService {
                         name: service_name.clone(),
-                        pid: Some(proc_id),
+                        pid: None,
                         status: Status::Starting,
+                        restarts: 0,
                     });
+
+                if !self
+                    .start_service(service.clone(), service_name.clone(), project_id.clone())
+                    .await?
+                {
+                    blocked.insert(service_name);
+                }
             } else {
                 self.storage
                     .entry(project_id.clone())
@@ -209,83 +339,426 @@ impl<P: ProcessManager> SupervisorBackground<P> {
                         status: Status::Waiting {
                             wait_for: service.deps.clone(),
                         },
+                        restarts: 0,
                     });
             }
         }
 
+        if !blocked.is_empty() {
+            self.stop_if_idle(&project_id).await;
+        }
+
         Ok(())
     }
 
+    /// Stop every service of a project. The project is reported as stopped once the stop hooks
+    /// of all of them are over.
     async fn down(&mut self, project_id: ProjectId) -> Result<()> {
-        if let Some(services) = self.storage.remove(&project_id) {
-            for service in services {
-                if let Some(pid) = service.pid {
-                    let _ = self.process_manager.kill(pid).await;
-                }
-            }
+        let services = self.storage.remove(&project_id).unwrap_or_default();
+        let processes: Vec<_> = services
+            .into_iter()
+            .filter_map(|service| Some((service.name, service.pid?)))
+            .collect();
+
+        // One more than there are processes, so that the project is not reported as stopped
+        // before all of them had their turn.
+        *self.stopping.entry(project_id.clone()).or_default() += processes.len() + 1;
+        for (service, pid) in processes {
+            self.stop_process(&project_id, &service, pid, AfterStop::Down)
+                .await?;
         }
+        self.down_finished(&project_id).await;
+
+        Ok(())
+    }
+
+    /// Count a service of a project being stopped with `down` as stopped, reporting the project
+    /// once none is left.
+    async fn down_finished(&mut self, project_id: &ProjectId) {
+        let Some(count) = self.stopping.get_mut(project_id) else {
+            return;
+        };
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        self.stopping.remove(project_id);
 
         let _ = self
             .output_tx
-            .send(SupervisorEvent::ProjectStopped { project_id })
+            .send(SupervisorEvent::ProjectStopped {
+                project_id: project_id.clone(),
+            })
             .await;
 
+        if self.stopping.is_empty() {
+            for response in self.shutdown_waiters.drain(..) {
+                let _ = response.send(Ok(()));
+            }
+        }
+    }
+
+    /// Continue once a service was stopped on request.
+    pub(super) async fn stopped(
+        &mut self,
+        project_id: &ProjectId,
+        service_name: &str,
+        then: AfterStop,
+    ) -> Result<()> {
+        match then {
+            AfterStop::Down => self.down_finished(project_id).await,
+            AfterStop::Service => {
+                let _ = self
+                    .output_tx
+                    .send(SupervisorEvent::ServiceStopped {
+                        project_id: project_id.clone(),
+                        service: service_name.to_owned(),
+                    })
+                    .await;
+                self.stop_if_idle(project_id).await;
+            }
+            AfterStop::Restart(config) => {
+                // The service may have been stopped while its stop hooks ran.
+                if !self.starting(project_id, service_name) {
+                    return Ok(());
+                }
+                let _ = self
+                    .output_tx
+                    .send(SupervisorEvent::ServiceRestarted {
+                        project_id: project_id.clone(),
+                        service: service_name.to_owned(),
+                    })
+                    .await;
+                if !self
+                    .start_service(*config, service_name.to_owned(), project_id.clone())
+                    .await?
+                {
+                    self.stop_if_idle(project_id).await;
+                }
+            }
+        }
         Ok(())
     }
 
-    async fn shutdown(&mut self) -> Result<()> {
+    /// Whether a service is marked as starting but has no process yet.
+    pub(super) fn starting(&self, project_id: &ProjectId, service_name: &str) -> bool {
+        self.storage
+            .get(project_id)
+            .and_then(|services| services.iter().find(|s| s.name == service_name))
+            .is_some_and(|running| running.status == Status::Starting && running.pid.is_none())
+    }
+
+    /// Drop what is kept about a process that exited or was stopped.
+    pub(super) fn forget_process(&mut self, pid: ProcId) {
+        self.stdin.remove(&pid);
+        self.processes.remove(&pid);
+    }
+
+    /// Stop a single service of a project, leaving the services that depend on it running.
+    async fn stop_service(&mut self, project_id: ProjectId, service: String) -> Result<()> {
+        let Some(running_services) = self.storage.get_mut(&project_id) else {
+            return Err(Error::ServiceNotRunning(service));
+        };
+        let Some(idx) = running_services.iter().position(|s| s.name == service) else {
+            return Err(Error::ServiceNotRunning(service));
+        };
+        let stopped = running_services.remove(idx);
+
+        match stopped.pid {
+            Some(pid) => {
+                self.stop_process(&project_id, &service, pid, AfterStop::Service)
+                    .await
+            }
+            None => {
+                self.stopped(&project_id, &service, AfterStop::Service)
+                    .await
+            }
+        }
+    }
+
+    /// Stop a service if it is running and start it again, or start it if it is not running.
+    async fn restart_service(&mut self, project_id: ProjectId, service: String) -> Result<()> {
+        let Some(config) = self
+            .config
+            .get(&project_id)
+            .and_then(|config| config.services.get(&service))
+            .cloned()
+        else {
+            return Err(Error::ServiceNotFound(project_id, service));
+        };
+
+        let Some(running) = self
+            .storage
+            .get_mut(&project_id)
+            .and_then(|services| services.iter_mut().find(|s| s.name == service))
+        else {
+            return self.up(project_id, vec![service]).await;
+        };
+        // A service waiting for its dependencies starts when they are ready anyway.
+        let Some(pid) = running.pid.take() else {
+            return Ok(());
+        };
+        running.status = Status::Starting;
+        running.restarts += 1;
+
+        self.stop_process(
+            &project_id,
+            &service,
+            pid,
+            AfterStop::Restart(Box::new(config)),
+        )
+        .await
+    }
+
+    /// Projects that have services running, sorted by path.
+    fn projects(&self) -> Vec<ProjectId> {
+        let mut projects: Vec<_> = self.storage.keys().cloned().collect();
+        projects.sort_by(|a, b| a.0.cmp(&b.0));
+        projects
+    }
+
+    /// Report every configured service of a project, sorted by name.
+    fn status(&self, project_id: ProjectId) -> Result<Vec<ServiceStatus>> {
+        let Some(config) = self.config.get(&project_id) else {
+            return Err(Error::ProjectNotFound(project_id));
+        };
+        let running_services = self.storage.get(&project_id);
+
+        Ok(config
+            .services
+            .keys()
+            .map(|name| {
+                let running =
+                    running_services.and_then(|services| services.iter().find(|s| &s.name == name));
+                let process = running
+                    .and_then(|running| running.pid)
+                    .and_then(|pid| self.processes.get(&pid));
+                ServiceStatus {
+                    service: name.clone(),
+                    state: match running.map(|running| &running.status) {
+                        Some(Status::Waiting { .. }) => ServiceState::Waiting,
+                        Some(Status::Starting) => ServiceState::Starting,
+                        Some(Status::Running) => ServiceState::Running,
+                        Some(Status::Stopped) | None => ServiceState::Stopped,
+                    },
+                    pid: process.and_then(|process| process.pid),
+                    uptime: process.map(|process| process.started_at.elapsed().as_secs()),
+                    restarts: running.map_or(0, |running| running.restarts),
+                    last_exit: self
+                        .exits
+                        .get(&project_id)
+                        .and_then(|exits| exits.get(name))
+                        .copied(),
+                }
+            })
+            .collect())
+    }
+
+    /// Remember the client's terminal size and pass it on to the services of the project that
+    /// run on a pseudo-terminal.
+    async fn resize(&mut self, project_id: ProjectId, size: WindowSize) {
+        let ttys: Vec<ProcId> = self
+            .storage
+            .get(&project_id)
+            .into_iter()
+            .flatten()
+            .filter(|service| {
+                self.config
+                    .get(&project_id)
+                    .and_then(|config| config.services.get(&service.name))
+                    .is_some_and(|config| config.tty)
+            })
+            .filter_map(|service| service.pid)
+            .collect();
+        for pid in ttys {
+            if let Err(err) = self.process_manager.resize(pid, size).await {
+                tracing::warn!("Cannot resize terminal of {pid:?}: {err:?}");
+            }
+        }
+        self.window_sizes.insert(project_id, size);
+    }
+
+    /// Queue `data` to be written to the stdin of a running service.
+    fn input(&mut self, project_id: ProjectId, service: String, data: Vec<u8>) -> Result<()> {
+        let Some(running_services) = self.storage.get(&project_id) else {
+            return Err(Error::ProjectNotFound(project_id));
+        };
+        let Some(running) = running_services.iter().find(|s| s.name == service) else {
+            return Err(Error::ServiceNotFound(project_id, service));
+        };
+        let Some(pid) = running.pid else {
+            return Err(Error::ServiceNotRunning(service));
+        };
+        let Some(stdin) = self.stdin.get(&pid) else {
+            return Err(Error::NoStdin(service));
+        };
+
+        stdin.try_send(data).map_err(|err| match err {
+            tokio::sync::mpsc::error::TrySendError::Full(_) => Error::InputFull(service),
+            tokio::sync::mpsc::error::TrySendError::Closed(_) => {
+                self.stdin.remove(&pid);
+                Error::ServiceNotRunning(service)
+            }
+        })
+    }
+
+    /// Sample every spawned service of `project_id` (or of all projects), sorted by project and
+    /// service name. Services that are waiting or have exited are left out.
+    async fn stats(&mut self, project_id: Option<&ProjectId>) -> Vec<ServiceStats> {
+        let mut processes: Vec<(ProjectId, String, ProcId)> = self
+            .storage
+            .iter()
+            .filter(|(id, _)| project_id.is_none_or(|project_id| project_id == *id))
+            .flat_map(|(id, services)| {
+                services
+                    .iter()
+                    .filter_map(|service| Some((id.clone(), service.name.clone(), service.pid?)))
+            })
+            .collect();
+        processes.sort_by(|a, b| (&a.0 .0, &a.1).cmp(&(&b.0 .0, &b.1)));
+
+        let pids: Vec<ProcId> = processes.iter().map(|(_, _, pid)| *pid).collect();
+        let mut samples = match self.process_manager.stats(&pids).await {
+            Ok(samples) => samples,
+            Err(err) => {
+                tracing::warn!("Cannot sample services: {err:?}");
+                return Vec::new();
+            }
+        };
+        processes
+            .into_iter()
+            .filter_map(|(project_id, service, pid)| {
+                Some(ServiceStats {
+                    project_id,
+                    service,
+                    stats: samples.remove(&pid)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Stop every project, answering `response` once all services are stopped.
+    async fn shutdown(&mut self, response: oneshot::Sender<Result<()>>) {
         for project_id in self.storage.keys().cloned().collect::<Vec<_>>() {
-            self.down(project_id).await?;
+            if let Err(err) = self.down(project_id).await {
+                let _ = response.send(Err(err));
+                return;
+            }
+        }
+        if self.stopping.is_empty() {
+            let _ = response.send(Ok(()));
+        } else {
+            self.shutdown_waiters.push(response);
+        }
+    }
+
+    /// Full environment of a service: the allowed part of the client environment, overridden by
+    /// the service's own variables.
+    fn service_env(&self, project_id: &ProjectId, service: &Service) -> Vec<(String, String)> {
+        service_env(
+            service,
+            self.client_env.get(project_id).unwrap_or(&HashMap::new()),
+        )
+    }
+
+    /// Describe a process run on behalf of `service`: the service itself or one of its hooks.
+    pub(super) fn command_spec(
+        &self,
+        project_id: &ProjectId,
+        service: &Service,
+        name: String,
+        cmd: &[String],
+    ) -> CommandSpec {
+        CommandSpec {
+            tty: service.tty,
+            window_size: self.window_sizes.get(project_id).copied(),
+            ..CommandSpec::for_service(service, name, cmd, self.service_env(project_id, service))
         }
-        Ok(())
     }
 
+    /// Spawn a service marked as starting, after running its `pre_start` hook.
+    ///
+    /// Returns `false` if the hook failed or the service could not be started, in which case the
+    /// service is reported as blocked.
     async fn start_service(
         &mut self,
         service: Service,
         service_name: String,
         project_id: ProjectId,
-    ) -> Result<ProcId> {
+    ) -> Result<bool> {
         tracing::debug!("Starting service {service_name:?} for project {project_id:?}");
 
-        let process = self
-            .process_manager
-            .spawn(CommandSpec {
-                name: service_name.clone(),
-                cmd: service.cmd.clone(),
-                cwd: service.cwd.clone(),
-                env: service
-                    .env
-                    .clone()
-                    .map(|h| h.into_iter().collect())
-                    .unwrap_or_default(),
-            })
-            .await?;
+        let after = AfterHook::Start(service.clone());
+        match self
+            .run_hook(
+                &project_id,
+                &service_name,
+                &service,
+                HookKind::PreStart,
+                after,
+            )
+            .await
+        {
+            Some(result) => {
+                self.pre_start_done(service, service_name, project_id, result)
+                    .await
+            }
+            None => Ok(true),
+        }
+    }
+
+    /// Spawn a service whose `pre_start` hook is over, or report it as blocked if the hook
+    /// failed.
+    pub(super) async fn pre_start_done(
+        &mut self,
+        service: Service,
+        service_name: String,
+        project_id: ProjectId,
+        result: HookResult,
+    ) -> Result<bool> {
+        if let Err(reason) = result {
+            self.block_service(&project_id, &service_name, reason).await;
+            return Ok(false);
+        }
+
+        let spec = self.command_spec(&project_id, &service, service_name.clone(), &service.cmd);
+        let process = match self.process_manager.spawn(spec).await {
+            Ok(process) => process,
+            Err(err) => {
+                self.block_service(&project_id, &service_name, err.to_string())
+                    .await;
+                return Ok(false);
+            }
+        };
+
+        self.processes.insert(
+            process.id,
+            ProcessInfo {
+                pid: process.pid,
+                started_at: Instant::now(),
+            },
+        );
+        if let Some(running) = self
+            .storage
+            .get_mut(&project_id)
+            .and_then(|services| services.iter_mut().find(|s| s.name == service_name))
+        {
+            running.pid = Some(process.id);
+        }
 
         {
             let commands_tx = self.commands_tx.clone();
-            let output_tx = self.output_tx.clone();
-            let mut stdout = process.stdout;
+            let stdout = self.forward_logs(&project_id, &service_name, process.stdout, false);
+            let pid = process.id;
             let project_id_clone = project_id.clone();
             let service_name_clone = service_name.clone();
             tokio::spawn(async move {
-                while let Some(command) = stdout.next().await {
-                    let log = String::from_utf8_lossy(&command);
-                    if let Err(err) = output_tx
-                        .send(SupervisorEvent::Log {
-                            project_id: project_id_clone.clone(),
-                            service: service_name_clone.clone(),
-                            message: log.to_string(),
-                        })
-                        .await
-                    {
-                        tracing::error!("Failed to send log event: {}", err);
-                    }
-                }
+                let _ = stdout.await;
                 if let Err(err) = commands_tx
                     .send(SupervisorCommand::EndOfLogs {
                         project_id: project_id_clone.clone(),
                         service: service_name_clone.clone(),
+                        pid,
                     })
                     .await
                 {
@@ -294,26 +767,9 @@ impl<P: ProcessManager> SupervisorBackground<P> {
             });
         }
 
-        {
-            let output_tx = self.output_tx.clone();
-            let mut stderr = process.stderr;
-            let project_id_clone = project_id.clone();
-            let service_name_clone = service_name.clone();
-            tokio::spawn(async move {
-                while let Some(command) = stderr.next().await {
-                    let log = String::from_utf8_lossy(&command);
-                    if let Err(err) = output_tx
-                        .send(SupervisorEvent::Log {
-                            project_id: project_id_clone.clone(),
-                            service: service_name_clone.clone(),
-                            message: log.to_string(),
-                        })
-                        .await
-                    {
-                        tracing::error!("Failed to send log event: {}", err);
-                    }
-                }
-            });
+        self.forward_logs(&project_id, &service_name, process.stderr, true);
+        if let Some(stdin) = process.stdin {
+            self.forward_stdin(process.id, stdin);
         }
 
         {
@@ -333,11 +789,139 @@ impl<P: ProcessManager> SupervisorBackground<P> {
             });
         }
 
-        Ok(process.id)
+        Ok(true)
+    }
+
+    /// Forward an output stream of a process to the log stream under `tag`, a line at a time.
+    pub(super) fn forward_logs<S>(
+        &self,
+        project_id: &ProjectId,
+        tag: &str,
+        mut stream: S,
+        stderr: bool,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        S: Stream<Item = Vec<u8>> + Unpin + Send + 'static,
+    {
+        let output_tx = self.output_tx.clone();
+        let project_id = project_id.clone();
+        let tag = tag.to_owned();
+        tokio::spawn(async move {
+            let mut lines = LineBuffer::default();
+            loop {
+                let message = if lines.is_empty() {
+                    match stream.next().await {
+                        Some(chunk) => lines.push(&chunk),
+                        None => break,
+                    }
+                } else {
+                    match tokio::time::timeout(PARTIAL_LINE_TIMEOUT, stream.next()).await {
+                        Ok(Some(chunk)) => lines.push(&chunk),
+                        Ok(None) => break,
+                        Err(_) => lines.flush(),
+                    }
+                };
+                let Some(message) = message else {
+                    continue;
+                };
+                if let Err(err) = output_tx
+                    .send(SupervisorEvent::Log {
+                        project_id: project_id.clone(),
+                        service: tag.clone(),
+                        message,
+                        stderr,
+                    })
+                    .await
+                {
+                    tracing::error!("Failed to send log event: {}", err);
+                }
+            }
+
+            if let Some(message) = lines.flush() {
+                let _ = output_tx
+                    .send(SupervisorEvent::Log {
+                        project_id,
+                        service: tag,
+                        message,
+                        stderr,
+                    })
+                    .await;
+            }
+        })
+    }
+
+    /// Write the input sent with `input` to the stdin of a process, until the process exits.
+    fn forward_stdin(&mut self, pid: ProcId, mut stdin: BoxWriter) {
+        let (input_tx, mut input_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(STDIN_BUFFER);
+        tokio::spawn(async move {
+            while let Some(data) = input_rx.recv().await {
+                if stdin.write_all(&data).await.is_err() || stdin.flush().await.is_err() {
+                    break;
+                }
+            }
+        });
+        self.stdin.insert(pid, input_tx);
+    }
+
+    /// Report a service that cannot start, and every service waiting for it.
+    async fn block_service(&mut self, project_id: &ProjectId, service_name: &str, reason: String) {
+        let mut queue = VecDeque::from([(service_name.to_owned(), reason)]);
+        while let Some((service, reason)) = queue.pop_front() {
+            tracing::warn!("Service {service:?} blocked: {reason}");
+
+            if let Some(running_services) = self.storage.get_mut(project_id) {
+                running_services.retain(|running| {
+                    if running.name == service {
+                        return false;
+                    }
+                    let waiting = matches!(
+                        &running.status,
+                        Status::Waiting { wait_for } if wait_for.contains(&service)
+                    );
+                    if waiting {
+                        queue.push_back((running.name.clone(), dependency_blocked(&service)));
+                    }
+                    !waiting
+                });
+            }
+
+            let _ = self
+                .output_tx
+                .send(SupervisorEvent::ServiceBlocked {
+                    project_id: project_id.clone(),
+                    service,
+                    reason,
+                })
+                .await;
+        }
+    }
+
+    /// Report the project as stopped once none of its services is left.
+    pub(super) async fn stop_if_idle(&mut self, project_id: &ProjectId) {
+        if self
+            .storage
+            .get(project_id)
+            .is_some_and(|services| !services.is_empty())
+        {
+            return;
+        }
+        self.storage.remove(project_id);
+
+        let _ = self
+            .output_tx
+            .send(SupervisorEvent::ProjectStopped {
+                project_id: project_id.clone(),
+            })
+            .await;
     }
 
     #[tracing::instrument(skip_all)]
-    async fn end_of_logs(&mut self, project_id: ProjectId, service_name: String) -> Result<()> {
+    async fn end_of_logs(
+        &mut self,
+        project_id: ProjectId,
+        service_name: String,
+        pid: ProcId,
+    ) -> Result<()> {
         tracing::info!("End of logs for service {}", service_name);
 
         let Some(running_services) = self.storage.get_mut(&project_id) else {
@@ -354,8 +938,16 @@ impl<P: ProcessManager> SupervisorBackground<P> {
             tracing::warn!("Service already starting");
             return Ok(());
         }
+        if running_services[idx].pid != Some(pid) {
+            // A process the service was restarted from on request.
+            tracing::warn!("Service already restarted");
+            return Ok(());
+        }
 
         running_services[idx].status = Status::Stopped;
+        running_services[idx].pid = None;
+        self.stdin.remove(&pid);
+        self.processes.remove(&pid);
 
         let Some(config) = self.config.get(&project_id) else {
             tracing::warn!("Project config not found");
@@ -366,6 +958,61 @@ impl<P: ProcessManager> SupervisorBackground<P> {
             return Ok(());
         };
 
+        // The output also ends if a service closes it while running on, which leaves no code.
+        let code = self
+            .process_manager
+            .wait(pid, EXIT_TIMEOUT)
+            .await
+            .ok()
+            .flatten();
+        self.exits
+            .entry(project_id.clone())
+            .or_default()
+            .insert(service_name.clone(), ServiceExit { code });
+        let _ = self
+            .output_tx
+            .send(SupervisorEvent::ServiceExited {
+                project_id: project_id.clone(),
+                service: service_name.clone(),
+                code,
+            })
+            .await;
+
+        // The service has already exited, a failing hook is only reported.
+        let after = AfterHook::Exited(service_cfg.clone());
+        match self
+            .run_hook(
+                &project_id,
+                &service_name,
+                &service_cfg,
+                HookKind::PostStop,
+                after,
+            )
+            .await
+        {
+            Some(_) => self.exited(project_id, service_name, service_cfg).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Restart a service whose process exited, or let it go, according to its restart policy.
+    pub(super) async fn exited(
+        &mut self,
+        project_id: ProjectId,
+        service_name: String,
+        service_cfg: Service,
+    ) -> Result<()> {
+        let Some(running_services) = self.storage.get_mut(&project_id) else {
+            return Ok(());
+        };
+        // The service may have been stopped or restarted while its `post_stop` hook ran.
+        let Some(idx) = running_services
+            .iter()
+            .position(|s| s.name == service_name && s.status == Status::Stopped)
+        else {
+            return Ok(());
+        };
+
         if matches!(service_cfg.restart, Restart::Never) {
             running_services.remove(idx);
 
@@ -391,6 +1038,7 @@ impl<P: ProcessManager> SupervisorBackground<P> {
 
         running_services[idx].status = Status::Starting;
         running_services[idx].pid = None;
+        running_services[idx].restarts += 1;
 
         let _ = self
             .output_tx
@@ -400,17 +1048,11 @@ impl<P: ProcessManager> SupervisorBackground<P> {
             })
             .await;
 
-        let proc_id = self
-            .start_service(service_cfg, service_name.clone(), project_id.clone())
-            .await?;
-
-        if let Some(running_services) = self.storage.get_mut(&project_id) {
-            if let Some(svc) = running_services.iter_mut().find(|s| s.name == service_name) {
-                if svc.pid.is_none() {
-                    svc.pid = Some(proc_id);
-                    svc.status = Status::Starting;
-                }
-            }
+        if !self
+            .start_service(service_cfg, service_name, project_id.clone())
+            .await?
+        {
+            self.stop_if_idle(&project_id).await;
         }
 
         Ok(())
@@ -421,12 +1063,14 @@ impl<P: ProcessManager> SupervisorBackground<P> {
         project_id: ProjectId,
         updated_service: String,
     ) -> Result<()> {
-        // TODO: too many clones
-        let Some(mut running_services) = self.storage.get(&project_id).cloned() else {
+        let Some(config) = self.config.get(&project_id).cloned() else {
             return Err(Error::ProjectNotFound(project_id));
         };
 
-        {
+        let pid = {
+            let Some(running_services) = self.storage.get_mut(&project_id) else {
+                return Err(Error::ProjectNotFound(project_id));
+            };
             let Some(service) = running_services
                 .iter_mut()
                 .find(|s| s.name == updated_service)
@@ -435,46 +1079,76 @@ impl<P: ProcessManager> SupervisorBackground<P> {
             };
 
             service.status = Status::Running;
+            service.pid
+        };
+
+        let Some(service) = config.services.get(&updated_service) else {
+            return Err(Error::ServiceNotFound(project_id, updated_service));
+        };
+        let after = AfterHook::Started(pid);
+        match self
+            .run_hook(
+                &project_id,
+                &updated_service,
+                service,
+                HookKind::PostStart,
+                after,
+            )
+            .await
+        {
+            Some(result) => {
+                self.post_start_done(project_id, updated_service, pid, result)
+                    .await
+            }
+            None => Ok(()),
         }
+    }
 
-        for running_service in &mut running_services {
-            if let Status::Waiting { wait_for } = &mut running_service.status {
-                let new_wait_for = wait_for
-                    .clone()
-                    .into_iter()
-                    .filter(|item| item != &updated_service)
-                    .collect();
+    /// Start the services that were waiting for a service whose `post_start` hook is over, or
+    /// kill the service if the hook failed.
+    pub(super) async fn post_start_done(
+        &mut self,
+        project_id: ProjectId,
+        updated_service: String,
+        pid: Option<ProcId>,
+        result: HookResult,
+    ) -> Result<()> {
+        if let Err(reason) = result {
+            // The service is considered failed: it is stopped and its dependents keep waiting.
+            tracing::warn!("Stopping service {updated_service:?}: {reason}");
+            if let Some(pid) = pid {
+                let _ = self.process_manager.kill(pid).await;
+            }
+            return Ok(());
+        }
+        let Some(config) = self.config.get(&project_id).cloned() else {
+            return Err(Error::ProjectNotFound(project_id));
+        };
 
-                *wait_for = new_wait_for;
+        let mut ready = Vec::new();
+        for running_service in self.storage.get_mut(&project_id).into_iter().flatten() {
+            if let Status::Waiting { wait_for } = &mut running_service.status {
+                wait_for.retain(|item| item != &updated_service);
 
                 if wait_for.is_empty() {
-                    running_service.status = Status::Running;
-
-                    let Some(config) = self.config.get(&project_id).cloned() else {
-                        return Err(Error::ProjectNotFound(project_id));
-                    };
-                    let Some(service) = config.services.get(&running_service.name) else {
-                        return Err(Error::ServiceNotFound(
-                            project_id,
-                            running_service.name.clone(),
-                        ));
-                    };
-
-                    let proc_id = self
-                        .start_service(
-                            service.clone(),
-                            running_service.name.clone(),
-                            project_id.clone(),
-                        )
-                        .await?;
-
-                    running_service.pid = Some(proc_id);
                     running_service.status = Status::Starting;
+                    ready.push(running_service.name.clone());
                 }
             }
         }
 
-        self.storage.insert(project_id, running_services);
+        for service_name in ready {
+            let Some(service) = config.services.get(&service_name) else {
+                return Err(Error::ServiceNotFound(project_id, service_name));
+            };
+
+            if !self
+                .start_service(service.clone(), service_name, project_id.clone())
+                .await?
+            {
+                self.stop_if_idle(&project_id).await;
+            }
+        }
 
         Ok(())
     }
@@ -554,14 +1228,47 @@ impl<P: ProcessManager> SupervisorBackground<P> {
     }
 }
 
+fn dependency_blocked(dependency: &str) -> String {
+    format!("depends on blocked service `{dependency}`")
+}
+
 #[cfg(test)]
 mod tests {
-    use tutti_types::{ProjectId, Service};
+    use std::collections::BTreeMap;
 
-    use crate::process_manager::MockProcessManager;
+    use tutti_types::{
+        Hook, HookFailure, Hooks, InheritEnv, LogFormat, ProcessStats, ProjectId, Service,
+    };
+
+    use crate::{process_manager::MockProcessManager, supervisor::hooks::STOP_POLL_INTERVAL};
 
     use super::*;
 
+    fn service(deps: &[&str]) -> Service {
+        Service {
+            cmd: vec!["echo".to_string()],
+            cwd: Some("/".parse().unwrap()),
+            env: None,
+            inherit_env: InheritEnv::default(),
+            profiles: vec![],
+            deps: deps.iter().map(ToString::to_string).collect(),
+            hooks: Hooks::default(),
+            user: None,
+            group: None,
+            umask: None,
+            nice: None,
+            rlimits: BTreeMap::new(),
+            tty: false,
+            stdin_open: false,
+            log_format: LogFormat::Text,
+            max_memory: None,
+            max_cpu_percent: None,
+            limit_grace_period: None,
+            healthcheck: None,
+            restart: Restart::Always,
+        }
+    }
+
     #[tokio::test]
     async fn test_toposort() {
         let project_id = ProjectId("/project".parse().unwrap());
@@ -571,80 +1278,628 @@ mod tests {
                 version: 1,
                 id: project_id.clone(),
                 services: vec![
-                    (
-                        "A".to_string(),
-                        Service {
-                            cmd: vec!["echo".to_string()],
-                            cwd: Some("/".parse().unwrap()),
-                            env: None,
-                            deps: vec!["B".to_string(), "C".to_string()],
-                            healthcheck: None,
-                            restart: Restart::Always,
-                        },
-                    ),
-                    (
-                        "B".to_string(),
-                        Service {
-                            cmd: vec!["echo".to_string()],
-                            cwd: Some("/".parse().unwrap()),
-                            env: None,
-                            deps: vec![],
-                            healthcheck: None,
-                            restart: Restart::Always,
-                        },
-                    ),
-                    (
-                        "C".to_string(),
-                        Service {
-                            cmd: vec!["echo".to_string()],
-                            cwd: Some("/".parse().unwrap()),
-                            env: None,
-                            deps: vec!["D".to_string(), "E".to_string()],
-                            healthcheck: None,
-                            restart: Restart::Always,
-                        },
-                    ),
-                    (
-                        "D".to_string(),
-                        Service {
-                            cmd: vec!["echo".to_string()],
-                            cwd: Some("/".parse().unwrap()),
-                            env: None,
-                            deps: vec!["F".to_string()],
-                            healthcheck: None,
-                            restart: Restart::Always,
-                        },
-                    ),
-                    (
-                        "E".to_string(),
-                        Service {
-                            cmd: vec!["echo".to_string()],
-                            cwd: Some("/".parse().unwrap()),
-                            env: None,
-                            deps: vec![],
-                            healthcheck: None,
-                            restart: Restart::Always,
-                        },
-                    ),
-                    (
-                        "F".to_string(),
-                        Service {
-                            cmd: vec!["echo".to_string()],
-                            cwd: Some("/".parse().unwrap()),
-                            env: None,
-                            deps: vec![],
-                            healthcheck: None,
-                            restart: Restart::Always,
-                        },
-                    ),
+                    ("A".to_string(), service(&["B", "C"])),
+                    ("B".to_string(), service(&[])),
+                    ("C".to_string(), service(&["D", "E"])),
+                    ("D".to_string(), service(&["F"])),
+                    ("E".to_string(), service(&[])),
+                    ("F".to_string(), service(&[])),
                 ]
                 .into_iter()
                 .collect(),
+                groups: BTreeMap::new(),
             },
-            &vec!["A".to_string()],
+            &["A".to_string()],
         )
         .unwrap();
 
         assert_eq!(result, vec!["B", "E", "F", "D", "C", "A"]);
     }
+
+    #[tokio::test]
+    async fn test_service_env() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, _output_rx) =
+            SupervisorBackground::new(MockProcessManager::default(), commands_tx, commands_rx);
+        background.update_config(
+            project_id.clone(),
+            Project {
+                version: 1,
+                id: project_id.clone(),
+                services: BTreeMap::new(),
+                groups: BTreeMap::new(),
+            },
+            HashMap::from_iter([
+                ("PATH".to_owned(), "/usr/bin".to_owned()),
+                ("HOME".to_owned(), "/home/user".to_owned()),
+                ("PORT".to_owned(), "3000".to_owned()),
+            ]),
+        );
+
+        let mut service = Service {
+            env: Some(HashMap::from_iter([("PORT".to_owned(), "4000".to_owned())])),
+            inherit_env: InheritEnv::All,
+            ..service(&[])
+        };
+        assert_eq!(
+            background.service_env(&project_id, &service),
+            vec![
+                ("HOME".to_owned(), "/home/user".to_owned()),
+                ("PATH".to_owned(), "/usr/bin".to_owned()),
+                ("PORT".to_owned(), "4000".to_owned()),
+            ]
+        );
+
+        service.inherit_env = InheritEnv::Only(vec!["PATH".to_owned()]);
+        assert_eq!(
+            background.service_env(&project_id, &service),
+            vec![
+                ("PATH".to_owned(), "/usr/bin".to_owned()),
+                ("PORT".to_owned(), "4000".to_owned()),
+            ]
+        );
+
+        service.inherit_env = InheritEnv::None;
+        assert_eq!(
+            background.service_env(&project_id, &service),
+            vec![("PORT".to_owned(), "4000".to_owned())]
+        );
+    }
+
+    fn project_with_pre_start_hook(project_id: &ProjectId) -> Project {
+        let api = Service {
+            hooks: Hooks {
+                pre_start: Some(Hook {
+                    cmd: vec!["npm".to_owned(), "install".to_owned()],
+                    timeout: 60,
+                    on_failure: HookFailure::Fail,
+                }),
+                ..Hooks::default()
+            },
+            ..service(&[])
+        };
+        Project {
+            version: 1,
+            id: project_id.clone(),
+            services: BTreeMap::from([
+                ("api".to_owned(), api),
+                ("web".to_owned(), service(&["api"])),
+            ]),
+            groups: BTreeMap::new(),
+        }
+    }
+
+    /// Handle the commands the supervisor sends itself until no hook is running anymore.
+    async fn finish_hooks(background: &mut SupervisorBackground<MockProcessManager>) {
+        while !background.hooks.is_empty() {
+            let command = background.commands_rx.recv().await.unwrap();
+            background.handle_commands(command).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pre_start_hook() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, _output_rx) =
+            SupervisorBackground::new(MockProcessManager::default(), commands_tx, commands_rx);
+        background.update_config(
+            project_id.clone(),
+            project_with_pre_start_hook(&project_id),
+            HashMap::new(),
+        );
+
+        background
+            .up(project_id.clone(), vec!["web".to_owned()])
+            .await
+            .unwrap();
+        assert_eq!(background.process_manager.spawned(), vec!["api:pre_start"]);
+
+        finish_hooks(&mut background).await;
+        assert_eq!(
+            background.process_manager.spawned(),
+            vec!["api:pre_start", "api"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_while_hook_runs() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (mut supervisor, _output_rx) = crate::supervisor::Supervisor::new(
+            MockProcessManager::default().with_running("api:pre_start"),
+        );
+        supervisor
+            .up(
+                project_with_pre_start_hook(&project_id),
+                vec!["web".to_owned()],
+                HashMap::new(),
+            )
+            .await
+            .unwrap();
+
+        // The hook may run for a minute, the supervisor keeps answering meanwhile.
+        let status = tokio::time::timeout(
+            Duration::from_secs(1),
+            supervisor.status(project_id.clone()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(status[0].service, "api");
+        assert_eq!(status[0].state, ServiceState::Starting);
+        assert_eq!(status[0].pid, None);
+        assert_eq!(status[1].state, ServiceState::Waiting);
+    }
+
+    #[tokio::test]
+    async fn test_pre_start_hook_timeout() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, mut output_rx) = SupervisorBackground::new(
+            MockProcessManager::default().with_running("api:pre_start"),
+            commands_tx,
+            commands_rx,
+        );
+        let mut project = project_with_pre_start_hook(&project_id);
+        let api = project.services.get_mut("api").unwrap();
+        api.hooks.pre_start.as_mut().unwrap().timeout = 0;
+        background.update_config(project_id.clone(), project, HashMap::new());
+
+        background
+            .up(project_id.clone(), vec!["api".to_owned()])
+            .await
+            .unwrap();
+        finish_hooks(&mut background).await;
+
+        assert_eq!(background.process_manager.spawned(), vec!["api:pre_start"]);
+        assert!(matches!(
+            output_rx.try_recv(),
+            Ok(SupervisorEvent::Log { message, .. })
+                if message == "pre_start hook timed out after 0s\n"
+        ));
+        assert!(matches!(
+            output_rx.try_recv(),
+            Ok(SupervisorEvent::ServiceBlocked { service, .. }) if service == "api"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stats_skips_waiting_services() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, _output_rx) =
+            SupervisorBackground::new(MockProcessManager::default(), commands_tx, commands_rx);
+        background.update_config(
+            project_id.clone(),
+            project_with_pre_start_hook(&project_id),
+            HashMap::new(),
+        );
+        background
+            .up(project_id.clone(), vec!["web".to_owned()])
+            .await
+            .unwrap();
+        finish_hooks(&mut background).await;
+
+        let stats = background.stats(Some(&project_id)).await;
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].service, "api");
+        assert_eq!(stats[0].stats.pid, 1);
+
+        let other = ProjectId("/other".parse().unwrap());
+        assert!(background.stats(Some(&other)).await.is_empty());
+        assert_eq!(background.stats(None).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failing_pre_start_hook_blocks_service() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, mut output_rx) = SupervisorBackground::new(
+            MockProcessManager::default().with_exit_code("api:pre_start", 1),
+            commands_tx,
+            commands_rx,
+        );
+        background.update_config(
+            project_id.clone(),
+            project_with_pre_start_hook(&project_id),
+            HashMap::new(),
+        );
+
+        background
+            .up(project_id.clone(), vec!["web".to_owned()])
+            .await
+            .unwrap();
+        finish_hooks(&mut background).await;
+
+        assert_eq!(background.process_manager.spawned(), vec!["api:pre_start"]);
+
+        let mut events = Vec::new();
+        while let Ok(event) = output_rx.try_recv() {
+            events.push(match event {
+                SupervisorEvent::Log {
+                    service, message, ..
+                } => format!("log {service}: {message}"),
+                SupervisorEvent::ServiceBlocked {
+                    service, reason, ..
+                } => format!("blocked {service}: {reason}"),
+                SupervisorEvent::ProjectStopped { .. } => "stopped".to_owned(),
+                other => format!("{other:?}"),
+            });
+        }
+        assert_eq!(
+            events,
+            vec![
+                "log api:pre_start: pre_start hook exited with code 1\n",
+                "blocked api: pre_start hook exited with code 1",
+                "blocked web: depends on blocked service `api`",
+                "stopped",
+            ]
+        );
+        assert!(!background.storage.contains_key(&project_id));
+    }
+
+    #[tokio::test]
+    async fn test_limit_exceeded_after_grace_period() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, mut output_rx) = SupervisorBackground::new(
+            MockProcessManager::default().with_stats(
+                "api",
+                ProcessStats {
+                    memory_rss: 2048,
+                    ..ProcessStats::default()
+                },
+            ),
+            commands_tx,
+            commands_rx,
+        );
+        let mut project = Project {
+            version: 1,
+            id: project_id.clone(),
+            services: BTreeMap::from([
+                (
+                    "api".to_owned(),
+                    Service {
+                        max_memory: Some(1024),
+                        limit_grace_period: Some(0),
+                        ..service(&[])
+                    },
+                ),
+                (
+                    "web".to_owned(),
+                    Service {
+                        max_memory: Some(1024),
+                        ..service(&[])
+                    },
+                ),
+            ]),
+            groups: BTreeMap::new(),
+        };
+        background.update_config(project_id.clone(), project.clone(), HashMap::new());
+        background
+            .up(project_id.clone(), vec!["api".to_owned(), "web".to_owned()])
+            .await
+            .unwrap();
+
+        background.check_limits().await;
+        let Ok(SupervisorEvent::LimitExceeded {
+            service, reason, ..
+        }) = output_rx.try_recv()
+        else {
+            panic!("expected a LimitExceeded event");
+        };
+        assert_eq!(service, "api");
+        assert_eq!(
+            reason,
+            "memory usage of 2048 bytes is above max_memory of 1024 bytes"
+        );
+        // `web` is within its limit.
+        assert!(output_rx.try_recv().is_err());
+        // Both services are sampled at once.
+        assert_eq!(background.process_manager.samples(), 1);
+        assert!(background.limit_breaches.is_empty());
+
+        // Within the default grace period the breach is only recorded.
+        project.services.get_mut("api").unwrap().limit_grace_period = None;
+        background.update_config(project_id.clone(), project, HashMap::new());
+        background.check_limits().await;
+        assert!(output_rx.try_recv().is_err());
+        assert_eq!(background.limit_breaches.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_input() {
+        use tokio::io::AsyncReadExt;
+
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, _output_rx) =
+            SupervisorBackground::new(MockProcessManager::default(), commands_tx, commands_rx);
+        let project = Project {
+            version: 1,
+            id: project_id.clone(),
+            services: BTreeMap::from([
+                (
+                    "repl".to_owned(),
+                    Service {
+                        stdin_open: true,
+                        ..service(&[])
+                    },
+                ),
+                ("api".to_owned(), service(&[])),
+            ]),
+            groups: BTreeMap::new(),
+        };
+        background.update_config(project_id.clone(), project, HashMap::new());
+        background
+            .up(
+                project_id.clone(),
+                vec!["repl".to_owned(), "api".to_owned()],
+            )
+            .await
+            .unwrap();
+
+        background
+            .input(project_id.clone(), "repl".to_owned(), b"1 + 1\n".to_vec())
+            .unwrap();
+        let mut stdin = background.process_manager.take_stdin("repl").unwrap();
+        let mut buf = [0; 6];
+        stdin.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"1 + 1\n");
+
+        let err = background
+            .input(project_id.clone(), "api".to_owned(), b"x".to_vec())
+            .unwrap_err();
+        assert!(matches!(err, Error::NoStdin(service) if service == "api"));
+        let err = background
+            .input(project_id, "db".to_owned(), b"x".to_vec())
+            .unwrap_err();
+        assert!(matches!(err, Error::ServiceNotFound(_, service) if service == "db"));
+    }
+
+    #[tokio::test]
+    async fn test_exec() {
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, mut output_rx) = SupervisorBackground::new(
+            MockProcessManager::default().with_exit_code("api:exec", 3),
+            commands_tx,
+            commands_rx,
+        );
+        background
+            .exec(
+                42,
+                "api",
+                &service(&[]),
+                &["migrate".to_owned()],
+                &HashMap::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(background.process_manager.spawned(), vec!["api:exec"]);
+
+        // The mock process has no output, so its end is reported right away.
+        let command = background.commands_rx.recv().await.unwrap();
+        assert!(matches!(
+            command,
+            SupervisorCommand::ExecOutputEnded { exec_id: 42 }
+        ));
+        background.handle_commands(command).await.unwrap();
+        assert!(matches!(
+            output_rx.try_recv(),
+            Ok(SupervisorEvent::ExecExited {
+                exec_id: 42,
+                code: 3
+            })
+        ));
+        assert!(background.execs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restart_and_stop_service() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, mut output_rx) =
+            SupervisorBackground::new(MockProcessManager::default(), commands_tx, commands_rx);
+        let project = Project {
+            version: 1,
+            id: project_id.clone(),
+            services: BTreeMap::from([
+                ("api".to_owned(), service(&[])),
+                ("web".to_owned(), service(&[])),
+            ]),
+            groups: BTreeMap::new(),
+        };
+        background.update_config(project_id.clone(), project, HashMap::new());
+        background
+            .up(project_id.clone(), vec!["api".to_owned()])
+            .await
+            .unwrap();
+
+        background
+            .restart_service(project_id.clone(), "api".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(background.process_manager.spawned(), vec!["api", "api"]);
+        background
+            .health_check_success(project_id.clone(), "api".to_owned())
+            .await
+            .unwrap();
+
+        // The end of the output of the replaced process does not restart the new one.
+        background
+            .end_of_logs(project_id.clone(), "api".to_owned(), ProcId(0))
+            .await
+            .unwrap();
+        assert_eq!(background.process_manager.spawned(), vec!["api", "api"]);
+
+        assert_eq!(background.projects(), vec![project_id.clone()]);
+        let status = background.status(project_id.clone()).unwrap();
+        assert_eq!(status.len(), 2);
+        assert_eq!(status[0].service, "api");
+        assert_eq!(status[0].state, ServiceState::Running);
+        assert_eq!(status[0].restarts, 1);
+        assert!(status[0].uptime.is_some());
+        assert_eq!(status[1].state, ServiceState::Stopped);
+        assert_eq!(status[1].uptime, None);
+
+        background
+            .stop_service(project_id.clone(), "api".to_owned())
+            .await
+            .unwrap();
+        let status = background.status(project_id.clone()).unwrap();
+        assert!(status
+            .iter()
+            .all(|status| status.state == ServiceState::Stopped));
+        let err = background
+            .stop_service(project_id.clone(), "api".to_owned())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ServiceNotRunning(service) if service == "api"));
+
+        let mut events = Vec::new();
+        while let Ok(event) = output_rx.try_recv() {
+            events.push(event);
+        }
+        assert!(matches!(
+            events.as_slice(),
+            [
+                SupervisorEvent::ServiceRestarted { .. },
+                SupervisorEvent::ServiceStopped { .. },
+                SupervisorEvent::ProjectStopped { .. },
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_post_stop_hook_waits_for_exit() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, mut output_rx) = SupervisorBackground::new(
+            MockProcessManager::default()
+                .with_running("api")
+                .with_lingering("api"),
+            commands_tx,
+            commands_rx,
+        );
+        let api = Service {
+            hooks: Hooks {
+                post_stop: Some(Hook {
+                    cmd: vec!["rm".to_owned(), "api.sock".to_owned()],
+                    timeout: 60,
+                    on_failure: HookFailure::Fail,
+                }),
+                ..Hooks::default()
+            },
+            ..service(&[])
+        };
+        let project = Project {
+            version: 1,
+            id: project_id.clone(),
+            services: BTreeMap::from([("api".to_owned(), api)]),
+            groups: BTreeMap::new(),
+        };
+        background.update_config(project_id.clone(), project, HashMap::new());
+        background
+            .up(project_id.clone(), vec!["api".to_owned()])
+            .await
+            .unwrap();
+        background
+            .health_check_success(project_id.clone(), "api".to_owned())
+            .await
+            .unwrap();
+
+        // The supervisor does not wait for the killed process to exit.
+        let started = Instant::now();
+        background
+            .stop_service(project_id.clone(), "api".to_owned())
+            .await
+            .unwrap();
+        assert!(started.elapsed() < STOP_POLL_INTERVAL);
+        assert_eq!(background.process_manager.spawned(), vec!["api"]);
+
+        background.process_manager.exit_lingering("api");
+        // Skip what the spawned process queued, such as the end of its output.
+        let command = loop {
+            let command = background.commands_rx.recv().await.unwrap();
+            if matches!(command, SupervisorCommand::CheckKilled { .. }) {
+                break command;
+            }
+        };
+        background.handle_commands(command).await.unwrap();
+        finish_hooks(&mut background).await;
+        assert_eq!(
+            background.process_manager.spawned(),
+            vec!["api", "api:post_stop"]
+        );
+
+        let mut events = Vec::new();
+        while let Ok(event) = output_rx.try_recv() {
+            events.push(event);
+        }
+        assert!(matches!(
+            events.as_slice(),
+            [
+                SupervisorEvent::ServiceStopped { .. },
+                SupervisorEvent::ProjectStopped { .. },
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_service_exit_code() {
+        let project_id = ProjectId("/project".parse().unwrap());
+        let (commands_tx, commands_rx) = tokio::sync::mpsc::channel(10);
+        let (mut background, mut output_rx) = SupervisorBackground::new(
+            MockProcessManager::default().with_exit_code("api", 3),
+            commands_tx,
+            commands_rx,
+        );
+        let project = Project {
+            version: 1,
+            id: project_id.clone(),
+            services: BTreeMap::from([(
+                "api".to_owned(),
+                Service {
+                    restart: Restart::Never,
+                    ..service(&[])
+                },
+            )]),
+            groups: BTreeMap::new(),
+        };
+        background.update_config(project_id.clone(), project, HashMap::new());
+        background
+            .up(project_id.clone(), vec!["api".to_owned()])
+            .await
+            .unwrap();
+        assert_eq!(
+            background.status(project_id.clone()).unwrap()[0].last_exit,
+            None
+        );
+        background
+            .health_check_success(project_id.clone(), "api".to_owned())
+            .await
+            .unwrap();
+        background
+            .end_of_logs(project_id.clone(), "api".to_owned(), ProcId(0))
+            .await
+            .unwrap();
+
+        let status = background.status(project_id.clone()).unwrap();
+        assert_eq!(status[0].state, ServiceState::Stopped);
+        assert_eq!(status[0].last_exit, Some(ServiceExit { code: Some(3) }));
+
+        let mut events = Vec::new();
+        while let Ok(event) = output_rx.try_recv() {
+            events.push(event);
+        }
+        assert!(matches!(
+            events.as_slice(),
+            [
+                SupervisorEvent::ServiceExited { service, code: Some(3), .. },
+                SupervisorEvent::ServiceStopped { .. },
+                SupervisorEvent::ProjectStopped { .. },
+            ] if service == "api"
+        ));
+    }
 }
@@ -1,5 +1,9 @@
-use tokio::sync::mpsc;
-use tutti_types::{Project, ProjectId};
+use std::collections::HashMap;
+
+use tokio::sync::{mpsc, oneshot};
+use tutti_types::{Project, ProjectId, Service, ServiceStats, ServiceStatus, WindowSize};
+
+use crate::{error::Result, ProcId};
 
 pub type UpResponse = mpsc::Sender<Result<(), ()>>;
 
@@ -8,6 +12,7 @@ pub enum SupervisorCommand {
     UpdateConfig {
         project_id: ProjectId,
         config: Project,
+        env: HashMap<String, String>,
     },
     Up {
         project_id: ProjectId,
@@ -16,15 +21,95 @@ pub enum SupervisorCommand {
     Down {
         project_id: ProjectId,
     },
-    Shutdown,
+    /// Stop every project, answering once their services are stopped.
+    Shutdown {
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// The output of a process of a service ended.
     EndOfLogs {
         project_id: ProjectId,
         service: String,
+        /// The process, which may have been replaced by the time this is handled.
+        pid: ProcId,
     },
     HealthCheckSuccess {
         project_id: ProjectId,
         service: String,
     },
+    /// The terminal of the client of a project was resized.
+    Resize {
+        project_id: ProjectId,
+        size: WindowSize,
+    },
+    /// Write to the stdin of a running service.
+    Input {
+        project_id: ProjectId,
+        service: String,
+        data: Vec<u8>,
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Run a one-off command with the directory, environment and process settings of a
+    /// service. Its output is reported with `ExecOutput` events, identified by `exec_id`.
+    Exec {
+        exec_id: u32,
+        project_id: ProjectId,
+        service: String,
+        config: Box<Service>,
+        cmd: Vec<String>,
+        /// Environment of the client, inherited according to the service's `inherit_env`.
+        env: HashMap<String, String>,
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// The output of a command started with `Exec` ended; report its exit code once it exits.
+    ExecOutputEnded {
+        exec_id: u32,
+    },
+    /// Interrupt a command started with `Exec`.
+    ExecKill {
+        exec_id: u32,
+    },
+    /// Check whether a hook exited, killing it once it runs past its timeout.
+    CheckHook {
+        hook_id: u32,
+    },
+    /// A hook is over and its output was forwarded: continue with what waited for it.
+    HookEnded {
+        hook_id: u32,
+        /// The reason of the failure, if the hook failed.
+        result: std::result::Result<(), String>,
+    },
+    /// Check whether a process killed on request exited, to run its service's `post_stop` hook.
+    CheckKilled {
+        pid: ProcId,
+    },
+    /// Stop one service of a project. Services depending on it keep running.
+    StopService {
+        project_id: ProjectId,
+        service: String,
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Stop one service of a project if it is running, and start it again.
+    RestartService {
+        project_id: ProjectId,
+        service: String,
+        response: oneshot::Sender<Result<()>>,
+    },
+    /// Report every service of a project, including the ones that are not running.
+    Status {
+        project_id: ProjectId,
+        response: oneshot::Sender<Result<Vec<ServiceStatus>>>,
+    },
+    /// Stop the services that have been over their resource limits for too long.
+    CheckLimits,
+    /// Sample the resource usage of the running services of a project, or of every project.
+    Stats {
+        project_id: Option<ProjectId>,
+        response: oneshot::Sender<Vec<ServiceStats>>,
+    },
+    /// List the projects that have services running.
+    Projects {
+        response: oneshot::Sender<Vec<ProjectId>>,
+    },
     // HealthCheckFailure {
     //     project_id: ProjectId,
     //     service: String,
@@ -37,6 +122,9 @@ pub enum SupervisorEvent {
         project_id: ProjectId,
         service: String,
         message: String,
+        /// Whether the output was written to stderr. Services on a pseudo-terminal only have
+        /// stdout.
+        stderr: bool,
     },
     ProjectStopped {
         project_id: ProjectId,
@@ -49,6 +137,39 @@ pub enum SupervisorEvent {
         project_id: ProjectId,
         service: String,
     },
+    /// The process of the service exited on its own, with its exit code if it is known, or 128
+    /// plus the signal that killed it. It is then stopped or restarted according to its restart
+    /// policy.
+    ServiceExited {
+        project_id: ProjectId,
+        service: String,
+        code: Option<i32>,
+    },
+    /// The service was not started because its `pre_start` hook failed or it depends on a
+    /// service that was not started.
+    ServiceBlocked {
+        project_id: ProjectId,
+        service: String,
+        reason: String,
+    },
+    /// The service was stopped because it used more memory or CPU than allowed. It is
+    /// restarted according to its restart policy.
+    LimitExceeded {
+        project_id: ProjectId,
+        service: String,
+        reason: String,
+    },
+    /// Output of a command started with `Exec`.
+    ExecOutput {
+        exec_id: u32,
+        data: Vec<u8>,
+        stderr: bool,
+    },
+    /// A command started with `Exec` exited.
+    ExecExited {
+        exec_id: u32,
+        code: i32,
+    },
     Error {
         project_id: ProjectId,
         message: String,
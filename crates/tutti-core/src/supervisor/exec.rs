@@ -0,0 +1,99 @@
+use std::{collections::HashMap, time::Duration};
+
+use futures::StreamExt;
+use tutti_types::Service;
+
+use crate::{
+    error::Result,
+    process_manager::service_env,
+    supervisor::{background::SupervisorBackground, commands::SupervisorEvent, SupervisorCommand},
+    CommandSpec, ProcessManager,
+};
+
+/// How often to check whether a command started with `Exec` exited after closing its output.
+pub(super) const EXEC_EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl<P: ProcessManager> SupervisorBackground<P> {
+    /// Start a one-off command and forward its output as `ExecOutput` events.
+    pub(super) async fn exec(
+        &mut self,
+        exec_id: u32,
+        service: &str,
+        config: &Service,
+        cmd: &[String],
+        env: &HashMap<String, String>,
+    ) -> Result<()> {
+        let spec = CommandSpec {
+            // Nothing reads its input: the command runs like a hook.
+            stdin: false,
+            ..CommandSpec::for_service(
+                config,
+                format!("{service}:exec"),
+                cmd,
+                service_env(config, env),
+            )
+        };
+        let process = self.process_manager.spawn(spec).await?;
+        self.execs.insert(exec_id, process.id);
+
+        let output_tx = self.output_tx.clone();
+        let commands_tx = self.commands_tx.clone();
+        tokio::spawn(async move {
+            let stdout = process.stdout.map(|data| (data, false));
+            let stderr = process.stderr.map(|data| (data, true));
+            let mut output = futures::stream::select(stdout, stderr);
+            while let Some((data, stderr)) = output.next().await {
+                let _ = output_tx
+                    .send(SupervisorEvent::ExecOutput {
+                        exec_id,
+                        data,
+                        stderr,
+                    })
+                    .await;
+            }
+            let _ = commands_tx
+                .send(SupervisorCommand::ExecOutputEnded { exec_id })
+                .await;
+        });
+
+        Ok(())
+    }
+
+    /// Interrupt a command started with `exec`, unless it already exited.
+    pub(super) async fn exec_kill(&mut self, exec_id: u32) -> Result<()> {
+        let Some(pid) = self.execs.get(&exec_id).copied() else {
+            return Ok(());
+        };
+        self.process_manager.shutdown(pid).await
+    }
+
+    /// Report the exit code of a command whose output ended, or check again a bit later if it
+    /// is still running.
+    pub(super) async fn exec_output_ended(&mut self, exec_id: u32) {
+        let Some(pid) = self.execs.get(&exec_id).copied() else {
+            return;
+        };
+        match self.process_manager.wait(pid, Duration::ZERO).await {
+            Ok(Some(code)) => {
+                self.execs.remove(&exec_id);
+                let _ = self
+                    .output_tx
+                    .send(SupervisorEvent::ExecExited { exec_id, code })
+                    .await;
+            }
+            Ok(None) => {
+                let commands_tx = self.commands_tx.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(EXEC_EXIT_POLL_INTERVAL).await;
+                    let _ = commands_tx
+                        .send(SupervisorCommand::ExecOutputEnded { exec_id })
+                        .await;
+                });
+            }
+            Err(err) => {
+                tracing::error!("Cannot wait for command {exec_id}: {err:?}");
+                self.execs.remove(&exec_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+use tutti_types::{Hook, HookFailure, HookKind, ProjectId, Service};
+
+use crate::{
+    error::Result,
+    supervisor::{background::SupervisorBackground, commands::SupervisorEvent, SupervisorCommand},
+    CommandSpec, ProcId, ProcessManager,
+};
+
+/// How long to wait for a killed process to exit and for its output to be flushed.
+pub(super) const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often to check whether a hook exited.
+pub(super) const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often to check whether a killed process exited, before its `post_stop` hook runs.
+pub(super) const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Result of a hook: the reason of the failure if it failed.
+pub(super) type HookResult = std::result::Result<(), String>;
+
+/// A hook that was started, until its result is handled.
+#[derive(Debug)]
+pub(super) struct RunningHook {
+    project_id: ProjectId,
+    service: String,
+    kind: HookKind,
+    hook: Hook,
+    process: ProcId,
+    started_at: Instant,
+    /// Whether the hook was killed for running past its timeout.
+    timed_out: bool,
+    /// Tasks forwarding the output of the hook.
+    output: Vec<JoinHandle<()>>,
+    then: AfterHook,
+}
+
+/// What happens to a service once one of its hooks is over.
+#[derive(Debug)]
+pub(super) enum AfterHook {
+    /// Spawn the service, after `pre_start`.
+    Start(Service),
+    /// Start the services waiting for it, after `post_start`. If the hook failed, its process is
+    /// killed instead.
+    Started(Option<ProcId>),
+    /// Kill its process, after `pre_stop`.
+    Stop(ProcId, AfterStop),
+    /// Report it as stopped, after `post_stop`.
+    Stopped(AfterStop),
+    /// Restart it or let it go according to its restart policy, after the `post_stop` of a
+    /// process that exited.
+    Exited(Service),
+}
+
+/// A process killed on request, until it exits and its service's `post_stop` hook can run.
+#[derive(Debug)]
+pub(super) struct KilledProcess {
+    project_id: ProjectId,
+    service: String,
+    config: Service,
+    killed_at: Instant,
+    then: AfterStop,
+}
+
+/// What happens once a service was stopped on request.
+#[derive(Debug, Clone)]
+pub(super) enum AfterStop {
+    /// Its whole project is being stopped.
+    Down,
+    /// Only the service was stopped.
+    Service,
+    /// It is started again.
+    Restart(Box<Service>),
+}
+
+impl<P: ProcessManager> SupervisorBackground<P> {
+    /// Stop a process of a service, running the service's stop hooks around it, and continue
+    /// with `then`. The service must already be out of `storage` or marked as restarting, so
+    /// that the end of its output is not taken for an exit.
+    pub(super) async fn stop_process(
+        &mut self,
+        project_id: &ProjectId,
+        service_name: &str,
+        pid: ProcId,
+        then: AfterStop,
+    ) -> Result<()> {
+        let config = self
+            .config
+            .get(project_id)
+            .and_then(|config| config.services.get(service_name))
+            .cloned();
+        if let Some(config) = &config {
+            let after = AfterHook::Stop(pid, then.clone());
+            if self
+                .run_hook(project_id, service_name, config, HookKind::PreStop, after)
+                .await
+                .is_none()
+            {
+                return Ok(());
+            }
+        }
+
+        self.kill_process(project_id, service_name, pid, then).await
+    }
+
+    /// Kill a process of a service after its `pre_stop` hook, and run its `post_stop` hook once
+    /// the process exited. Stop hooks cannot prevent the service from stopping, failures are only
+    /// reported in the logs.
+    pub(super) async fn kill_process(
+        &mut self,
+        project_id: &ProjectId,
+        service_name: &str,
+        pid: ProcId,
+        then: AfterStop,
+    ) -> Result<()> {
+        let _ = self.process_manager.kill(pid).await;
+        self.forget_process(pid);
+
+        let config = self
+            .config
+            .get(project_id)
+            .and_then(|config| config.services.get(service_name))
+            .filter(|config| config.hooks.post_stop.is_some())
+            .cloned();
+        let Some(config) = config else {
+            return self.stopped(project_id, service_name, then).await;
+        };
+        self.killed.insert(
+            pid,
+            KilledProcess {
+                project_id: project_id.clone(),
+                service: service_name.to_owned(),
+                config,
+                killed_at: Instant::now(),
+                then,
+            },
+        );
+        self.check_killed(pid).await
+    }
+
+    /// Run the `post_stop` hook of a killed process once it exited, or once it has had
+    /// `STOP_TIMEOUT` to do so. Until then it is checked again a bit later.
+    pub(super) async fn check_killed(&mut self, pid: ProcId) -> Result<()> {
+        let Some(killed) = self.killed.get(&pid) else {
+            return Ok(());
+        };
+        let running = matches!(
+            self.process_manager.wait(pid, Duration::ZERO).await,
+            Ok(None)
+        );
+        if running && killed.killed_at.elapsed() < STOP_TIMEOUT {
+            let commands_tx = self.commands_tx.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(STOP_POLL_INTERVAL).await;
+                let _ = commands_tx
+                    .send(SupervisorCommand::CheckKilled { pid })
+                    .await;
+            });
+            return Ok(());
+        }
+
+        let Some(KilledProcess {
+            project_id,
+            service,
+            config,
+            then,
+            ..
+        }) = self.killed.remove(&pid)
+        else {
+            return Ok(());
+        };
+        let after = AfterHook::Stopped(then.clone());
+        if self
+            .run_hook(&project_id, &service, &config, HookKind::PostStop, after)
+            .await
+            .is_none()
+        {
+            return Ok(());
+        }
+        self.stopped(&project_id, &service, then).await
+    }
+
+    /// Start one of the service's hooks in the service's directory and environment. Its output
+    /// goes to the log stream under `service:hook`. The supervisor keeps going while the hook
+    /// runs: it is checked with `CheckHook` until it exits, then `HookEnded` continues with
+    /// `then`.
+    ///
+    /// Returns the result right away if there is nothing to wait for, because the service has no
+    /// such hook or it failed to start. The result is an error if the hook failed and is not
+    /// allowed to.
+    pub(super) async fn run_hook(
+        &mut self,
+        project_id: &ProjectId,
+        service_name: &str,
+        service: &Service,
+        kind: HookKind,
+        then: AfterHook,
+    ) -> Option<HookResult> {
+        let Some(hook) = service.hooks.get(kind) else {
+            return Some(Ok(()));
+        };
+        let tag = format!("{service_name}:{kind}");
+        tracing::debug!("Running hook {tag:?} for project {project_id:?}");
+
+        let spawned = self
+            .process_manager
+            .spawn(CommandSpec {
+                // Hooks always run on pipes, they are not interactive.
+                tty: false,
+                stdin: false,
+                ..self.command_spec(project_id, service, tag.clone(), &hook.cmd)
+            })
+            .await;
+        let process = match spawned {
+            Ok(process) => process,
+            Err(err) => {
+                let reason = format!("{kind} hook failed to start: {err}");
+                return Some(self.hook_failed(project_id, &tag, hook, reason).await);
+            }
+        };
+
+        let output = vec![
+            self.forward_logs(project_id, &tag, process.stdout, false),
+            self.forward_logs(project_id, &tag, process.stderr, true),
+        ];
+        let hook_id = self.next_hook_id;
+        self.next_hook_id = self.next_hook_id.wrapping_add(1);
+        self.hooks.insert(
+            hook_id,
+            RunningHook {
+                project_id: project_id.clone(),
+                service: service_name.to_owned(),
+                kind,
+                hook: hook.clone(),
+                process: process.id,
+                started_at: Instant::now(),
+                timed_out: false,
+                output,
+                then,
+            },
+        );
+        self.check_hook_later(hook_id);
+
+        None
+    }
+
+    pub(super) fn check_hook_later(&self, hook_id: u32) {
+        let commands_tx = self.commands_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(HOOK_POLL_INTERVAL).await;
+            let _ = commands_tx
+                .send(SupervisorCommand::CheckHook { hook_id })
+                .await;
+        });
+    }
+
+    /// Report the result of a hook that exited, or check again a bit later if it is still
+    /// running. A hook past its timeout is killed.
+    pub(super) async fn check_hook(&mut self, hook_id: u32) {
+        let Some(running) = self.hooks.get_mut(&hook_id) else {
+            return;
+        };
+        let kind = running.kind;
+        let result = match self
+            .process_manager
+            .wait(running.process, Duration::ZERO)
+            .await
+        {
+            Ok(None) => {
+                let timeout = Duration::from_secs(running.hook.timeout);
+                if !running.timed_out && running.started_at.elapsed() >= timeout {
+                    let _ = self.process_manager.kill(running.process).await;
+                    running.timed_out = true;
+                }
+                self.check_hook_later(hook_id);
+                return;
+            }
+            Ok(Some(_)) if running.timed_out => Err(format!(
+                "{kind} hook timed out after {}s",
+                running.hook.timeout
+            )),
+            Ok(Some(0)) => Ok(()),
+            Ok(Some(code)) => Err(format!("{kind} hook exited with code {code}")),
+            Err(err) => Err(format!("{kind} hook failed: {err}")),
+        };
+
+        // Let the output of the hook reach the log stream before anything else is reported.
+        // Processes left behind by the hook may keep the pipes open.
+        let output = std::mem::take(&mut running.output);
+        let commands_tx = self.commands_tx.clone();
+        tokio::spawn(async move {
+            for output in output {
+                let _ = tokio::time::timeout(STOP_TIMEOUT, output).await;
+            }
+            let _ = commands_tx
+                .send(SupervisorCommand::HookEnded { hook_id, result })
+                .await;
+        });
+    }
+
+    /// Continue with what waited for a hook that is over.
+    pub(super) async fn hook_ended(&mut self, hook_id: u32, result: HookResult) -> Result<()> {
+        let Some(running) = self.hooks.remove(&hook_id) else {
+            return Ok(());
+        };
+        let result = match result {
+            Ok(()) => Ok(()),
+            Err(reason) => {
+                let tag = format!("{}:{}", running.service, running.kind);
+                self.hook_failed(&running.project_id, &tag, &running.hook, reason)
+                    .await
+            }
+        };
+
+        let RunningHook {
+            project_id,
+            service,
+            then,
+            ..
+        } = running;
+        match then {
+            AfterHook::Start(config) => {
+                // The service may have been stopped while the hook ran.
+                if self.starting(&project_id, &service)
+                    && !self
+                        .pre_start_done(config, service, project_id.clone(), result)
+                        .await?
+                {
+                    self.stop_if_idle(&project_id).await;
+                }
+                Ok(())
+            }
+            AfterHook::Started(pid) => {
+                let current = self
+                    .storage
+                    .get(&project_id)
+                    .and_then(|services| services.iter().find(|s| s.name == service))
+                    .is_some_and(|running| running.pid == pid);
+                if !current {
+                    return Ok(());
+                }
+                self.post_start_done(project_id, service, pid, result).await
+            }
+            AfterHook::Stop(pid, then) => self.kill_process(&project_id, &service, pid, then).await,
+            AfterHook::Stopped(then) => self.stopped(&project_id, &service, then).await,
+            AfterHook::Exited(config) => self.exited(project_id, service, config).await,
+        }
+    }
+
+    /// Report the failure of a hook in the log stream. Returns it unless the hook is allowed
+    /// to fail.
+    pub(super) async fn hook_failed(
+        &self,
+        project_id: &ProjectId,
+        tag: &str,
+        hook: &Hook,
+        reason: String,
+    ) -> HookResult {
+        let _ = self
+            .output_tx
+            .send(SupervisorEvent::Log {
+                project_id: project_id.clone(),
+                service: tag.to_owned(),
+                message: format!("{reason}\n"),
+                stderr: true,
+            })
+            .await;
+
+        match hook.on_failure {
+            HookFailure::Fail => Err(reason),
+            HookFailure::Continue => Ok(()),
+        }
+    }
+}
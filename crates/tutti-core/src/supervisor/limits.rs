@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tutti_types::{ProcessStats, ProjectId, Service};
+
+use crate::{
+    supervisor::{background::SupervisorBackground, commands::SupervisorEvent},
+    ProcId, ProcessManager,
+};
+
+/// How often services with `max_memory` or `max_cpu_percent` are sampled.
+pub(super) const LIMIT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a limit may be exceeded if the service has no `limit_grace_period`.
+pub(super) const DEFAULT_LIMIT_GRACE_PERIOD: u64 = 10;
+
+impl<P: ProcessManager> SupervisorBackground<P> {
+    /// Kill the services whose process tree has been over `max_memory` or `max_cpu_percent` for
+    /// longer than their grace period. They are then restarted according to their restart policy.
+    pub(super) async fn check_limits(&mut self) {
+        let limited: Vec<(ProjectId, String, ProcId, Service)> = self
+            .storage
+            .iter()
+            .flat_map(|(project_id, services)| {
+                let config = self.config.get(project_id);
+                services.iter().filter_map(move |service| {
+                    let config = config?.services.get(&service.name)?;
+                    if config.max_memory.is_none() && config.max_cpu_percent.is_none() {
+                        return None;
+                    }
+                    Some((
+                        project_id.clone(),
+                        service.name.clone(),
+                        service.pid?,
+                        config.clone(),
+                    ))
+                })
+            })
+            .collect();
+
+        if limited.is_empty() {
+            return;
+        }
+        let pids: Vec<ProcId> = limited.iter().map(|(_, _, pid, _)| *pid).collect();
+        let mut samples = match self.process_manager.stats(&pids).await {
+            Ok(samples) => samples,
+            Err(err) => {
+                tracing::warn!("Cannot sample services: {err:?}");
+                return;
+            }
+        };
+
+        let now = Instant::now();
+        let mut breaches = HashMap::new();
+        for (project_id, service_name, pid, service) in limited {
+            let Some(stats) = samples.remove(&pid) else {
+                continue;
+            };
+            let Some(reason) = limit_breach(&service, &stats) else {
+                continue;
+            };
+
+            let since = self.limit_breaches.get(&pid).copied().unwrap_or(now);
+            let grace = service
+                .limit_grace_period
+                .unwrap_or(DEFAULT_LIMIT_GRACE_PERIOD);
+            if now.duration_since(since) < Duration::from_secs(grace) {
+                breaches.insert(pid, since);
+                continue;
+            }
+
+            tracing::info!("Service {service_name:?} exceeded its limits: {reason}");
+            let _ = self
+                .output_tx
+                .send(SupervisorEvent::LimitExceeded {
+                    project_id,
+                    service: service_name,
+                    reason,
+                })
+                .await;
+            // The end of its output restarts or stops the service.
+            let _ = self.process_manager.kill(pid).await;
+        }
+        // Processes back under their limits, or gone, start over.
+        self.limit_breaches = breaches;
+    }
+}
+
+/// Describe the first limit of `service` that `stats` is over.
+fn limit_breach(service: &Service, stats: &ProcessStats) -> Option<String> {
+    if let Some(max) = service.max_memory.filter(|max| stats.memory_rss > *max) {
+        return Some(format!(
+            "memory usage of {} bytes is above max_memory of {max} bytes",
+            stats.memory_rss
+        ));
+    }
+    if let Some(max) = service
+        .max_cpu_percent
+        .filter(|max| stats.cpu_percent > f64::from(*max))
+    {
+        return Some(format!(
+            "CPU usage of {:.1}% is above max_cpu_percent of {max}%",
+            stats.cpu_percent
+        ));
+    }
+    None
+}
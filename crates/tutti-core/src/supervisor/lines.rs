@@ -0,0 +1,73 @@
+/// Longest piece of output held back waiting for the end of its line.
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// Output of a process cut at line ends, so that a log message never ends halfway through a
+/// line, whatever the size of the chunks the output is read in.
+#[derive(Debug, Default)]
+pub(crate) struct LineBuffer {
+    /// Output after the last newline.
+    partial: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Append a chunk of output, returning the lines it completes with their newlines.
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Option<String> {
+        self.partial.extend_from_slice(chunk);
+        let end = match self.partial.iter().rposition(|byte| *byte == b'\n') {
+            Some(newline) => newline + 1,
+            // Output that never ends a line, such as a binary dump, is passed on in pieces.
+            None if self.partial.len() >= MAX_LINE_LENGTH => self.partial.len(),
+            None => return None,
+        };
+        let rest = self.partial.split_off(end);
+        let lines = std::mem::replace(&mut self.partial, rest);
+        Some(String::from_utf8_lossy(&lines).into_owned())
+    }
+
+    /// Take the unterminated rest of the output, if there is any.
+    pub(crate) fn flush(&mut self) -> Option<String> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        let partial = std::mem::take(&mut self.partial);
+        Some(String::from_utf8_lossy(&partial).into_owned())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.partial.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_buffer() {
+        let mut lines = LineBuffer::default();
+        assert_eq!(lines.push(b"{\"level\":\"info\","), None);
+        assert_eq!(
+            lines.push(b"\"msg\":\"ready\"}\nGET /us").as_deref(),
+            Some("{\"level\":\"info\",\"msg\":\"ready\"}\n")
+        );
+        assert_eq!(
+            lines.push(b"ers\nGET /health\n").as_deref(),
+            Some("GET /users\nGET /health\n")
+        );
+        assert!(lines.is_empty());
+
+        // A character split between two chunks.
+        assert_eq!(lines.push(&"é\n".as_bytes()[..1]), None);
+        assert_eq!(lines.push(&"é\n".as_bytes()[1..]).as_deref(), Some("é\n"));
+
+        assert_eq!(lines.push(b"> "), None);
+        assert_eq!(lines.flush().as_deref(), Some("> "));
+        assert_eq!(lines.flush(), None);
+
+        let long = vec![b'x'; MAX_LINE_LENGTH];
+        assert_eq!(
+            lines.push(&long).map(|text| text.len()),
+            Some(MAX_LINE_LENGTH)
+        );
+    }
+}
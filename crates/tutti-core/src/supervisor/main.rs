@@ -1,5 +1,7 @@
-use tokio::sync::mpsc;
-use tutti_types::{Project, ProjectId};
+use std::collections::HashMap;
+
+use tokio::sync::{mpsc, oneshot};
+use tutti_types::{Project, ProjectId, Service, ServiceStats, ServiceStatus, WindowSize};
 
 use crate::{
     error::{Error, Result},
@@ -50,24 +52,215 @@ impl Supervisor {
         Ok(())
     }
 
-    /// Shutdown the supervisor.
+    /// Stop every project, returning once their services are stopped.
     ///
     /// # Errors
     /// Returns an error if the supervisor fails to shutdown.
     pub async fn shutdown(&mut self) -> Result<()> {
+        let (response, result) = oneshot::channel();
+        self.commands_tx
+            .send(SupervisorCommand::Shutdown { response })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        result
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?
+    }
+
+    /// Set the terminal size of the services of `project_id` that run on a pseudo-terminal,
+    /// including the ones started later.
+    ///
+    /// # Errors
+    /// Returns an error if the supervisor is not running.
+    pub async fn resize(&mut self, project_id: ProjectId, size: WindowSize) -> Result<()> {
         self.commands_tx
-            .send(SupervisorCommand::Shutdown)
+            .send(SupervisorCommand::Resize { project_id, size })
             .await
             .map_err(|err| Error::Internal(err.to_string()))?;
 
         Ok(())
     }
 
+    /// Send `data` to the stdin of a running service of `project_id`.
+    ///
+    /// # Errors
+    /// Returns an error if the service is not running or does not keep its stdin open.
+    pub async fn input(
+        &mut self,
+        project_id: ProjectId,
+        service: String,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        let (response, result) = oneshot::channel();
+        self.commands_tx
+            .send(SupervisorCommand::Input {
+                project_id,
+                service,
+                data,
+                response,
+            })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        result
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?
+    }
+
+    /// Run `cmd` with the directory, environment and process settings of `service`, whose
+    /// configuration is `config`. Its output and exit code are reported as
+    /// [`SupervisorEvent::ExecOutput`] and [`SupervisorEvent::ExecExited`] events with
+    /// `exec_id`, which the caller chooses so it can watch for them before the command starts.
+    ///
+    /// # Errors
+    /// Returns an error if the command cannot be started.
+    pub async fn exec(
+        &mut self,
+        exec_id: u32,
+        project_id: ProjectId,
+        service: String,
+        config: Service,
+        cmd: Vec<String>,
+        env: HashMap<String, String>,
+    ) -> Result<()> {
+        let (response, result) = oneshot::channel();
+        self.commands_tx
+            .send(SupervisorCommand::Exec {
+                exec_id,
+                project_id,
+                service,
+                config: Box::new(config),
+                cmd,
+                env,
+                response,
+            })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        result
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?
+    }
+
+    /// Interrupt a command started with [`Supervisor::exec`].
+    ///
+    /// # Errors
+    /// Returns an error if the supervisor is not running.
+    pub async fn exec_kill(&mut self, exec_id: u32) -> Result<()> {
+        self.commands_tx
+            .send(SupervisorCommand::ExecKill { exec_id })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Stop one service of `project_id`, leaving the services that depend on it running.
+    ///
+    /// # Errors
+    /// Returns an error if the service is not running.
+    pub async fn stop_service(&mut self, project_id: ProjectId, service: String) -> Result<()> {
+        let (response, result) = oneshot::channel();
+        self.commands_tx
+            .send(SupervisorCommand::StopService {
+                project_id,
+                service,
+                response,
+            })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        result
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?
+    }
+
+    /// Restart one service of `project_id`, or start it if it is not running.
+    ///
+    /// # Errors
+    /// Returns an error if the project or the service is unknown.
+    pub async fn restart_service(&mut self, project_id: ProjectId, service: String) -> Result<()> {
+        let (response, result) = oneshot::channel();
+        self.commands_tx
+            .send(SupervisorCommand::RestartService {
+                project_id,
+                service,
+                response,
+            })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        result
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?
+    }
+
+    /// State, pid, uptime and restart count of every service of `project_id`.
+    ///
+    /// # Errors
+    /// Returns an error if the project was never started.
+    pub async fn status(&mut self, project_id: ProjectId) -> Result<Vec<ServiceStatus>> {
+        let (response, result) = oneshot::channel();
+        self.commands_tx
+            .send(SupervisorCommand::Status {
+                project_id,
+                response,
+            })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        result
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?
+    }
+
+    /// Resource usage of the running services of `project_id`, or of every project if `None`.
+    ///
+    /// # Errors
+    /// Returns an error if the supervisor is not running.
+    pub async fn stats(&mut self, project_id: Option<ProjectId>) -> Result<Vec<ServiceStats>> {
+        let (response, stats) = oneshot::channel();
+        self.commands_tx
+            .send(SupervisorCommand::Stats {
+                project_id,
+                response,
+            })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        stats.await.map_err(|err| Error::Internal(err.to_string()))
+    }
+
+    /// Projects that have services running.
+    ///
+    /// # Errors
+    /// Returns an error if the supervisor is not running.
+    pub async fn projects(&mut self) -> Result<Vec<ProjectId>> {
+        let (response, projects) = oneshot::channel();
+        self.commands_tx
+            .send(SupervisorCommand::Projects { response })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?;
+
+        projects
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))
+    }
+
     /// Start the supervisor.
     ///
+    /// `env` is the environment of the client starting the project; services inherit it
+    /// according to their `inherit_env` setting.
+    ///
     /// # Errors
     /// Returns an error if the supervisor fails to start.
-    pub async fn up(&mut self, project: Project, services: Vec<String>) -> Result<()> {
+    pub async fn up(
+        &mut self,
+        project: Project,
+        services: Vec<String>,
+        env: HashMap<String, String>,
+    ) -> Result<()> {
         tracing::trace!(
             "Received up command for project {project:?} to start services {services:?}"
         );
@@ -78,6 +271,7 @@ impl Supervisor {
             .send(SupervisorCommand::UpdateConfig {
                 project_id: project_id.clone(),
                 config: project,
+                env,
             })
             .await
             .map_err(|err| Error::Internal(err.to_string()))?;
@@ -1,5 +1,9 @@
 mod background;
 mod commands;
+mod exec;
+mod hooks;
+mod limits;
+mod lines;
 mod main;
 
 pub use commands::{SupervisorCommand, SupervisorEvent, UpResponse};
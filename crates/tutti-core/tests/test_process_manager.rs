@@ -1,6 +1,8 @@
 use futures::StreamExt;
 use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 use tutti_core::{CommandSpec, ProcessManager, UnixProcessManager};
+use tutti_types::{Rlimit, RlimitValue, WindowSize};
 
 #[tokio::test]
 #[cfg(unix)]
@@ -13,6 +15,7 @@ async fn test_process_manager_stdout() {
             cmd: vec!["bash".to_owned(), "./stdout.sh".to_owned()],
             cwd: Some("./tests/fixtures/".parse().unwrap()),
             env: vec![],
+            ..CommandSpec::default()
         })
         .await
         .unwrap();
@@ -51,6 +54,7 @@ async fn test_process_manager_stderr() {
             cmd: vec!["bash".to_owned(), "./stderr.sh".to_owned()],
             cwd: Some("./tests/fixtures/".parse().unwrap()),
             env: vec![],
+            ..CommandSpec::default()
         })
         .await
         .unwrap();
@@ -90,6 +94,7 @@ async fn test_process_manager_both() {
             cmd: vec!["bash".to_owned(), "./both.sh".to_owned()],
             cwd: Some("./tests/fixtures/".parse().unwrap()),
             env: vec![],
+            ..CommandSpec::default()
         })
         .await
         .unwrap();
@@ -131,13 +136,15 @@ async fn test_process_manager_sigint() {
             cmd: vec!["bash".to_owned(), "./infinite_sigint_kills.sh".to_owned()],
             cwd: Some("./tests/fixtures/".parse().unwrap()),
             env: vec![],
+            ..CommandSpec::default()
         })
         .await
         .unwrap();
 
     pm.shutdown(out.id).await.unwrap();
     let result = pm.wait(out.id, Duration::from_millis(100)).await.unwrap();
-    assert_eq!(result, Some(0));
+    // 128 + SIGINT
+    assert_eq!(result, Some(130));
 }
 
 #[tokio::test]
@@ -154,6 +161,7 @@ async fn test_process_manager_sigkill() {
             ],
             cwd: Some("./tests/fixtures/".parse().unwrap()),
             env: vec![],
+            ..CommandSpec::default()
         })
         .await
         .unwrap();
@@ -161,7 +169,7 @@ async fn test_process_manager_sigkill() {
     // Wait for the signal handler to be installed
     let mut stdout = out.stdout;
     let mut lines_read = 0;
-    while let Some(_) = stdout.next().await {
+    while stdout.next().await.is_some() {
         lines_read += 1;
         if lines_read == 3 {
             break;
@@ -169,9 +177,176 @@ async fn test_process_manager_sigkill() {
     }
 
     pm.shutdown(out.id).await.unwrap();
-    let result = pm.wait(out.id, Duration::from_millis(1000)).await.unwrap();
+    let result = pm.wait(out.id, Duration::from_secs(1)).await.unwrap();
     assert_eq!(result, None);
     pm.kill(out.id).await.unwrap();
     let result = pm.wait(out.id, Duration::from_millis(100)).await.unwrap();
-    assert_eq!(result, Some(0));
+    // 128 + SIGKILL
+    assert_eq!(result, Some(137));
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn test_process_manager_env_is_not_inherited() {
+    let mut pm = UnixProcessManager::new();
+
+    let out = pm
+        .spawn(CommandSpec {
+            name: "env".to_owned(),
+            cmd: vec!["/usr/bin/env".to_owned()],
+            cwd: None,
+            env: vec![("TUTTI_TEST".to_owned(), "1".to_owned())],
+            ..CommandSpec::default()
+        })
+        .await
+        .unwrap();
+
+    let mut stdout = out.stdout;
+    let mut actual_stdout = String::new();
+    while let Some(line) = stdout.next().await {
+        actual_stdout.push_str(&String::from_utf8_lossy(&line));
+    }
+    assert_eq!(actual_stdout, "TUTTI_TEST=1\n");
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn test_process_manager_process_settings() {
+    let mut pm = UnixProcessManager::new();
+
+    let out = pm
+        .spawn(CommandSpec {
+            name: "settings".to_owned(),
+            cmd: vec![
+                "/bin/sh".to_owned(),
+                "-c".to_owned(),
+                "umask; ulimit -n; ulimit -c".to_owned(),
+            ],
+            umask: Some(0o027),
+            rlimits: vec![
+                (Rlimit::Nofile, RlimitValue::Limited(64)),
+                (Rlimit::Core, RlimitValue::Limited(0)),
+            ],
+            ..CommandSpec::default()
+        })
+        .await
+        .unwrap();
+
+    let mut stdout = out.stdout;
+    let mut actual_stdout = String::new();
+    while let Some(line) = stdout.next().await {
+        actual_stdout.push_str(&String::from_utf8_lossy(&line));
+    }
+    assert_eq!(actual_stdout, "0027\n64\n0\n");
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn test_process_manager_spawn_error() {
+    let mut pm = UnixProcessManager::new();
+
+    let err = pm
+        .spawn(CommandSpec {
+            name: "missing".to_owned(),
+            cmd: vec!["tutti-no-such-program".to_owned()],
+            ..CommandSpec::default()
+        })
+        .await
+        .err()
+        .unwrap();
+    assert!(err
+        .to_string()
+        .starts_with("cannot start `tutti-no-such-program`: "));
+
+    let err = pm
+        .spawn(CommandSpec {
+            name: "user".to_owned(),
+            cmd: vec!["/bin/true".to_owned()],
+            user: Some("tutti-no-such-user".to_owned()),
+            ..CommandSpec::default()
+        })
+        .await
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("tutti-no-such-user"));
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn test_process_manager_tty() {
+    let mut pm = UnixProcessManager::new();
+
+    let out = pm
+        .spawn(CommandSpec {
+            name: "tty".to_owned(),
+            cmd: vec![
+                "/bin/sh".to_owned(),
+                "-c".to_owned(),
+                "test -t 0 && test -t 1 && echo tty; stty size; echo err >&2".to_owned(),
+            ],
+            tty: true,
+            window_size: Some(WindowSize {
+                cols: 120,
+                rows: 40,
+            }),
+            ..CommandSpec::default()
+        })
+        .await
+        .unwrap();
+
+    let mut stdout = out.stdout;
+    let mut actual_stdout = String::new();
+    while let Some(line) = stdout.next().await {
+        actual_stdout.push_str(&String::from_utf8_lossy(&line));
+    }
+    assert_eq!(actual_stdout, "tty\n40 120\nerr\n");
+
+    let mut stderr = out.stderr;
+    assert!(stderr.next().await.is_none());
+}
+
+#[tokio::test]
+#[cfg(unix)]
+async fn test_process_manager_stdin() {
+    let mut pm = UnixProcessManager::new();
+
+    for tty in [false, true] {
+        let out = pm
+            .spawn(CommandSpec {
+                name: "stdin".to_owned(),
+                cmd: vec![
+                    "/bin/sh".to_owned(),
+                    "-c".to_owned(),
+                    "read line; echo \"<$line>\"".to_owned(),
+                ],
+                tty,
+                stdin: true,
+                ..CommandSpec::default()
+            })
+            .await
+            .unwrap();
+
+        let mut stdin = out.stdin.unwrap();
+        stdin.write_all(b"hello\n").await.unwrap();
+        stdin.flush().await.unwrap();
+
+        let mut stdout = out.stdout;
+        let mut actual_stdout = String::new();
+        while let Some(line) = stdout.next().await {
+            actual_stdout.push_str(&String::from_utf8_lossy(&line));
+        }
+        // The terminal echoes the input.
+        let expected = if tty { "hello\n<hello>\n" } else { "<hello>\n" };
+        assert_eq!(actual_stdout, expected);
+    }
+
+    let out = pm
+        .spawn(CommandSpec {
+            name: "no-stdin".to_owned(),
+            cmd: vec!["/bin/true".to_owned()],
+            ..CommandSpec::default()
+        })
+        .await
+        .unwrap();
+    assert!(out.stdin.is_none());
 }
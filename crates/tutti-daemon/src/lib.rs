@@ -1,20 +1,44 @@
-use std::{path::PathBuf, process, sync::Arc};
+use std::{
+    fs::OpenOptions,
+    os::unix::{net::UnixStream, process::CommandExt},
+    path::PathBuf,
+    process::{self, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use futures_util::FutureExt;
-use tokio::sync::{mpsc::Receiver, Mutex};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{mpsc::Receiver, Mutex},
+};
 use tutti_core::{Supervisor, SupervisorEvent, UnixProcessManager};
 use tutti_transport::{
     api::TuttiApi,
     error::{TransportError, TransportResult},
     server::ipc_server::IpcServer,
 };
+use tutti_types::{DaemonStatus, ProjectId};
+
+use crate::lock::DaemonLock;
+
+mod lock;
 
 pub const SOCKET_FILE: &str = "tutti.sock";
+pub const LOCK_FILE: &str = "tutti.lock";
+pub const PID_FILE: &str = "tutti.pid";
+/// Where the output of a daemon started with [`DaemonRunner::spawn`] goes.
+pub const LOG_FILE: &str = "daemon.log";
+
+/// How long [`DaemonRunner::spawn`] waits for the daemon to accept connections.
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(5);
+const SPAWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 #[derive(Debug, Clone)]
 struct Context {
     supervisor: Arc<Mutex<Supervisor>>,
     receiver: Arc<Mutex<Receiver<SupervisorEvent>>>,
+    started_at: Instant,
 }
 
 impl Context {
@@ -25,35 +49,146 @@ impl Context {
         Context {
             supervisor,
             receiver,
+            started_at: Instant::now(),
         }
     }
 }
 
+/// Answer a request the supervisor may reject: `Pong`, or `Error` with the reason.
+fn acknowledge<E: std::fmt::Display>(project_id: ProjectId, result: Result<(), E>) -> TuttiApi {
+    match result {
+        Ok(()) => TuttiApi::Pong,
+        Err(err) => TuttiApi::Error {
+            project_id,
+            message: err.to_string(),
+        },
+    }
+}
+
+#[allow(clippy::too_many_lines)]
 async fn unary_handler(message: TuttiApi, context: Context) -> TransportResult<TuttiApi> {
     match message {
         TuttiApi::Ping => Ok(TuttiApi::Pong),
-        TuttiApi::Up { project, services } => {
+        TuttiApi::Up {
+            project,
+            services,
+            env,
+        } => {
             tracing::info!("Starting project {project:?} with services {services:?}");
 
+            let project_id = project.id.clone();
+            let mut guard = context.supervisor.lock().await;
+            let result = guard.up(project, services, env).await;
+
+            Ok(acknowledge(project_id, result))
+        }
+        TuttiApi::Down { project_id } => {
+            tracing::info!("Stopping project {project_id:?}");
+
             let mut guard = context.supervisor.lock().await;
             guard
-                .up(project, services)
+                .down(project_id)
                 .await
                 .map_err(|_| TransportError::UnknownMessage)?;
 
             Ok(TuttiApi::Pong)
         }
-        TuttiApi::Down { project_id } => {
-            tracing::info!("Stopping project {project_id:?}");
+        TuttiApi::Resize { project_id, size } => {
+            let mut guard = context.supervisor.lock().await;
+            guard
+                .resize(project_id, size)
+                .await
+                .map_err(|_| TransportError::UnknownMessage)?;
 
+            Ok(TuttiApi::Pong)
+        }
+        TuttiApi::Input {
+            project_id,
+            service,
+            data,
+        } => {
+            let mut guard = context.supervisor.lock().await;
+            let result = guard.input(project_id.clone(), service, data).await;
+            Ok(acknowledge(project_id, result))
+        }
+        TuttiApi::Exec {
+            exec_id,
+            project_id,
+            service,
+            config,
+            cmd,
+            env,
+        } => {
+            let mut guard = context.supervisor.lock().await;
+            let result = guard
+                .exec(exec_id, project_id.clone(), service, *config, cmd, env)
+                .await;
+            Ok(acknowledge(project_id, result))
+        }
+        TuttiApi::ExecKill { exec_id } => {
             let mut guard = context.supervisor.lock().await;
             guard
-                .down(project_id)
+                .exec_kill(exec_id)
                 .await
                 .map_err(|_| TransportError::UnknownMessage)?;
 
             Ok(TuttiApi::Pong)
         }
+        TuttiApi::StopService {
+            project_id,
+            service,
+        } => {
+            tracing::info!("Stopping service {service:?} of {project_id:?}");
+
+            let mut guard = context.supervisor.lock().await;
+            let result = guard.stop_service(project_id.clone(), service).await;
+            Ok(acknowledge(project_id, result))
+        }
+        TuttiApi::RestartService {
+            project_id,
+            service,
+        } => {
+            tracing::info!("Restarting service {service:?} of {project_id:?}");
+
+            let mut guard = context.supervisor.lock().await;
+            let result = guard.restart_service(project_id.clone(), service).await;
+            Ok(acknowledge(project_id, result))
+        }
+        TuttiApi::Status { project_id } => {
+            let mut guard = context.supervisor.lock().await;
+            match guard.status(project_id.clone()).await {
+                Ok(services) => Ok(TuttiApi::StatusReport { services }),
+                Err(err) => Ok(TuttiApi::Error {
+                    project_id,
+                    message: err.to_string(),
+                }),
+            }
+        }
+        TuttiApi::Stats { project_id } => {
+            let mut guard = context.supervisor.lock().await;
+            let services = guard
+                .stats(project_id)
+                .await
+                .map_err(|_| TransportError::UnknownMessage)?;
+
+            Ok(TuttiApi::StatsReport { services })
+        }
+        TuttiApi::DaemonStatus => {
+            let mut guard = context.supervisor.lock().await;
+            let projects = guard
+                .projects()
+                .await
+                .map_err(|_| TransportError::UnknownMessage)?;
+
+            Ok(TuttiApi::DaemonStatusReport {
+                status: DaemonStatus {
+                    pid: process::id(),
+                    version: env!("CARGO_PKG_VERSION").to_owned(),
+                    uptime: context.started_at.elapsed().as_secs(),
+                    projects,
+                },
+            })
+        }
         TuttiApi::Shutdown => {
             tracing::info!("Stopping supervisor");
 
@@ -90,10 +225,12 @@ async fn stream_handler(context: Context) -> TransportResult<TuttiApi> {
             project_id,
             service,
             message,
+            stderr,
         } => Ok(TuttiApi::Log {
             project_id,
             service,
             message,
+            stderr,
         }),
         SupervisorEvent::ProjectStopped { project_id } => {
             Ok(TuttiApi::ProjectStopped { project_id })
@@ -112,6 +249,43 @@ async fn stream_handler(context: Context) -> TransportResult<TuttiApi> {
             project_id,
             service,
         }),
+        SupervisorEvent::ServiceExited {
+            project_id,
+            service,
+            code,
+        } => Ok(TuttiApi::ServiceExited {
+            project_id,
+            service,
+            code,
+        }),
+        SupervisorEvent::ServiceBlocked {
+            project_id,
+            service,
+            reason,
+        } => Ok(TuttiApi::ServiceBlocked {
+            project_id,
+            service,
+            reason,
+        }),
+        SupervisorEvent::LimitExceeded {
+            project_id,
+            service,
+            reason,
+        } => Ok(TuttiApi::LimitExceeded {
+            project_id,
+            service,
+            reason,
+        }),
+        SupervisorEvent::ExecOutput {
+            exec_id,
+            data,
+            stderr,
+        } => Ok(TuttiApi::ExecOutput {
+            exec_id,
+            data,
+            stderr,
+        }),
+        SupervisorEvent::ExecExited { exec_id, code } => Ok(TuttiApi::ExecExited { exec_id, code }),
         SupervisorEvent::Error {
             project_id,
             message,
@@ -148,75 +322,129 @@ impl DaemonRunner {
         Ok(())
     }
 
-    /// Clear the system directory.
-    ///
-    /// # Errors
-    /// Returns an error if the system directory cannot be cleared.
-    pub fn clear(&self) -> Result<(), String> {
-        if std::fs::exists(&self.system)
-            .map_err(|err| format!("Cannot clear system directory: {err:?}"))?
-        {
-            std::fs::remove_dir_all(&self.system)
-                .map_err(|err| format!("Cannot remove system directory: {err:?}"))?;
-        }
-
-        Ok(())
-    }
-
     /// Get the socket path.
     #[must_use]
     pub fn socket_path(&self) -> PathBuf {
         self.system.join(SOCKET_FILE)
     }
 
-    /// Spawn the daemon process.
+    /// Get the path of the daemon log.
+    #[must_use]
+    pub fn log_path(&self) -> PathBuf {
+        self.system.join(LOG_FILE)
+    }
+
+    /// Start the daemon in the background: the current executable is run with
+    /// `daemon --system-directory <dir> run` in a session of its own, with its output going to
+    /// the daemon log. Returns once the daemon accepts connections.
     ///
     /// # Errors
-    /// Returns an error if the daemon process cannot be spawned.
+    /// Returns an error if the daemon process cannot be spawned, or exits or does not accept
+    /// connections in time.
     pub fn spawn(&self) -> Result<(), String> {
-        std::process::Command::new("tutti-cli")
+        let exe = std::env::current_exe()
+            .map_err(|err| format!("Cannot find the tutti executable: {err:?}"))?;
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_path())
+            .map_err(|err| format!("Cannot open daemon log: {err:?}"))?;
+        let log_err = log
+            .try_clone()
+            .map_err(|err| format!("Cannot open daemon log: {err:?}"))?;
+
+        let mut command = process::Command::new(exe);
+        command
             .arg("daemon")
+            .arg("--system-directory")
+            .arg(&self.system)
             .arg("run")
             .env("RUST_LOG", "ERROR")
+            .stdin(Stdio::null())
+            .stdout(log)
+            .stderr(log_err);
+        // Detach from the terminal, so that the daemon survives it and gets none of its signals.
+        #[allow(unsafe_code)]
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let mut child = command
             .spawn()
             .map_err(|err| format!("Cannot spawn daemon process: {err:?}"))?;
 
-        for _ in 0..10 {
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            if self.socket_path().exists() {
+        let deadline = Instant::now() + SPAWN_TIMEOUT;
+        loop {
+            // Another client may have started a daemon at the same time, which is as good.
+            if UnixStream::connect(self.socket_path()).is_ok() {
                 return Ok(());
             }
+            if let Ok(Some(status)) = child.try_wait() {
+                return Err(format!(
+                    "Daemon process exited with {status}, see {}",
+                    self.log_path().display()
+                ));
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "Timeout waiting for daemon process to start, see {}",
+                    self.log_path().display()
+                ));
+            }
+            std::thread::sleep(SPAWN_POLL_INTERVAL);
         }
-
-        Err("Timeout waiting for daemon process to start".to_string())
     }
 
-    /// Start the daemon process.
+    /// Run the daemon until it gets `Shutdown`, SIGTERM or SIGINT, holding the lock on the
+    /// system directory. The services of every project are stopped before it returns.
     ///
     /// # Errors
-    /// Returns an error if the daemon process cannot be started.
+    /// Returns an error if another daemon runs on the system directory or the daemon process
+    /// cannot be started.
     #[tracing::instrument(skip_all)]
     pub async fn start(&self) -> Result<(), String> {
         tracing::info!("Starting daemon process...");
+        let lock = DaemonLock::acquire(&self.system.join(LOCK_FILE), &self.system.join(PID_FILE))?;
+        // Only a daemon that did not exit cleanly leaves its socket behind.
+        let _ = std::fs::remove_file(self.socket_path());
+
+        let mut terminate = signal(SignalKind::terminate())
+            .map_err(|err| format!("Cannot listen for signals: {err:?}"))?;
+        let mut interrupt = signal(SignalKind::interrupt())
+            .map_err(|err| format!("Cannot listen for signals: {err:?}"))?;
+
         let (supervisor, receiver) = Supervisor::new(UnixProcessManager::new());
         tracing::debug!("Supervisor created");
+        let supervisor = Arc::new(Mutex::new(supervisor));
 
         let unary_handler =
             Arc::new(|api: TuttiApi, context: Context| unary_handler(api, context).boxed());
         let stream_handler = Arc::new(|context: Context| stream_handler(context).boxed());
 
-        IpcServer::<Context>::new(
-            self.system.join(SOCKET_FILE),
-            Context::new(
-                Arc::new(Mutex::new(supervisor)),
-                Arc::new(Mutex::new(receiver)),
-            ),
+        let server = IpcServer::<Context>::new(
+            self.socket_path(),
+            Context::new(supervisor.clone(), Arc::new(Mutex::new(receiver))),
         )
         .map_err(|err| format!("Cannot start IPC Server: {err:?}"))?
         .add_unary_handler(unary_handler)
-        .add_stream_handler(stream_handler)
-        .start()
-        .await;
+        .add_stream_handler(stream_handler);
+
+        tokio::select! {
+            () = server.start() => {}
+            _ = terminate.recv() => tracing::info!("Received SIGTERM"),
+            _ = interrupt.recv() => tracing::info!("Received SIGINT"),
+        }
+
+        // Already done if the daemon was asked to shut down.
+        if let Err(err) = supervisor.lock().await.shutdown().await {
+            tracing::error!("Failed to stop the services: {err:?}");
+        }
+        let _ = std::fs::remove_file(self.socket_path());
+        drop(lock);
 
         Ok(())
     }
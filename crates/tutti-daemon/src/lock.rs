@@ -0,0 +1,85 @@
+use std::{
+    fs::{File, OpenOptions},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Exclusive lock on a system directory, held by the daemon for as long as it runs so that a
+/// second one cannot start on the same socket. Also writes the pidfile, which is removed again
+/// when the lock is dropped.
+#[derive(Debug)]
+pub struct DaemonLock {
+    _file: File,
+    pidfile: PathBuf,
+}
+
+impl DaemonLock {
+    /// Take the lock at `lockfile` and write our pid to `pidfile`.
+    ///
+    /// # Errors
+    /// Returns an error if another daemon holds the lock, or if the files cannot be written.
+    pub fn acquire(lockfile: &Path, pidfile: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lockfile)
+            .map_err(|err| format!("Cannot open lock file: {err:?}"))?;
+
+        // The kernel releases the lock when the file is closed, also if the daemon crashes.
+        #[allow(unsafe_code)]
+        let rc = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if rc != 0 {
+            return Err(match read_pid(pidfile) {
+                Some(pid) => format!("Another daemon is already running with pid {pid}"),
+                None => "Another daemon is already running".to_string(),
+            });
+        }
+
+        std::fs::write(pidfile, format!("{}\n", process::id()))
+            .map_err(|err| format!("Cannot write pidfile: {err:?}"))?;
+
+        Ok(Self {
+            _file: file,
+            pidfile: pidfile.to_path_buf(),
+        })
+    }
+}
+
+impl Drop for DaemonLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.pidfile);
+    }
+}
+
+/// Pid written to `pidfile`, if there is one.
+#[must_use]
+pub fn read_pid(pidfile: &Path) -> Option<u32> {
+    std::fs::read_to_string(pidfile).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock() {
+        let dir = std::env::temp_dir().join(format!("tutti-lock-{}", process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let lockfile = dir.join("tutti.lock");
+        let pidfile = dir.join("tutti.pid");
+
+        let lock = DaemonLock::acquire(&lockfile, &pidfile).unwrap();
+        assert_eq!(read_pid(&pidfile), Some(process::id()));
+
+        let err = DaemonLock::acquire(&lockfile, &pidfile).unwrap_err();
+        assert!(err.contains(&process::id().to_string()));
+
+        drop(lock);
+        assert_eq!(read_pid(&pidfile), None);
+        assert!(DaemonLock::acquire(&lockfile, &pidfile).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
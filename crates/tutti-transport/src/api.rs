@@ -1,5 +1,11 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
-use tutti_types::{Project, ProjectId};
+use tutti_types::{
+    DaemonStatus, Project, ProjectId, Service, ServiceStats, ServiceStatus, WindowSize,
+};
+
+use crate::filter::LogFilter;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TuttiMessage {
@@ -23,16 +29,90 @@ pub enum TuttiApi {
         project_id: ProjectId,
         service: String,
         message: String,
+        #[serde(default)]
+        stderr: bool,
     },
     Up {
         project: Project,
         services: Vec<String>,
+        /// Environment of the client, forwarded to the services.
+        #[serde(default)]
+        env: HashMap<String, String>,
     },
     Down {
         project_id: ProjectId,
     },
+    /// The client's terminal was resized; passed on to services running with `tty`.
+    Resize {
+        project_id: ProjectId,
+        size: WindowSize,
+    },
+    /// Write to the stdin of a running service. Answered with `Pong`, or `Error` if the service
+    /// cannot take input.
+    Input {
+        project_id: ProjectId,
+        service: String,
+        data: Vec<u8>,
+    },
+    /// Run a one-off command with the settings of `service`, configured as `config`. Answered
+    /// with `Pong`, or `Error` if it cannot be started. The output and the exit code follow as
+    /// `ExecOutput` and `ExecExited` stream messages with the `exec_id` chosen by the client.
+    Exec {
+        exec_id: u32,
+        project_id: ProjectId,
+        service: String,
+        config: Box<Service>,
+        cmd: Vec<String>,
+        /// Environment of the client, inherited according to the service's `inherit_env`.
+        #[serde(default)]
+        env: HashMap<String, String>,
+    },
+    /// Interrupt a command started with [`TuttiApi::Exec`].
+    ExecKill {
+        exec_id: u32,
+    },
     Shutdown,
     Subscribe,
+    /// Only pass the `Log` stream messages matching `filter` on to this connection. Handled by
+    /// the server itself and answered with `Pong`, or `Error` if a pattern is invalid.
+    FilterLogs {
+        filter: LogFilter,
+    },
+    /// Request the resource usage of the services of a project, or of every project if `None`.
+    Stats {
+        project_id: Option<ProjectId>,
+    },
+    /// Response to [`TuttiApi::Stats`].
+    StatsReport {
+        services: Vec<ServiceStats>,
+    },
+    /// Stop one service of a project. Answered with `Pong`, or `Error` if it is not running.
+    StopService {
+        project_id: ProjectId,
+        service: String,
+    },
+    /// Restart one service of a project, or start it if it is not running. Answered with
+    /// `Pong`, or `Error` if the daemon does not know the service.
+    RestartService {
+        project_id: ProjectId,
+        service: String,
+    },
+    /// Request the status of every service of a project. Answered with `StatusReport`, or
+    /// `Error` if the project was never started.
+    Status {
+        project_id: ProjectId,
+    },
+    /// Response to [`TuttiApi::Status`].
+    StatusReport {
+        services: Vec<ServiceStatus>,
+    },
+    /// Request the pid, version, uptime and projects of the daemon. Answered with
+    /// `DaemonStatusReport`.
+    DaemonStatus,
+    /// Response to [`TuttiApi::DaemonStatus`].
+    DaemonStatusReport {
+        status: DaemonStatus,
+    },
     ProjectStopped {
         project_id: ProjectId,
     },
@@ -44,6 +124,33 @@ pub enum TuttiApi {
         project_id: ProjectId,
         service: String,
     },
+    /// The process of a service exited on its own, with its exit code if known. Processes
+    /// killed by a signal have 128 plus the signal. Followed by `ServiceStopped` or
+    /// `ServiceRestarted`.
+    ServiceExited {
+        project_id: ProjectId,
+        service: String,
+        code: Option<i32>,
+    },
+    ServiceBlocked {
+        project_id: ProjectId,
+        service: String,
+        reason: String,
+    },
+    LimitExceeded {
+        project_id: ProjectId,
+        service: String,
+        reason: String,
+    },
+    ExecOutput {
+        exec_id: u32,
+        data: Vec<u8>,
+        stderr: bool,
+    },
+    ExecExited {
+        exec_id: u32,
+        code: i32,
+    },
     Error {
         project_id: ProjectId,
         message: String,
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use futures_util::StreamExt;
 use tokio::{
@@ -7,12 +7,15 @@ use tokio::{
     task::JoinHandle,
 };
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
-use tutti_types::{Project, ProjectId};
+use tutti_types::{
+    DaemonStatus, Project, ProjectId, Service, ServiceStats, ServiceStatus, WindowSize,
+};
 
 use crate::{
     api::{MessageType, TuttiApi, TuttiMessage},
     client::worker::IpcClientWorker,
     error::{TransportError, TransportResult},
+    filter::LogFilter,
 };
 
 const BUFFER_SIZE: usize = 100;
@@ -52,8 +55,8 @@ impl IpcClient {
         let task = tokio::spawn(async move {
             let framed = Framed::new(socket, LengthDelimitedCodec::new());
             let (sink, stream) = framed.split();
-            if IpcClientWorker::new(sink, stream, rx).run().await.is_err() {
-                todo!()
+            if let Err(err) = IpcClientWorker::new(sink, stream, rx).run().await {
+                tracing::error!("IPC client worker failed: {err:?}");
             }
         });
 
@@ -110,16 +113,31 @@ impl IpcClient {
         self.send(TuttiApi::Ping).await.is_ok()
     }
 
-    /// Start a project with the given services.
+    /// Start a project with the given services, passing the client environment along.
     ///
     /// # Errors
-    /// Returns an error if the project cannot be started.
-    pub async fn up(&mut self, project: Project, services: Vec<String>) -> TransportResult<()> {
+    /// Returns [`TransportError::Rejected`] if the project cannot be started, or another error
+    /// if the request fails.
+    pub async fn up(
+        &mut self,
+        project: Project,
+        services: Vec<String>,
+        env: HashMap<String, String>,
+    ) -> TransportResult<()> {
         tracing::debug!("Starting services");
 
-        self.send(TuttiApi::Up { project, services }).await?;
-
-        Ok(())
+        match self
+            .send(TuttiApi::Up {
+                project,
+                services,
+                env,
+            })
+            .await?
+        {
+            TuttiApi::Pong => Ok(()),
+            TuttiApi::Error { message, .. } => Err(TransportError::Rejected(message)),
+            _ => Err(TransportError::UnknownMessage),
+        }
     }
 
     /// Stop a project.
@@ -134,6 +152,167 @@ impl IpcClient {
         Ok(())
     }
 
+    /// Tell the daemon the size of the client's terminal.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails.
+    pub async fn resize(&mut self, project_id: ProjectId, size: WindowSize) -> TransportResult<()> {
+        self.send(TuttiApi::Resize { project_id, size }).await?;
+
+        Ok(())
+    }
+
+    /// Send input to a running service.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::Rejected`] if the service is not running or does not keep its
+    /// stdin open, or another error if the request fails.
+    pub async fn input(
+        &mut self,
+        project_id: ProjectId,
+        service: String,
+        data: Vec<u8>,
+    ) -> TransportResult<()> {
+        match self
+            .send(TuttiApi::Input {
+                project_id,
+                service,
+                data,
+            })
+            .await?
+        {
+            TuttiApi::Pong => Ok(()),
+            TuttiApi::Error { message, .. } => Err(TransportError::Rejected(message)),
+            _ => Err(TransportError::UnknownMessage),
+        }
+    }
+
+    /// Run a one-off command with the settings of a service. Subscribe first to get its
+    /// output and exit code, which are tagged with `exec_id`.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::Rejected`] if the command cannot be started, or another error
+    /// if the request fails.
+    pub async fn exec(
+        &mut self,
+        exec_id: u32,
+        project_id: ProjectId,
+        service: String,
+        config: Service,
+        cmd: Vec<String>,
+        env: HashMap<String, String>,
+    ) -> TransportResult<()> {
+        match self
+            .send(TuttiApi::Exec {
+                exec_id,
+                project_id,
+                service,
+                config: Box::new(config),
+                cmd,
+                env,
+            })
+            .await?
+        {
+            TuttiApi::Pong => Ok(()),
+            TuttiApi::Error { message, .. } => Err(TransportError::Rejected(message)),
+            _ => Err(TransportError::UnknownMessage),
+        }
+    }
+
+    /// Interrupt a command started with [`IpcClient::exec`].
+    ///
+    /// # Errors
+    /// Returns an error if the request fails.
+    pub async fn exec_kill(&mut self, exec_id: u32) -> TransportResult<()> {
+        self.send(TuttiApi::ExecKill { exec_id }).await?;
+
+        Ok(())
+    }
+
+    /// Get the resource usage of the services of a project, or of every project if `None`.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon answers with something else.
+    pub async fn stats(
+        &mut self,
+        project_id: Option<ProjectId>,
+    ) -> TransportResult<Vec<ServiceStats>> {
+        match self.send(TuttiApi::Stats { project_id }).await? {
+            TuttiApi::StatsReport { services } => Ok(services),
+            _ => Err(TransportError::UnknownMessage),
+        }
+    }
+
+    /// Stop one service of a project.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::Rejected`] if the service is not running, or another error if
+    /// the request fails.
+    pub async fn stop_service(
+        &mut self,
+        project_id: ProjectId,
+        service: String,
+    ) -> TransportResult<()> {
+        match self
+            .send(TuttiApi::StopService {
+                project_id,
+                service,
+            })
+            .await?
+        {
+            TuttiApi::Pong => Ok(()),
+            TuttiApi::Error { message, .. } => Err(TransportError::Rejected(message)),
+            _ => Err(TransportError::UnknownMessage),
+        }
+    }
+
+    /// Restart one service of a project, or start it if it is not running.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::Rejected`] if the daemon does not know the service, or another
+    /// error if the request fails.
+    pub async fn restart_service(
+        &mut self,
+        project_id: ProjectId,
+        service: String,
+    ) -> TransportResult<()> {
+        match self
+            .send(TuttiApi::RestartService {
+                project_id,
+                service,
+            })
+            .await?
+        {
+            TuttiApi::Pong => Ok(()),
+            TuttiApi::Error { message, .. } => Err(TransportError::Rejected(message)),
+            _ => Err(TransportError::UnknownMessage),
+        }
+    }
+
+    /// Get the status of every service of a project.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::Rejected`] if the daemon does not know the project, or another
+    /// error if the request fails.
+    pub async fn status(&mut self, project_id: ProjectId) -> TransportResult<Vec<ServiceStatus>> {
+        match self.send(TuttiApi::Status { project_id }).await? {
+            TuttiApi::StatusReport { services } => Ok(services),
+            TuttiApi::Error { message, .. } => Err(TransportError::Rejected(message)),
+            _ => Err(TransportError::UnknownMessage),
+        }
+    }
+
+    /// Get the pid, version, uptime and running projects of the daemon.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails.
+    pub async fn daemon_status(&mut self) -> TransportResult<DaemonStatus> {
+        match self.send(TuttiApi::DaemonStatus).await? {
+            TuttiApi::DaemonStatusReport { status } => Ok(status),
+            _ => Err(TransportError::UnknownMessage),
+        }
+    }
+
     /// Stop a project.
     ///
     /// # Errors
@@ -161,6 +340,19 @@ impl IpcClient {
         Ok(())
     }
 
+    /// Only receive the log lines matching `filter` on this connection's event stream.
+    ///
+    /// # Errors
+    /// Returns [`TransportError::Rejected`] if a pattern of the filter is invalid, or another
+    /// error if the request fails.
+    pub async fn filter_logs(&mut self, filter: LogFilter) -> TransportResult<()> {
+        match self.send(TuttiApi::FilterLogs { filter }).await? {
+            TuttiApi::Pong => Ok(()),
+            TuttiApi::Error { message, .. } => Err(TransportError::Rejected(message)),
+            _ => Err(TransportError::UnknownMessage),
+        }
+    }
+
     /// Subscribe to Tutti events.
     ///
     /// # Errors
@@ -101,17 +101,24 @@ where
         Ok(())
     }
 
-    /// Run the worker.
+    /// Run the worker until the server closes the connection or the client is dropped. Pending
+    /// requests and subscriptions then see their channel closed.
     ///
     /// # Errors
     /// This function runs the worker in an infinite loop, handling incoming messages from the socket and the message channel.
     pub async fn run(&mut self) -> TransportResult<()> {
         loop {
             select! {
-                Some(Ok(msg)) = self.stream.next() => {
+                msg = self.stream.next() => {
+                    let Some(Ok(msg)) = msg else {
+                        return Ok(());
+                    };
                     self.handle_socket_message(msg).await?;
                 }
-                Some((msg, sender)) = self.receiver.recv() => {
+                request = self.receiver.recv() => {
+                    let Some((msg, sender)) = request else {
+                        return Ok(());
+                    };
                     self.handle_mpsc_message(msg, sender).await?;
                 }
             }
@@ -136,7 +143,7 @@ mod tests {
         _server_io: DuplexStream,
     }
 
-    async fn prepare_worker() -> PrepareWorker {
+    fn prepare_worker() -> PrepareWorker {
         let (client_io, server_io) = duplex(64 * 1024);
         let (_, req_rx) = mpsc::channel::<(TuttiMessage, mpsc::Sender<TuttiMessage>)>(8);
 
@@ -153,7 +160,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_worker() {
-        let mut fixture = prepare_worker().await;
+        let mut fixture = prepare_worker();
 
         let (tx, mut rx) = mpsc::channel::<TuttiMessage>(8);
 
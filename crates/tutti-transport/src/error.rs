@@ -6,4 +6,6 @@ pub enum TransportError {
     UnknownMessage,
     SocketError(std::io::Error),
     SendError(String),
+    /// The daemon refused the request, with its reason.
+    Rejected(String),
 }
@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tutti_types::{LogFormat, LogLevel, ProjectId};
+
+use crate::{api::TuttiApi, record::LogRecord};
+
+/// Which log lines of a project a connection wants, sent with [`TuttiApi::FilterLogs`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogFilter {
+    pub project_id: ProjectId,
+    /// Services whose output is shown, or every service if empty.
+    #[serde(default)]
+    pub services: Vec<String>,
+    /// Regular expression a line has to match.
+    #[serde(default)]
+    pub grep: Option<String>,
+    /// Regular expression of lines that are left out.
+    #[serde(default)]
+    pub exclude: Option<String>,
+    /// Lowest level shown, see [`detect_level`].
+    #[serde(default)]
+    pub level: Option<LogLevel>,
+    /// Only show what services write to stderr.
+    #[serde(default)]
+    pub only_stderr: bool,
+    /// Fields, as `(key, value)`, that structured lines must have.
+    #[serde(default)]
+    pub fields: Vec<(String, String)>,
+    /// `log_format` of the services that do not print text, which their lines are parsed with.
+    #[serde(default)]
+    pub formats: HashMap<String, LogFormat>,
+}
+
+impl LogFilter {
+    /// Compile the patterns of the filter.
+    ///
+    /// # Errors
+    /// Returns an error if `grep` or `exclude` is not a valid regular expression.
+    pub fn compile(self) -> Result<LogMatcher, regex::Error> {
+        Ok(LogMatcher {
+            grep: self.grep.as_deref().map(Regex::new).transpose()?,
+            exclude: self.exclude.as_deref().map(Regex::new).transpose()?,
+            project_id: self.project_id,
+            services: self.services,
+            level: self.level,
+            only_stderr: self.only_stderr,
+            fields: self.fields,
+            formats: self.formats,
+            levels: HashMap::new(),
+        })
+    }
+}
+
+/// A compiled [`LogFilter`]. It remembers the level of the last line of every service, which
+/// indented lines such as stack traces inherit.
+#[derive(Debug)]
+pub struct LogMatcher {
+    project_id: ProjectId,
+    services: Vec<String>,
+    grep: Option<Regex>,
+    exclude: Option<Regex>,
+    level: Option<LogLevel>,
+    only_stderr: bool,
+    fields: Vec<(String, String)>,
+    formats: HashMap<String, LogFormat>,
+    levels: HashMap<String, LogLevel>,
+}
+
+impl LogMatcher {
+    /// Drop the lines of a [`TuttiApi::Log`] message that do not match, returning whether
+    /// anything is left to send. The supervisor only sends whole lines, apart from output that
+    /// stalls halfway through one, like a prompt. Other messages are left alone.
+    pub fn apply(&mut self, message: &mut TuttiApi) -> bool {
+        let TuttiApi::Log {
+            project_id,
+            service,
+            message,
+            stderr,
+        } = message
+        else {
+            return true;
+        };
+        if *project_id != self.project_id || (self.only_stderr && !*stderr) {
+            return false;
+        }
+        let base = service
+            .split_once(':')
+            .map_or(service.as_str(), |(base, _)| base);
+        if !self.services.is_empty() && !self.services.iter().any(|name| name == base) {
+            return false;
+        }
+
+        let mut kept = String::new();
+        for line in message.split_inclusive('\n') {
+            let text = line.strip_suffix('\n').unwrap_or(line);
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            if self.matches(service, text, *stderr) {
+                kept.push_str(line);
+            }
+        }
+        if kept.is_empty() {
+            return false;
+        }
+        *message = kept;
+        true
+    }
+
+    fn matches(&mut self, service: &str, line: &str, stderr: bool) -> bool {
+        let format = self.formats.get(service).copied().unwrap_or_default();
+        let record = LogRecord::parse(format, line);
+        if !self.fields.is_empty() {
+            let Some(record) = &record else {
+                return false;
+            };
+            let has_fields = self
+                .fields
+                .iter()
+                .all(|(key, value)| record.field(key).as_ref() == Some(value));
+            if !has_fields {
+                return false;
+            }
+        }
+
+        if let Some(min) = self.level {
+            let detected = match &record {
+                Some(record) => record.level,
+                None => detect_level(line),
+            };
+            let level = match detected {
+                Some(level) => level,
+                None if line.starts_with(char::is_whitespace) => {
+                    self.levels.get(service).copied().unwrap_or(LogLevel::Info)
+                }
+                None if stderr => LogLevel::Error,
+                None => LogLevel::Info,
+            };
+            self.levels.insert(service.to_owned(), level);
+            if level < min {
+                return false;
+            }
+        }
+        self.grep.as_ref().is_none_or(|grep| grep.is_match(line))
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|exclude| exclude.is_match(line))
+    }
+}
+
+/// Guess the level of a log line: the `level`, `lvl` or `severity` field of a JSON line
+/// (numbers as used by pino and bunyan), or else the first level name among the first words.
+#[must_use]
+pub fn detect_level(line: &str) -> Option<LogLevel> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('{') {
+        if let Some(record) = LogRecord::parse(LogFormat::Json, trimmed) {
+            return record.level;
+        }
+    }
+
+    // Words with digits are parts of timestamps, pids and the like.
+    trimmed
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_alphabetic()))
+        .take(5)
+        .find_map(|word| word.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn filter() -> LogFilter {
+        LogFilter {
+            project_id: ProjectId(PathBuf::from("/project")),
+            services: Vec::new(),
+            grep: None,
+            exclude: None,
+            level: None,
+            only_stderr: false,
+            fields: Vec::new(),
+            formats: HashMap::new(),
+        }
+    }
+
+    fn log(service: &str, message: &str, stderr: bool) -> TuttiApi {
+        TuttiApi::Log {
+            project_id: ProjectId(PathBuf::from("/project")),
+            service: service.to_owned(),
+            message: message.to_owned(),
+            stderr,
+        }
+    }
+
+    fn kept(matcher: &mut LogMatcher, mut message: TuttiApi) -> Option<String> {
+        if !matcher.apply(&mut message) {
+            return None;
+        }
+        match message {
+            TuttiApi::Log { message, .. } => Some(message),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_detect_level() {
+        assert_eq!(
+            detect_level("2024-05-01T10:00:00.123Z WARN server: slow request"),
+            Some(LogLevel::Warn)
+        );
+        assert_eq!(
+            detect_level("time=12:00 level=error msg=boom"),
+            Some(LogLevel::Error)
+        );
+        assert_eq!(detect_level("[DEBUG] cache miss"), Some(LogLevel::Debug));
+        assert_eq!(
+            detect_level(r#"{"level":"info","msg":"ready"}"#),
+            Some(LogLevel::Info)
+        );
+        assert_eq!(
+            detect_level(r#"{"level":50,"msg":"failed"}"#),
+            Some(LogLevel::Error)
+        );
+        assert_eq!(detect_level("Listening on port 8080"), None);
+    }
+
+    #[test]
+    fn test_grep_and_exclude() {
+        let mut matcher = LogFilter {
+            grep: Some("GET|POST".to_owned()),
+            exclude: Some("/health".to_owned()),
+            ..filter()
+        }
+        .compile()
+        .unwrap();
+
+        let message = log(
+            "api",
+            "GET /users\nGET /health\nstarting\nPOST /users\n",
+            false,
+        );
+        assert_eq!(
+            kept(&mut matcher, message).as_deref(),
+            Some("GET /users\nPOST /users\n")
+        );
+        assert_eq!(kept(&mut matcher, log("api", "idle\n", false)), None);
+        // Output is passed on as it was written.
+        assert_eq!(
+            kept(&mut matcher, log("api", "GET /users\r\nPOST ", false)).as_deref(),
+            Some("GET /users\r\nPOST ")
+        );
+    }
+
+    #[test]
+    fn test_level_keeps_stack_traces() {
+        let mut matcher = LogFilter {
+            level: Some(LogLevel::Warn),
+            ..filter()
+        }
+        .compile()
+        .unwrap();
+
+        let message = log(
+            "api",
+            "INFO ready\nERROR request failed\n    at handler (app.js:10)\nplain output\n",
+            false,
+        );
+        assert_eq!(
+            kept(&mut matcher, message).as_deref(),
+            Some("ERROR request failed\n    at handler (app.js:10)\n")
+        );
+        // Lines without a level count as errors on stderr.
+        assert_eq!(
+            kept(&mut matcher, log("api", "segfault\n", true)).as_deref(),
+            Some("segfault\n")
+        );
+    }
+
+    #[test]
+    fn test_services_streams_and_projects() {
+        let mut matcher = LogFilter {
+            services: vec!["api".to_owned()],
+            only_stderr: true,
+            ..filter()
+        }
+        .compile()
+        .unwrap();
+
+        assert!(kept(&mut matcher, log("api:pre_start", "oops\n", true)).is_some());
+        assert!(kept(&mut matcher, log("api", "fine\n", false)).is_none());
+        assert!(kept(&mut matcher, log("worker", "oops\n", true)).is_none());
+
+        let mut other = TuttiApi::Log {
+            project_id: ProjectId(PathBuf::from("/other")),
+            service: "api".to_owned(),
+            message: "oops\n".to_owned(),
+            stderr: true,
+        };
+        assert!(!matcher.apply(&mut other));
+
+        let mut stopped = TuttiApi::ProjectStopped {
+            project_id: ProjectId(PathBuf::from("/other")),
+        };
+        assert!(matcher.apply(&mut stopped));
+    }
+
+    #[test]
+    fn test_structured_lines() {
+        let mut matcher = LogFilter {
+            level: Some(LogLevel::Info),
+            fields: vec![("path".to_owned(), "/users".to_owned())],
+            formats: HashMap::from([("api".to_owned(), LogFormat::Logfmt)]),
+            ..filter()
+        }
+        .compile()
+        .unwrap();
+
+        let message = log(
+            "api",
+            "level=info msg=done path=/users\nlevel=debug msg=cache path=/users\nlevel=info path=/health\n",
+            false,
+        );
+        assert_eq!(
+            kept(&mut matcher, message).as_deref(),
+            Some("level=info msg=done path=/users\n")
+        );
+        // Lines that are not logfmt, and the output of hooks, have no fields.
+        assert!(kept(&mut matcher, log("api", "GET /users\n", false)).is_none());
+        assert!(kept(&mut matcher, log("api:pre_start", "path=/users\n", false)).is_none());
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let invalid = LogFilter {
+            grep: Some("(".to_owned()),
+            ..filter()
+        };
+        assert!(invalid.compile().is_err());
+    }
+}
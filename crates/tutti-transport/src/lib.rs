@@ -1,4 +1,6 @@
 pub mod api;
 pub mod client;
 pub mod error;
+pub mod filter;
+pub mod record;
 pub mod server;
@@ -0,0 +1,193 @@
+use serde_json::{Map, Value};
+use tutti_types::{LogFormat, LogLevel};
+
+/// Keys holding the level of a structured line.
+const LEVEL_KEYS: [&str; 3] = ["level", "lvl", "severity"];
+/// Keys holding the message of a structured line.
+const MESSAGE_KEYS: [&str; 2] = ["msg", "message"];
+
+/// A log line parsed according to the `log_format` of its service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub level: Option<LogLevel>,
+    pub message: String,
+    /// Every other field of the line.
+    pub fields: Map<String, Value>,
+}
+
+impl LogRecord {
+    /// Parse `line`, or return `None` if it is not in `format`, e.g. a panic message printed by
+    /// a service that otherwise logs JSON.
+    #[must_use]
+    pub fn parse(format: LogFormat, line: &str) -> Option<Self> {
+        let fields = match format {
+            LogFormat::Text => return None,
+            LogFormat::Json => match serde_json::from_str(line.trim()) {
+                Ok(Value::Object(fields)) => fields,
+                _ => return None,
+            },
+            LogFormat::Logfmt => parse_logfmt(line)?,
+        };
+        Some(Self::from_fields(fields))
+    }
+
+    fn from_fields(mut fields: Map<String, Value>) -> Self {
+        // `tracing-subscriber` nests the message and the fields of an event under `fields`.
+        if let Some(Value::Object(nested)) = fields.remove("fields") {
+            fields.extend(nested);
+        }
+        let level = LEVEL_KEYS
+            .iter()
+            .find_map(|key| fields.remove(*key))
+            .and_then(|value| level_of(&value));
+        let message = MESSAGE_KEYS
+            .iter()
+            .find_map(|key| fields.remove(*key))
+            .map(|value| display(&value))
+            .unwrap_or_default();
+        Self {
+            level,
+            message,
+            fields,
+        }
+    }
+
+    /// Value of a field as it is printed, without quotes for strings.
+    #[must_use]
+    pub fn field(&self, key: &str) -> Option<String> {
+        self.fields.get(key).map(display)
+    }
+}
+
+/// Level named by a field: a name like `warn`, or a number as used by pino and bunyan.
+#[must_use]
+pub fn level_of(value: &Value) -> Option<LogLevel> {
+    match value {
+        Value::String(name) => name.parse().ok(),
+        Value::Number(number) => number.as_u64().map(|number| match number {
+            0..=10 => LogLevel::Trace,
+            11..=20 => LogLevel::Debug,
+            21..=30 => LogLevel::Info,
+            31..=40 => LogLevel::Warn,
+            _ => LogLevel::Error,
+        }),
+        _ => None,
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Split a logfmt line into its fields. Keys without a value are `true`, as in the logfmt
+/// convention. Returns `None` unless the line has at least one `key=value` pair.
+fn parse_logfmt(line: &str) -> Option<Map<String, Value>> {
+    let mut fields = Map::new();
+    let mut has_pairs = false;
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '=') {
+            key.push(c);
+        }
+        if key.is_empty() {
+            return None;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            fields.insert(key, Value::Bool(true));
+            continue;
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => match chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some(escaped) => value.push(escaped),
+                        None => {}
+                    },
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+        fields.insert(key, Value::String(value));
+        has_pairs = true;
+    }
+    has_pairs.then_some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let record = LogRecord::parse(
+            LogFormat::Json,
+            r#"{"level":"warn","ts":1718000000.5,"msg":"slow query","ms":1200,"table":"users"}"#,
+        )
+        .unwrap();
+        assert_eq!(record.level, Some(LogLevel::Warn));
+        assert_eq!(record.message, "slow query");
+        assert_eq!(record.field("ms").as_deref(), Some("1200"));
+        assert_eq!(record.field("table").as_deref(), Some("users"));
+        assert!(record.field("msg").is_none());
+
+        // pino
+        let record = LogRecord::parse(LogFormat::Json, r#"{"level":50,"msg":"failed"}"#).unwrap();
+        assert_eq!(record.level, Some(LogLevel::Error));
+
+        // tracing-subscriber
+        let record = LogRecord::parse(
+            LogFormat::Json,
+            r#"{"timestamp":"2024-06-10T10:00:00Z","level":"INFO","fields":{"message":"listening","port":8080},"target":"api"}"#,
+        )
+        .unwrap();
+        assert_eq!(record.level, Some(LogLevel::Info));
+        assert_eq!(record.message, "listening");
+        assert_eq!(record.fields["port"], json!(8080));
+        assert_eq!(record.fields["target"], json!("api"));
+
+        assert!(LogRecord::parse(LogFormat::Json, "thread 'main' panicked").is_none());
+        assert!(LogRecord::parse(LogFormat::Json, "[1, 2]").is_none());
+    }
+
+    #[test]
+    fn test_parse_logfmt() {
+        let record = LogRecord::parse(
+            LogFormat::Logfmt,
+            r#"time=2024-06-10T10:00:00Z level=error msg="request failed: \"timeout\"" path=/users retry"#,
+        )
+        .unwrap();
+        assert_eq!(record.level, Some(LogLevel::Error));
+        assert_eq!(record.message, r#"request failed: "timeout""#);
+        assert_eq!(record.field("path").as_deref(), Some("/users"));
+        assert_eq!(record.field("retry").as_deref(), Some("true"));
+        assert_eq!(
+            record.field("time").as_deref(),
+            Some("2024-06-10T10:00:00Z")
+        );
+
+        assert!(LogRecord::parse(LogFormat::Logfmt, "Starting server").is_none());
+        assert!(LogRecord::parse(LogFormat::Logfmt, "a == b").is_none());
+        assert!(LogRecord::parse(LogFormat::Text, "level=info").is_none());
+    }
+}
@@ -79,8 +79,8 @@ mod tests {
         let mut fanout = Fanout::new();
         let (tx1, mut rx1) = mpsc::channel(8);
         let (tx2, mut rx2) = mpsc::channel(8);
-        let (tx3, _rx3) = mpsc::channel::<&'static str>(8);
-        drop(_rx3);
+        let (tx3, rx3) = mpsc::channel::<&'static str>(8);
+        drop(rx3);
 
         fanout.subscribe(tx1);
         fanout.subscribe(tx2);
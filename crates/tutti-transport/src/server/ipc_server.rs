@@ -1,4 +1,8 @@
-use std::{fmt::Debug, path::PathBuf, sync::Arc};
+use std::{
+    fmt::Debug,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use bytes::Bytes;
 use futures_util::{future::BoxFuture, SinkExt, StreamExt};
@@ -11,6 +15,7 @@ use tokio_util::codec::{Framed, LengthDelimitedCodec};
 use crate::{
     api::{MessageType, TuttiApi, TuttiMessage},
     error::{TransportError, TransportResult},
+    filter::{LogFilter, LogMatcher},
     server::fanout::Fanout,
 };
 
@@ -112,15 +117,25 @@ impl<C: Clone + Debug + Send + Sync + 'static> IpcServer<C> {
                 guard.subscribe(tx_clone);
             }
 
+            // Set by the connection with `FilterLogs`, applied to the stream messages it gets.
+            let log_filter = Arc::new(Mutex::new(None::<LogMatcher>));
+
             let unary_handler = self.unary_handler.clone();
             let context = self.context.clone();
+            let log_filter_clone = log_filter.clone();
             tokio::spawn(async move {
                 while let Some(Ok(body)) = stream.next().await {
                     let Ok(message) = serde_json::from_slice::<TuttiMessage>(&body) else {
                         continue;
                     };
-                    let Ok(response) = (unary_handler)(message.body, context.clone()).await else {
-                        continue;
+                    let response = if let TuttiApi::FilterLogs { filter } = message.body {
+                        set_log_filter(&log_filter_clone, filter)
+                    } else {
+                        let Ok(response) = (unary_handler)(message.body, context.clone()).await
+                        else {
+                            continue;
+                        };
+                        response
                     };
 
                     let full_response = TuttiMessage {
@@ -133,7 +148,16 @@ impl<C: Clone + Debug + Send + Sync + 'static> IpcServer<C> {
             });
 
             tokio::spawn(async move {
-                while let Some(message) = rx.recv().await {
+                while let Some(mut message) = rx.recv().await {
+                    if message.req_type == MessageType::Stream {
+                        if let Ok(mut guard) = log_filter.lock() {
+                            if let Some(matcher) = guard.as_mut() {
+                                if !matcher.apply(&mut message.body) {
+                                    continue;
+                                }
+                            }
+                        }
+                    }
                     let Ok(serialized_response) = serde_json::to_vec(&message) else {
                         continue;
                     };
@@ -143,3 +167,19 @@ impl<C: Clone + Debug + Send + Sync + 'static> IpcServer<C> {
         }
     }
 }
+
+fn set_log_filter(log_filter: &Mutex<Option<LogMatcher>>, filter: LogFilter) -> TuttiApi {
+    let project_id = filter.project_id.clone();
+    match filter.compile() {
+        Ok(matcher) => {
+            if let Ok(mut guard) = log_filter.lock() {
+                *guard = Some(matcher);
+            }
+            TuttiApi::Pong
+        }
+        Err(err) => TuttiApi::Error {
+            project_id,
+            message: format!("Invalid log filter: {err}"),
+        },
+    }
+}
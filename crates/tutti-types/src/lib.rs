@@ -2,6 +2,7 @@ use std::{
     collections::{BTreeMap, HashMap},
     fmt::Display,
     path::PathBuf,
+    str::FromStr,
 };
 
 use serde::{Deserialize, Serialize};
@@ -20,25 +21,378 @@ pub struct Project {
     pub version: u32,
     pub id: ProjectId,
     pub services: BTreeMap<String, Service>,
+    /// Named sets of services, selected with `@name`.
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum Restart {
     Always,
     #[default]
     Never,
 }
 
+/// Which variables of the client environment a service receives.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InheritEnv {
+    #[default]
+    All,
+    None,
+    Only(Vec<String>),
+}
+
+impl InheritEnv {
+    /// Whether the variable `name` should be passed to the service.
+    #[must_use]
+    pub fn allows(&self, name: &str) -> bool {
+        match self {
+            InheritEnv::All => true,
+            InheritEnv::None => false,
+            InheritEnv::Only(names) => names.iter().any(|allowed| allowed == name),
+        }
+    }
+}
+
+/// Point in a service's lifetime at which a hook runs.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HookKind {
+    /// Before the service is spawned. A failure prevents the service from starting.
+    PreStart,
+    /// Once the service is healthy.
+    PostStart,
+    /// Before the service is stopped.
+    PreStop,
+    /// After the service has exited.
+    PostStop,
+}
+
+impl HookKind {
+    pub const ALL: [HookKind; 4] = [
+        HookKind::PreStart,
+        HookKind::PostStart,
+        HookKind::PreStop,
+        HookKind::PostStop,
+    ];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HookKind::PreStart => "pre_start",
+            HookKind::PostStart => "post_start",
+            HookKind::PreStop => "pre_stop",
+            HookKind::PostStop => "post_stop",
+        }
+    }
+}
+
+impl Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What happens when a hook exits with a non-zero code or times out.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HookFailure {
+    #[default]
+    Fail,
+    Continue,
+}
+
+/// A command run in the service's working directory and environment.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Hook {
+    pub cmd: Vec<String>,
+    /// Seconds after which the hook is killed and considered failed.
+    pub timeout: u64,
+    pub on_failure: HookFailure,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_start: Option<Hook>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_start: Option<Hook>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_stop: Option<Hook>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_stop: Option<Hook>,
+}
+
+impl Hooks {
+    #[must_use]
+    pub fn get(&self, kind: HookKind) -> Option<&Hook> {
+        match kind {
+            HookKind::PreStart => self.pre_start.as_ref(),
+            HookKind::PostStart => self.post_start.as_ref(),
+            HookKind::PreStop => self.pre_stop.as_ref(),
+            HookKind::PostStop => self.post_stop.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: HookKind) -> &mut Option<Hook> {
+        match kind {
+            HookKind::PreStart => &mut self.pre_start,
+            HookKind::PostStart => &mut self.post_start,
+            HookKind::PreStop => &mut self.pre_stop,
+            HookKind::PostStop => &mut self.post_stop,
+        }
+    }
+}
+
+/// Resource limits that can be set for a service, named like `ulimit`'s `RLIMIT_*` resources.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Rlimit {
+    /// Open file descriptors.
+    Nofile,
+    /// Processes of the user.
+    Nproc,
+    /// Size of core dumps in bytes.
+    Core,
+    /// Address space in bytes.
+    As,
+}
+
+impl Rlimit {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Rlimit::Nofile => "nofile",
+            Rlimit::Nproc => "nproc",
+            Rlimit::Core => "core",
+            Rlimit::As => "as",
+        }
+    }
+}
+
+impl Display for Rlimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Value of a resource limit, used as both the soft and the hard limit.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RlimitValue {
+    Unlimited,
+    #[serde(untagged)]
+    Limited(u64),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Service {
     pub cmd: Vec<String>,
     pub cwd: Option<PathBuf>,
     pub env: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub inherit_env: InheritEnv,
+    /// Services with profiles only start by default when one of their profiles is active.
+    #[serde(default)]
+    pub profiles: Vec<String>,
     pub deps: Vec<String>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// User (name or uid) to run the service as.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Group (name or gid) to run the service as, defaults to the user's primary group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub umask: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub rlimits: BTreeMap<Rlimit, RlimitValue>,
+    /// Run the service on a pseudo-terminal instead of pipes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tty: bool,
+    /// Keep the service's stdin open, so input can be sent to it with `tutti attach`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stdin_open: bool,
+    /// How the lines the service prints are parsed into a level, a message and fields.
+    #[serde(default, skip_serializing_if = "LogFormat::is_text")]
+    pub log_format: LogFormat,
+    /// Resident memory in bytes the service's process tree may use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<u64>,
+    /// CPU usage in percent of one core the service's process tree may use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu_percent: Option<u32>,
+    /// Seconds a limit may be exceeded before the service is stopped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_grace_period: Option<u64>,
     pub healthcheck: Option<()>, // TODO
     pub restart: Restart,
 }
 
+/// Format of the lines a service prints.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Free-form text, printed as is.
+    #[default]
+    Text,
+    /// One JSON object per line, as written by pino, zap or `tracing-subscriber`.
+    Json,
+    /// `key=value` pairs.
+    Logfmt,
+}
+
+impl LogFormat {
+    #[must_use]
+    pub fn is_text(&self) -> bool {
+        matches!(self, LogFormat::Text)
+    }
+}
+
+/// Size of a terminal in characters.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WindowSize {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+/// Resource usage of a service's process tree: its main process and everything it started.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProcessStats {
+    /// Pid of the service's main process.
+    pub pid: u32,
+    /// CPU time used since the previous sample, in percent of one core. The first sample covers
+    /// the lifetime of the main process.
+    pub cpu_percent: f64,
+    /// Resident memory in bytes.
+    pub memory_rss: u64,
+    pub threads: u64,
+    pub open_fds: u64,
+    /// Processes in the tree besides the main process.
+    pub children: u64,
+}
+
+/// Resource usage of one running service.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceStats {
+    pub project_id: ProjectId,
+    pub service: String,
+    pub stats: ProcessStats,
+}
+
+/// What a service of a project is doing.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceState {
+    /// Waiting for its dependencies to become healthy.
+    Waiting,
+    /// Spawned but not healthy yet.
+    Starting,
+    Running,
+    Stopped,
+}
+
+impl ServiceState {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ServiceState::Waiting => "waiting",
+            ServiceState::Starting => "starting",
+            ServiceState::Running => "running",
+            ServiceState::Stopped => "stopped",
+        }
+    }
+}
+
+/// Status of one service of a project.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ServiceStatus {
+    pub service: String,
+    pub state: ServiceState,
+    /// Pid of the service's main process, if the platform reports one.
+    pub pid: Option<u32>,
+    /// Seconds since the current process of the service was spawned.
+    pub uptime: Option<u64>,
+    /// How many times the service was restarted, by its restart policy or on request.
+    pub restarts: u32,
+    /// How the last process of the service ended, once the daemon saw one exit.
+    #[serde(default)]
+    pub last_exit: Option<ServiceExit>,
+}
+
+/// How a process of a service ended.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ServiceExit {
+    /// Exit code of the process, `None` if it is unknown, such as for a process killed by a
+    /// signal.
+    pub code: Option<i32>,
+}
+
+/// What the daemon reports about itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub version: String,
+    /// Seconds since the daemon was started.
+    pub uptime: u64,
+    /// Projects that have services running.
+    pub projects: Vec<ProjectId>,
+}
+
+/// Severity of a log line, from the least to the most severe.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Trace => "trace",
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Warn => "warn",
+            LogLevel::Error => "error",
+        }
+    }
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses the level names used by common loggers, in any case: `warning` is `warn`, and
+/// `fatal`, `critical` and `panic` are `error`.
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trace" | "trc" => Ok(LogLevel::Trace),
+            "debug" | "dbg" => Ok(LogLevel::Debug),
+            "info" | "inf" | "notice" => Ok(LogLevel::Info),
+            "warn" | "wrn" | "warning" => Ok(LogLevel::Warn),
+            "error" | "err" | "fatal" | "critical" | "crit" | "panic" | "alert" | "emerg" => {
+                Ok(LogLevel::Error)
+            }
+            _ => Err(format!("unknown log level `{s}`")),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -48,4 +402,23 @@ mod tests {
         let project_id = ProjectId(PathBuf::from("/path/to/project"));
         assert_eq!(project_id.to_string(), "/path/to/project");
     }
+
+    #[test]
+    fn test_inherit_env_allows() {
+        assert!(InheritEnv::All.allows("PATH"));
+        assert!(!InheritEnv::None.allows("PATH"));
+
+        let only = InheritEnv::Only(vec!["PATH".to_owned(), "HOME".to_owned()]);
+        assert!(only.allows("HOME"));
+        assert!(!only.allows("SECRET"));
+    }
+
+    #[test]
+    fn test_log_level_from_str() {
+        assert_eq!("WARNING".parse(), Ok(LogLevel::Warn));
+        assert_eq!("fatal".parse(), Ok(LogLevel::Error));
+        assert_eq!("Info".parse(), Ok(LogLevel::Info));
+        assert!("loud".parse::<LogLevel>().is_err());
+        assert!(LogLevel::Debug < LogLevel::Warn);
+    }
 }